@@ -1,14 +1,32 @@
 use clap::Parser;
 
+use crate::lang::compiler::schema::Document;
+
 
 /// Argument parser using `clap`
 #[derive(Parser, Debug)]
 #[command(name = "lithc")]
 pub(crate) struct Args {
-    #[arg(short, long)]
+    #[arg(short, long, required_unless_present_any = ["from_ast", "schema"], default_value = "")]
     pub(crate) input: String,
+    /// Compiles an AST in the JSON format printed by `--debug`, instead of a source file.
+    #[arg(long = "from-ast", conflicts_with = "input")]
+    pub(crate) from_ast: Option<String>,
     #[arg(short, long, required = false, default_value = "")]
     pub(crate) output: String,
     #[arg(short, long, required = false)]
     pub(crate) debug: bool,
+    /// Prints the tokens of the input as JSON.
+    #[arg(long, required = false)]
+    pub(crate) tokens: bool,
+    /// Prints the concrete syntax tree of the input, with whitespace and comments, followed by an
+    /// outline of its declarations.
+    #[arg(long, required = false)]
+    pub(crate) cst: bool,
+    /// Prints the JSON Schema of the AST or token format, then exits.
+    #[arg(long, value_enum)]
+    pub(crate) schema: Option<Document>,
+    /// Enables `#[cfg(...)]` or `@cfg(...)` gated declarations, such as `--cfg debug` or `--cfg feature=x`.
+    #[arg(long = "cfg", required = false)]
+    pub(crate) cfg: Vec<String>,
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::lang::util::vec::UnshiftExpect;
+
+use super::{
+    cst::SyntaxKind,
+    expression::Expression,
+    token::{Span, TokenStream, TokenType},
+    CompilerResult,
+};
+
+/// Attributes the compiler knows about. Any other name is reported as a warning.
+pub(crate) const KNOWN_ATTRIBUTES: &[&str] = &["cfg", "deprecated", "index", "inline", "test"];
+
+/// An annotation such as `#[deprecated("use other")]` placed before a declaration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Attribute {
+    pub(crate) name: String,
+    pub(crate) arguments: Vec<Expression>,
+    pub(crate) span: Span,
+}
+
+impl Attribute {
+    /// Whether the next tokens start an attribute, either `#[...]` or the `@cfg(...)` shorthand.
+    pub(crate) fn starts(tokens: &TokenStream) -> bool {
+        match tokens.peek().map(|token| token.type_) {
+            Some(TokenType::Hash) => true,
+            Some(TokenType::At) => tokens.peek_nth(1).is_some_and(|name| name.value == "cfg"),
+            _ => false,
+        }
+    }
+
+    /// Parses any number of consecutive `#[name]`, `#[name(args)]` or `@cfg(args)` attributes.
+    pub(crate) fn parse_many(tokens: &mut TokenStream) -> CompilerResult<Vec<Attribute>> {
+        let mut attributes = Vec::new();
+
+        while Attribute::starts(tokens) {
+            if tokens.next_matches(TokenType::At) {
+                attributes.push(Attribute::cfg(tokens)?);
+            } else {
+                attributes.push(Attribute::parse(tokens)?);
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Parses `@cfg(predicate)`, which is the same as `#[cfg(predicate)]`.
+    fn cfg(tokens: &mut TokenStream) -> CompilerResult<Attribute> {
+        tokens.start_node(SyntaxKind::Attribute);
+        let start = tokens.unshift_expect(TokenType::At)?.span.start;
+        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
+        let (arguments, end) = Attribute::arguments(tokens)?;
+        tokens.finish_node();
+
+        Ok(Attribute {
+            name,
+            arguments,
+            span: start..end,
+        })
+    }
+
+    fn parse(tokens: &mut TokenStream) -> CompilerResult<Attribute> {
+        tokens.start_node(SyntaxKind::Attribute);
+        let start = tokens.unshift_expect(TokenType::Hash)?.span.start;
+        tokens.unshift_expect(TokenType::LeftBracket)?;
+
+        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
+        let arguments = if tokens.next_matches(TokenType::LeftParen) {
+            Attribute::arguments(tokens)?.0
+        } else {
+            Vec::new()
+        };
+
+        let end = tokens.unshift_expect(TokenType::RightBracket)?.span.end;
+        tokens.finish_node();
+
+        Ok(Attribute {
+            name,
+            arguments,
+            span: start..end,
+        })
+    }
+
+    /// Parses `(a, b)` and returns the arguments along with the end of the closing paren.
+    fn arguments(tokens: &mut TokenStream) -> CompilerResult<(Vec<Expression>, usize)> {
+        tokens.start_node(SyntaxKind::ArgumentList);
+        tokens.unshift_expect(TokenType::LeftParen)?;
+
+        let mut arguments = Vec::new();
+        while !tokens.next_matches(TokenType::RightParen) {
+            arguments.push(Expression::parse(tokens)?);
+
+            if !tokens.next_matches(TokenType::RightParen) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
+        }
+
+        let end = tokens.unshift_expect(TokenType::RightParen)?.span.end;
+        tokens.finish_node();
+        Ok((arguments, end))
+    }
+}
@@ -0,0 +1,291 @@
+use super::{
+    attribute::Attribute,
+    expression::{Expression, ExpressionType, LiteralExpression, LiteralType},
+    parser::AST,
+    statement::{
+        BlockStatement, ConstStatement, EnumStatement, FunctionStatement, LetStatement, Statement,
+        StatementType, StructStatement,
+    },
+    token::{Token, TokenType},
+    visit::{walk_ast_mut, walk_block_mut, VisitorMut},
+    CompilerError, CompilerResult, ErrorCode,
+};
+
+/// The options set with `--cfg`, which `#[cfg(...)]` attributes on declarations are checked against.
+///
+/// `--cfg debug` sets a flag, checked with `#[cfg(debug)]`, and `--cfg feature=x` sets a
+/// key and value, checked with `#[cfg(feature = "x")]`. Predicates can be combined with
+/// `not(...)`, `all(...)` and `any(...)`. `@cfg(...)` is another spelling of `#[cfg(...)]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Cfg {
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Cfg {
+    /// Parses `--cfg` flags such as `debug` or `feature=x`. Quotes around the value are optional.
+    pub(crate) fn from_flags(flags: &[String]) -> Cfg {
+        let options = flags
+            .iter()
+            .map(|flag| match flag.split_once('=') {
+                Some((key, value)) => (
+                    String::from(key.trim()),
+                    Some(String::from(value.trim().trim_matches('"'))),
+                ),
+                None => (String::from(flag.trim()), None),
+            })
+            .collect();
+
+        Cfg { options }
+    }
+
+    /// Removes the declarations whose `#[cfg(...)]` attributes don't hold, at the top level and
+    /// in nested blocks, and reports malformed predicates.
+    pub(crate) fn strip(&self, ast: &mut AST) -> Vec<CompilerError> {
+        let mut strip = Strip {
+            cfg: self,
+            errors: Vec::new(),
+        };
+        strip.visit_ast_mut(ast);
+        strip.errors
+    }
+
+    /// Whether every `#[cfg(...)]` attribute of a declaration holds.
+    fn enabled(&self, statement: &StatementType, errors: &mut Vec<CompilerError>) -> bool {
+        let attributes = match statement {
+            StatementType::Const(ConstStatement { attributes, .. })
+            | StatementType::Enum(EnumStatement { attributes, .. })
+            | StatementType::Function(FunctionStatement { attributes, .. })
+            | StatementType::Let(LetStatement { attributes, .. })
+            | StatementType::Struct(StructStatement { attributes, .. }) => attributes,
+            _ => return true,
+        };
+
+        let mut enabled = true;
+        for attribute in attributes
+            .iter()
+            .filter(|attribute| attribute.name == "cfg")
+        {
+            match self.attribute(attribute) {
+                Ok(holds) => enabled &= holds,
+                Err(error) => errors.push(*error),
+            }
+        }
+
+        enabled
+    }
+
+    fn attribute(&self, attribute: &Attribute) -> CompilerResult<bool> {
+        match attribute.arguments.as_slice() {
+            [predicate] => self.predicate(predicate),
+            _ => Err(Box::new(CompilerError {
+                error_code: ErrorCode::InvalidCfg,
+                error_message: format!(
+                    "'cfg' expects a single predicate, got {}",
+                    attribute.arguments.len()
+                ),
+                span_message: String::from("Expected one predicate"),
+                token: Token {
+                    type_: TokenType::Identifier,
+                    value: attribute.name.clone(),
+                    span: attribute.span.clone(),
+                },
+                help: Some(String::from(PREDICATE_HELP)),
+                info: None,
+            })),
+        }
+    }
+
+    fn predicate(&self, predicate: &Expression) -> CompilerResult<bool> {
+        match &predicate.kind {
+            ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) => Ok(self
+                .options
+                .iter()
+                .any(|(key, option)| key == value && option.is_none())),
+            ExpressionType::Assignment(assignment) => {
+                match (&assignment.target.kind, &assignment.value.kind) {
+                    (
+                        ExpressionType::Literal(LiteralExpression {
+                            literal: LiteralType::Unknown,
+                            value: key,
+                        }),
+                        ExpressionType::Literal(LiteralExpression {
+                            literal: LiteralType::String,
+                            value,
+                        }),
+                    ) => Ok(self
+                        .options
+                        .iter()
+                        .any(|option| option.0 == *key && option.1.as_ref() == Some(value))),
+                    _ => Err(Cfg::invalid(predicate)),
+                }
+            }
+            ExpressionType::FunctionCall(call) => {
+                let ExpressionType::Literal(LiteralExpression { value: name, .. }) =
+                    &call.callee.kind
+                else {
+                    return Err(Cfg::invalid(predicate));
+                };
+
+                let mut results = Vec::new();
+                for argument in &call.arguments {
+                    if argument.name.is_some() {
+                        return Err(Cfg::invalid(&argument.value));
+                    }
+                    results.push(self.predicate(&argument.value)?);
+                }
+
+                match (name.as_str(), results.as_slice()) {
+                    ("not", [holds]) => Ok(!holds),
+                    ("all", results) => Ok(results.iter().all(|holds| *holds)),
+                    ("any", results) => Ok(results.iter().any(|holds| *holds)),
+                    _ => Err(Cfg::invalid(predicate)),
+                }
+            }
+            _ => Err(Cfg::invalid(predicate)),
+        }
+    }
+
+    fn invalid(predicate: &Expression) -> Box<CompilerError> {
+        Box::new(CompilerError {
+            error_code: ErrorCode::InvalidCfg,
+            error_message: String::from("Invalid 'cfg' predicate"),
+            span_message: String::from("This is not a cfg predicate"),
+            token: Token {
+                type_: TokenType::Identifier,
+                value: String::from(predicate.root_identifier().unwrap_or("")),
+                span: predicate.span.clone(),
+            },
+            help: Some(String::from(PREDICATE_HELP)),
+            info: None,
+        })
+    }
+}
+
+/// Drops disabled declarations from every statement list in the tree.
+struct Strip<'c> {
+    cfg: &'c Cfg,
+    errors: Vec<CompilerError>,
+}
+
+impl Strip<'_> {
+    fn retain(&mut self, statements: &mut Vec<Statement>) {
+        statements.retain(|statement| self.cfg.enabled(&statement.kind, &mut self.errors));
+    }
+}
+
+impl VisitorMut for Strip<'_> {
+    fn visit_ast_mut(&mut self, ast: &mut AST) {
+        self.retain(&mut ast.statements);
+        walk_ast_mut(self, ast);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockStatement) {
+        self.retain(&mut block.statements);
+        walk_block_mut(self, block);
+    }
+}
+
+const PREDICATE_HELP: &str =
+    "Predicates look like `debug`, `feature = \"x\"`, `not(debug)`, `all(a, b)` or `any(a, b)`";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{lexer::Lexer, node::NodeIds, parser::Parser};
+    use crate::tests::before_each;
+
+    fn strip(input: &str, flags: &[&str]) -> (Vec<String>, Vec<ErrorCode>) {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let mut ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+
+        let flags: Vec<String> = flags.iter().map(|flag| String::from(*flag)).collect();
+        let errors = Cfg::from_flags(&flags).strip(&mut ast);
+
+        let names = ast
+            .statements
+            .iter()
+            .filter_map(|statement| statement.kind.declared_name())
+            .map(String::from)
+            .collect();
+
+        (
+            names,
+            errors.into_iter().map(|error| error.error_code).collect(),
+        )
+    }
+
+    #[test]
+    fn test_cfg_predicates() {
+        before_each();
+        let input = r#"
+            #[cfg(debug)] const A = 1;
+            #[cfg(feature = "postgres")] const B = 2;
+            #[cfg(not(debug))] const C = 3;
+            #[cfg(any(debug, feature = "sqlite"))] const D = 4;
+            #[cfg(all(debug, feature = "sqlite"))] const E = 5;
+            const F = 6;
+        "#;
+
+        assert_eq!(
+            strip(input, &["debug", "feature=\"postgres\""]),
+            (
+                vec!["A", "B", "D", "F"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                vec![]
+            )
+        );
+        assert_eq!(
+            strip(input, &["feature=sqlite"]),
+            (
+                vec!["C", "D", "F"].into_iter().map(String::from).collect(),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn test_cfg_nested_and_invalid() {
+        before_each();
+        let (names, errors) = strip(
+            "function f() { #[cfg(debug)] let x = 1; } #[cfg(1)] const A = 1; #[cfg] const B = 2;",
+            &[],
+        );
+        assert_eq!(names, vec!["f", "A", "B"]);
+        assert_eq!(errors, vec![ErrorCode::InvalidCfg, ErrorCode::InvalidCfg]);
+
+        let mut lexer = Lexer::new();
+        lexer.lex("function f() { #[cfg(debug)] let x = 1; let y = 2; }");
+        let mut ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+        Cfg::default().strip(&mut ast);
+
+        let StatementType::Function(function) = &ast.statements[0].kind else {
+            panic!("Expected function");
+        };
+        assert_eq!(function.body.statements.len(), 1);
+    }
+    #[test]
+    fn test_cfg_shorthand() {
+        before_each();
+        let input = r#"
+            @cfg(feature = "x") const A = 1;
+            @cfg(not(debug)) #[inline] function b() {}
+            function c() { @cfg(debug) let x = 1; @cfg(feature = "x") let y = 2; }
+        "#;
+
+        let (names, errors) = strip(input, &["feature=x"]);
+        assert_eq!(names, vec!["A", "b", "c"]);
+        assert!(errors.is_empty());
+
+        let (names, errors) = strip(input, &["debug"]);
+        assert_eq!(names, vec!["c"]);
+        assert!(errors.is_empty());
+    }
+}
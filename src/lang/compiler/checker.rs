@@ -0,0 +1,2542 @@
+use std::collections::HashMap;
+
+use super::{
+    attribute::{Attribute, KNOWN_ATTRIBUTES},
+    exhaustiveness::{self, Enums, Variant},
+    expression::{
+        Argument, BinaryExpression, ClosureBody, Expression, ExpressionType, IntrinsicExpression,
+        LiteralExpression, LiteralType, MatchExpression, MemberExpression, RecordExpression,
+        RecordField, StructExpression,
+    },
+    intrinsic::IntrinsicRegistry,
+    parser::AST,
+    pattern::{Pattern, PatternType},
+    statement::{
+        BlockStatement, EnumStatement, FunctionStatement, Parameter, Statement, StatementType,
+        StructStatement, TypeParameter,
+    },
+    token::{Span, Token, TokenType},
+    types::{Constraint, NamedType, ParameterType, TypeExpr, TypeExprType},
+    CompilerError, ErrorCode,
+};
+use crate::lang::util::suggest;
+
+/// Semantic checks that need every declaration in the file to be known,
+/// such as struct names used before they are declared.
+pub(crate) struct Checker<'a> {
+    structs: HashMap<&'a str, &'a StructStatement>,
+    enums: Enums<'a>,
+    intrinsics: &'a IntrinsicRegistry,
+    scopes: Vec<Vec<Variable<'a>>>,
+    /// The declared return types of the functions and closures being checked, innermost last.
+    returns: Vec<ParameterType>,
+    /// The element types of the generators being checked, or `None` for functions and
+    /// closures that aren't generators, innermost last.
+    yields: Vec<Option<ParameterType>>,
+    /// The type parameters of the generic functions being checked, innermost last.
+    generics: Vec<&'a [TypeParameter]>,
+    errors: Vec<CompilerError>,
+}
+
+/// A name in scope, with the type of its value as far as it is known.
+#[derive(Debug, Clone)]
+struct Variable<'a> {
+    name: &'a str,
+    binding: Binding,
+    type_: ParameterType,
+    /// The declaration of a function, which call arguments are matched against.
+    function: Option<&'a FunctionStatement>,
+}
+
+/// How a name was bound, which decides whether it can be assigned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    Const,
+    Function,
+    Immutable,
+    Mutable,
+    Parameter,
+}
+
+impl<'a> Checker<'a> {
+    /// Checks the whole AST and returns every error found, in source order.
+    ///
+    /// `imports` are the declarations this file imports from other modules,
+    /// and `intrinsics` are the `@name(...)` calls that may be used.
+    pub(crate) fn check(
+        ast: &'a AST,
+        imports: &[&'a StatementType],
+        intrinsics: &'a IntrinsicRegistry,
+    ) -> Vec<CompilerError> {
+        let mut checker = Checker {
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            intrinsics,
+            scopes: vec![Vec::new()],
+            returns: Vec::new(),
+            yields: Vec::new(),
+            generics: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        for import in imports {
+            if let StatementType::Struct(struct_) = import {
+                checker.structs.insert(&struct_.name, struct_);
+            }
+        }
+
+        for statement in &ast.statements {
+            if let StatementType::Struct(struct_) = &statement.kind {
+                if checker.structs.insert(&struct_.name, struct_).is_some() {
+                    checker.error(
+                        ErrorCode::DuplicateStructDeclaration,
+                        format!("Struct '{}' is declared more than once", struct_.name),
+                        String::from("This struct was already declared"),
+                        &struct_.name,
+                        statement.span.clone(),
+                        Some(String::from("Rename one of the structs")),
+                    );
+                }
+            }
+        }
+
+        // The first declaration of a name wins, and later ones are reported
+        let mut enums: Vec<&EnumStatement> = Vec::new();
+
+        for import in imports {
+            if let StatementType::Enum(enum_) = import {
+                checker.enums.insert(&enum_.name, Vec::new());
+                enums.push(enum_);
+            }
+        }
+
+        for statement in &ast.statements {
+            if let StatementType::Enum(enum_) = &statement.kind {
+                let name = enum_.name.as_str();
+                if checker.structs.contains_key(name) || checker.enums.contains_key(name) {
+                    checker.error(
+                        ErrorCode::DuplicateEnumDeclaration,
+                        format!("Enum '{}' is declared more than once", name),
+                        String::from("A struct or enum with this name was already declared"),
+                        name,
+                        statement.span.clone(),
+                        Some(String::from("Rename one of the declarations")),
+                    );
+                } else {
+                    checker.enums.insert(name, Vec::new());
+                    enums.push(enum_);
+                }
+            }
+        }
+
+        // Types can only be resolved once every struct and enum is known
+        for enum_ in enums {
+            let variants = checker.variants(enum_);
+            checker.enums.insert(&enum_.name, variants);
+        }
+
+        for import in imports {
+            match import {
+                StatementType::Const(const_) => {
+                    let type_ = checker.declared_type(&const_.type_);
+                    checker.bind(&const_.name, Binding::Const, type_);
+                }
+                StatementType::Function(function) => checker.bind_function(function),
+                // Other modules can read an exported `let`, but not assign to it
+                StatementType::Let(let_) => {
+                    let type_ = checker.declared_type(&let_.type_);
+                    checker.bind(&let_.name, Binding::Immutable, type_);
+                }
+                _ => {}
+            }
+        }
+
+        // Functions can be called before they are declared
+        for statement in &ast.statements {
+            if let StatementType::Function(function) = &statement.kind {
+                checker.bind_function(function);
+            }
+        }
+
+        checker.statements(&ast.statements);
+        checker.errors
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) {
+        for statement in statements {
+            self.statement(&statement.kind);
+        }
+    }
+
+    fn block(&mut self, block: &'a BlockStatement) {
+        self.scopes.push(Vec::new());
+        self.statements(&block.statements);
+        if let Some(tail) = &block.tail {
+            self.expression(tail);
+        }
+        self.scopes.pop();
+    }
+
+    /// Checks a function or closure body, whose tail value is returned.
+    fn body(&mut self, body: &'a BlockStatement) {
+        self.scopes.push(Vec::new());
+        self.statements(&body.statements);
+        if let Some(tail) = &body.tail {
+            self.expression(tail);
+
+            // A generator ends without a value, even if it ends with an `if` or a block
+            if !self.in_generator() {
+                self.returned(tail);
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn in_generator(&self) -> bool {
+        matches!(self.yields.last(), Some(Some(_)))
+    }
+
+    fn returned(&mut self, value: &Expression) {
+        if self.in_generator() {
+            self.error(
+                ErrorCode::InvalidReturnType,
+                String::from("Generators can't return a value"),
+                String::from("Returned from a generator"),
+                value.root_identifier().unwrap_or(""),
+                value.span.clone(),
+                Some(String::from(
+                    "Use `yield` to produce a value, and `return;` to stop early",
+                )),
+            );
+            return;
+        }
+
+        if let Some(expected) = self.returns.last().cloned() {
+            self.non_null(value, &expected);
+        }
+    }
+
+    fn bind(&mut self, name: &'a str, binding: Binding, type_: ParameterType) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Variable {
+                name,
+                binding,
+                type_,
+                function: None,
+            });
+        }
+    }
+
+    fn bind_function(&mut self, function: &'a FunctionStatement) {
+        let type_ = self.function_type(function);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Variable {
+                name: &function.name,
+                binding: Binding::Function,
+                type_,
+                function: Some(function),
+            });
+        }
+    }
+
+    /// Binds the parameters in order, so a default value can refer to the parameters before it.
+    fn parameters(&mut self, parameters: &'a [Parameter]) {
+        let mut defaulted = false;
+
+        for (index, parameter) in parameters.iter().enumerate() {
+            let mut type_ = self.optional_type(&parameter.type_, ErrorCode::InvalidParameterType);
+
+            if parameter.variadic {
+                self.variadic(parameter, &type_, index + 1 == parameters.len());
+            } else if let Some(default) = &parameter.default {
+                defaulted = true;
+                self.expression(default);
+                self.non_null(default, &type_);
+
+                if type_ == ParameterType::Unknown {
+                    type_ = self.value_type(default);
+                }
+            } else if defaulted {
+                self.error(
+                    ErrorCode::InvalidParameterOrder,
+                    format!(
+                        "Parameter '{}' has no default value, but follows one that does",
+                        parameter.name
+                    ),
+                    String::from("Required parameter after a parameter with a default"),
+                    &parameter.name,
+                    parameter.span.clone(),
+                    Some(String::from(
+                        "Move required parameters before the ones with default values",
+                    )),
+                );
+            }
+
+            self.bind(&parameter.name, Binding::Parameter, type_);
+        }
+    }
+
+    fn variadic(&mut self, parameter: &Parameter, type_: &ParameterType, last: bool) {
+        if !last {
+            self.error(
+                ErrorCode::InvalidParameterOrder,
+                format!(
+                    "Variadic parameter '{}' must be the last parameter",
+                    parameter.name
+                ),
+                String::from("Parameters follow this variadic parameter"),
+                &parameter.name,
+                parameter.span.clone(),
+                None,
+            );
+        }
+
+        if parameter.default.is_some() {
+            self.error(
+                ErrorCode::InvalidParameterType,
+                format!(
+                    "Variadic parameter '{}' can't have a default value",
+                    parameter.name
+                ),
+                String::from("Default value on a variadic parameter"),
+                &parameter.name,
+                parameter.span.clone(),
+                Some(String::from(
+                    "It is an empty list when no arguments are left over",
+                )),
+            );
+        }
+
+        if !matches!(type_, ParameterType::List(_) | ParameterType::Unknown) {
+            self.error(
+                ErrorCode::InvalidParameterType,
+                format!(
+                    "Variadic parameter '{}' should be a list, got {}",
+                    parameter.name, type_
+                ),
+                String::from("Variadic parameters collect their arguments into a list"),
+                &parameter.name,
+                parameter.span.clone(),
+                Some(format!(
+                    "Declare it as `...{}: list<{}>`",
+                    parameter.name, type_
+                )),
+            );
+        }
+    }
+
+    /// Finds the innermost variable called `name`, if it was declared in this file.
+    fn variable(&self, name: &str) -> Option<&Variable<'a>> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|variable| variable.name == name)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.variable(name).map(|variable| variable.binding)
+    }
+
+    fn statement(&mut self, statement: &'a StatementType) {
+        match statement {
+            StatementType::Unknown | StatementType::Import(_) => {}
+            StatementType::Block(block) => self.block(block),
+            StatementType::Const(const_) => {
+                self.attributes(&const_.attributes);
+                let type_ = self.optional_type(&const_.type_, ErrorCode::InvalidParameterType);
+
+                self.expression(&const_.value);
+                self.non_null(&const_.value, &type_);
+                self.constant(&const_.name, &const_.value);
+
+                let type_ = match type_ {
+                    ParameterType::Unknown => self.value_type(&const_.value),
+                    type_ => type_,
+                };
+                self.bind(&const_.name, Binding::Const, type_);
+            }
+            StatementType::Expression(expression) => self.expression(expression),
+            StatementType::For(for_) => {
+                self.expression(&for_.iterable);
+
+                let element = match self.value_type(&for_.iterable) {
+                    ParameterType::List(element) | ParameterType::Iterator(element) => *element,
+                    _ => ParameterType::Unknown,
+                };
+
+                self.scopes.push(Vec::new());
+                self.bind(&for_.binding, Binding::Immutable, element);
+                self.block(&for_.body);
+                self.scopes.pop();
+            }
+            StatementType::Let(let_) => {
+                self.attributes(&let_.attributes);
+                let mut type_ = self.optional_type(&let_.type_, ErrorCode::InvalidParameterType);
+
+                if let Some(value) = &let_.value {
+                    self.expression(value);
+                    self.non_null(value, &type_);
+
+                    if type_ == ParameterType::Unknown {
+                        type_ = self.value_type(value);
+                    }
+                }
+
+                let binding = if let_.mutable {
+                    Binding::Mutable
+                } else {
+                    Binding::Immutable
+                };
+                self.bind(&let_.name, binding, type_);
+            }
+            StatementType::Function(function) => {
+                self.attributes(&function.attributes);
+                self.type_parameters(&function.type_parameters);
+
+                // Generic bodies are checked once, with their type parameters left opaque
+                self.generics.push(&function.type_parameters);
+                let return_type =
+                    self.optional_type(&function.return_type, ErrorCode::InvalidReturnType);
+                let yields = function
+                    .generator
+                    .then(|| self.element_type(function, &return_type));
+
+                self.scopes.push(Vec::new());
+                self.returns.push(return_type);
+                self.yields.push(yields);
+                self.parameters(&function.parameters);
+                self.body(&function.body);
+                self.yields.pop();
+                self.returns.pop();
+                self.scopes.pop();
+                self.generics.pop();
+            }
+            StatementType::Return(return_) => {
+                if let Some(value) = &return_.value {
+                    self.expression(value);
+                    self.returned(value);
+                }
+            }
+            StatementType::Try(try_) => {
+                self.block(&try_.body);
+
+                self.scopes.push(Vec::new());
+                self.bind(&try_.binding, Binding::Immutable, ParameterType::Error);
+                self.block(&try_.handler);
+                self.scopes.pop();
+            }
+            StatementType::Intrinsic(intrinsic) => self.intrinsic(intrinsic),
+            StatementType::Struct(struct_) => {
+                self.attributes(&struct_.attributes);
+                for field in &struct_.fields {
+                    self.type_(&field.type_, ErrorCode::InvalidParameterType);
+                }
+            }
+            StatementType::Enum(enum_) => {
+                self.attributes(&enum_.attributes);
+                for field in enum_.variants.iter().flat_map(|variant| &variant.fields) {
+                    self.type_(&field.type_, ErrorCode::InvalidParameterType);
+                }
+            }
+        }
+    }
+
+    /// The variants of an enum, with the types of their fields. Errors in the types are
+    /// reported when the enum itself is checked.
+    fn variants(&mut self, enum_: &'a EnumStatement) -> Vec<Variant<'a>> {
+        let errors = std::mem::take(&mut self.errors);
+        let variants = enum_
+            .variants
+            .iter()
+            .map(|variant| Variant {
+                name: &variant.name,
+                fields: variant
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let type_ = self.type_(&field.type_, ErrorCode::InvalidParameterType);
+                        (field.name.as_str(), type_)
+                    })
+                    .collect(),
+            })
+            .collect();
+        self.errors = errors;
+        variants
+    }
+
+    fn attributes(&mut self, attributes: &'a [Attribute]) {
+        for attribute in attributes {
+            // cfg predicates name options, not bindings, and are evaluated before checking.
+            if attribute.name != "cfg" {
+                for argument in &attribute.arguments {
+                    self.expression(argument);
+                }
+            }
+
+            if !KNOWN_ATTRIBUTES.contains(&attribute.name.as_str()) {
+                self.error(
+                    ErrorCode::UnknownAttribute,
+                    format!("Unknown attribute: '{}'", attribute.name),
+                    String::from("This attribute is ignored"),
+                    &attribute.name,
+                    attribute.span.clone(),
+                    Some(format!("Known attributes: {}", KNOWN_ATTRIBUTES.join(", "))),
+                );
+            }
+        }
+    }
+
+    /// Resolves a type without reporting errors, for declarations that report them
+    /// when they are checked themselves.
+    fn declared_type(&mut self, type_: &Option<TypeExpr>) -> ParameterType {
+        let errors = std::mem::take(&mut self.errors);
+        let resolved = self.optional_type(type_, ErrorCode::InvalidParameterType);
+        self.errors = errors;
+        resolved
+    }
+
+    fn function_type(&mut self, function: &'a FunctionStatement) -> ParameterType {
+        self.generics.push(&function.type_parameters);
+        let return_type = match self.declared_type(&function.return_type) {
+            ParameterType::Unknown if function.generator => {
+                ParameterType::Iterator(Box::new(ParameterType::Unknown))
+            }
+            return_type => return_type,
+        };
+        let type_ = ParameterType::Function(
+            function
+                .parameters
+                .iter()
+                .map(|parameter| self.declared_type(&parameter.type_))
+                .collect(),
+            Box::new(return_type),
+        );
+        self.generics.pop();
+        type_
+    }
+
+    fn optional_type(&mut self, type_: &Option<TypeExpr>, error_code: ErrorCode) -> ParameterType {
+        match type_ {
+            Some(type_) => self.type_(type_, error_code),
+            None => ParameterType::Unknown,
+        }
+    }
+
+    /// Resolves the names in a type, reporting unknown names with `error_code`.
+    fn type_(&mut self, type_: &TypeExpr, error_code: ErrorCode) -> ParameterType {
+        match &type_.type_ {
+            TypeExprType::Named(named) => self.named_type(named, &type_.span, error_code),
+            TypeExprType::Optional(inner) => {
+                ParameterType::Optional(Box::new(self.type_(inner, error_code)))
+            }
+            TypeExprType::Tuple(types) => ParameterType::Tuple(
+                types
+                    .iter()
+                    .map(|type_| self.type_(type_, error_code))
+                    .collect(),
+            ),
+            TypeExprType::Function(function) => ParameterType::Function(
+                function
+                    .parameters
+                    .iter()
+                    .map(|type_| self.type_(type_, error_code))
+                    .collect(),
+                Box::new(match &function.return_type {
+                    Some(return_type) => self.type_(return_type, error_code),
+                    None => ParameterType::Unknown,
+                }),
+            ),
+        }
+    }
+
+    fn named_type(
+        &mut self,
+        named: &NamedType,
+        span: &Span,
+        error_code: ErrorCode,
+    ) -> ParameterType {
+        let arguments: Vec<ParameterType> = named
+            .arguments
+            .iter()
+            .map(|argument| self.type_(argument, error_code))
+            .collect();
+
+        let (resolved, expected_arguments) = match named.name.as_str() {
+            "list" => (
+                arguments
+                    .first()
+                    .map(|element| ParameterType::List(Box::new(element.clone()))),
+                1,
+            ),
+            "iter" => (
+                arguments
+                    .first()
+                    .map(|element| ParameterType::Iterator(Box::new(element.clone()))),
+                1,
+            ),
+            "result" => (
+                match arguments.as_slice() {
+                    [value, error] => Some(ParameterType::Result(
+                        Box::new(value.clone()),
+                        Box::new(error.clone()),
+                    )),
+                    _ => None,
+                },
+                2,
+            ),
+            "map" => (
+                match arguments.as_slice() {
+                    [key, value] => Some(ParameterType::Map(
+                        Box::new(key.clone()),
+                        Box::new(value.clone()),
+                    )),
+                    _ => None,
+                },
+                2,
+            ),
+            name if self.type_parameter(name).is_some() => {
+                (Some(ParameterType::Parameter(String::from(name))), 0)
+            }
+            name if self.structs.contains_key(name) => {
+                (Some(ParameterType::Struct(String::from(name))), 0)
+            }
+            name if self.enums.contains_key(name) => {
+                (Some(ParameterType::Enum(String::from(name))), 0)
+            }
+            name => match ParameterType::from(String::from(name)) {
+                ParameterType::Unknown => {
+                    self.unknown_type(named, error_code);
+                    return ParameterType::Unknown;
+                }
+                builtin => (Some(builtin), 0),
+            },
+        };
+
+        if arguments.len() != expected_arguments {
+            self.error(
+                ErrorCode::WrongTypeArgumentCount,
+                format!(
+                    "'{}' expects {} type argument(s), got {}",
+                    named.name,
+                    expected_arguments,
+                    arguments.len()
+                ),
+                format!(
+                    "'{}' is used with the wrong number of type arguments",
+                    named.name
+                ),
+                &named.name,
+                span.clone(),
+                None,
+            );
+            return ParameterType::Unknown;
+        }
+
+        resolved.unwrap_or(ParameterType::Unknown)
+    }
+
+    fn unknown_type(&mut self, named: &NamedType, error_code: ErrorCode) {
+        let mut declared_names: Vec<&str> = self
+            .structs
+            .keys()
+            .chain(self.enums.keys())
+            .copied()
+            .collect();
+        declared_names.sort();
+
+        let mut known_types = vec![
+            "int",
+            "bool",
+            "string",
+            "list<T>",
+            "iter<T>",
+            "map<K, V>",
+            "result<T, E>",
+            "error",
+            "fn(T) -> R",
+        ];
+        known_types.extend(declared_names);
+
+        self.error(
+            error_code,
+            format!("{}: got '{}'", error_code, named.name),
+            String::from("This type is not a built-in type or a declared struct or enum"),
+            &named.name,
+            named.name_span.clone(),
+            Some(format!("Expected one of: \n- {}", known_types.join("\n- "))),
+        );
+    }
+
+    fn expression(&mut self, expression: &'a Expression) {
+        match &expression.kind {
+            ExpressionType::Assignment(assignment) => {
+                self.expression(&assignment.target);
+                self.expression(&assignment.value);
+                self.assignment(&assignment.target);
+            }
+            ExpressionType::Binary(binary) => {
+                self.expression(&binary.left);
+
+                // The right side of `&&` only runs if the left side is true, and of `||` if it is false
+                let narrowed = match binary.operator.type_ {
+                    TokenType::AmpersandAmpersand => self.narrowing(&binary.left, true),
+                    TokenType::PipePipe => self.narrowing(&binary.left, false),
+                    _ => Vec::new(),
+                };
+                self.scopes.push(narrowed);
+                self.expression(&binary.right);
+                self.scopes.pop();
+
+                self.comparison(binary);
+
+                if matches!(
+                    binary.operator.type_,
+                    TokenType::Asterisk
+                        | TokenType::GreaterThan
+                        | TokenType::LessThan
+                        | TokenType::Minus
+                        | TokenType::Plus
+                        | TokenType::Slash
+                ) {
+                    self.require_non_null(&binary.left);
+                    self.require_non_null(&binary.right);
+                }
+            }
+            ExpressionType::Block(block) => self.block(block),
+            ExpressionType::Closure(closure) => {
+                let return_type =
+                    self.optional_type(&closure.return_type, ErrorCode::InvalidReturnType);
+
+                self.scopes.push(Vec::new());
+                self.returns.push(return_type);
+                self.yields.push(None);
+                self.parameters(&closure.parameters);
+
+                match &closure.body {
+                    ClosureBody::Expression(body) => {
+                        self.expression(body);
+                        self.returned(body);
+                    }
+                    ClosureBody::Block(block) => self.body(block),
+                }
+
+                self.yields.pop();
+                self.returns.pop();
+                self.scopes.pop();
+            }
+            ExpressionType::Coalesce(coalesce) => {
+                self.expression(&coalesce.value);
+                self.expression(&coalesce.default);
+            }
+            ExpressionType::FunctionCall(call) => {
+                self.expression(&call.callee);
+                for argument in &call.arguments {
+                    self.expression(&argument.value);
+                }
+
+                self.require_non_null(&call.callee);
+
+                if let Some((function, types, _)) = self.signature(&call.callee) {
+                    self.arguments(function, &types, &call.arguments, expression.span.clone());
+                } else if let Some((name, variant)) = self.callee_variant(&call.callee) {
+                    self.variant_construction(&name, &variant, &call.arguments, &expression.span);
+                } else if let ParameterType::Function(parameters, _) = self.value_type(&call.callee)
+                {
+                    for (argument, parameter) in call.arguments.iter().zip(&parameters) {
+                        self.non_null(&argument.value, parameter);
+                    }
+                }
+            }
+            ExpressionType::If(if_) => {
+                self.expression(&if_.condition);
+
+                let narrowed = self.narrowing(&if_.condition, true);
+                self.scopes.push(narrowed);
+                self.block(&if_.then_branch);
+                self.scopes.pop();
+
+                if let Some(else_branch) = &if_.else_branch {
+                    let narrowed = self.narrowing(&if_.condition, false);
+                    self.scopes.push(narrowed);
+                    self.expression(else_branch);
+                    self.scopes.pop();
+                }
+            }
+            ExpressionType::Index(index) => {
+                self.expression(&index.object);
+                self.expression(&index.index);
+                self.require_non_null(&index.object);
+            }
+            ExpressionType::Intrinsic(intrinsic) => self.intrinsic(intrinsic),
+            ExpressionType::List(list) => {
+                for element in &list.elements {
+                    self.expression(element);
+                }
+            }
+            ExpressionType::Literal(_) => {}
+            ExpressionType::Match(match_) => self.match_(match_, &expression.span),
+            ExpressionType::Member(member) => {
+                self.expression(&member.object);
+                self.require_non_null(&member.object);
+
+                if let Some(enum_) = self.enum_name(member) {
+                    if self.variant(member).is_none() {
+                        self.unknown_variant(enum_, &member.property, &expression.span);
+                    }
+                }
+            }
+            ExpressionType::OptionalIndex(index) => {
+                self.expression(&index.object);
+                self.expression(&index.index);
+            }
+            ExpressionType::OptionalMember(member) => self.expression(&member.object),
+            ExpressionType::Propagate(propagate) => {
+                self.expression(&propagate.value);
+                self.propagate(&propagate.value, &expression.span);
+            }
+            ExpressionType::Range(range) => {
+                for part in range.start.iter().chain(&range.end) {
+                    self.expression(part);
+                }
+            }
+            ExpressionType::Record(record) => {
+                for field in &record.fields {
+                    self.expression(&field.value);
+                }
+            }
+            ExpressionType::Struct(struct_) => {
+                for field in &struct_.fields {
+                    self.expression(&field.value);
+                }
+
+                self.struct_construction(&struct_.name, &struct_.fields, &expression.span);
+            }
+            ExpressionType::Unary(unary) => self.expression(&unary.operand),
+            ExpressionType::Yield(yield_) => {
+                if let Some(value) = &yield_.value {
+                    self.expression(value);
+                }
+
+                match (self.yields.last().cloned(), &yield_.value) {
+                    (Some(Some(element)), Some(value)) => self.non_null(value, &element),
+                    (Some(Some(_)), None) => {}
+                    _ => self.error(
+                        ErrorCode::InvalidYield,
+                        String::from("'yield' can only be used inside a generator function"),
+                        String::from("Not inside a generator"),
+                        "yield",
+                        expression.span.clone(),
+                        Some(String::from(
+                            "Declare the enclosing function with `function*`",
+                        )),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// The type of the values a generator yields, from its `iter<T>` return type.
+    fn element_type(
+        &mut self,
+        function: &FunctionStatement,
+        return_type: &ParameterType,
+    ) -> ParameterType {
+        match return_type {
+            ParameterType::Iterator(element) => (**element).clone(),
+            ParameterType::Unknown => ParameterType::Unknown,
+            return_type => {
+                self.error(
+                    ErrorCode::InvalidReturnType,
+                    format!(
+                        "Generator '{}' should return iter<T>, got {}",
+                        function.name, return_type
+                    ),
+                    String::from("Generators return an iterator over the values they yield"),
+                    &function.name,
+                    function
+                        .return_type
+                        .as_ref()
+                        .map_or(0..0, |type_| type_.span.clone()),
+                    Some(format!("Declare it as `iter<{}>`", return_type)),
+                );
+                ParameterType::Unknown
+            }
+        }
+    }
+
+    /// The declaration and type of the function `callee` names, if it is a function declaration.
+    fn signature(
+        &self,
+        callee: &Expression,
+    ) -> Option<(&'a FunctionStatement, Vec<ParameterType>, ParameterType)> {
+        let ExpressionType::Literal(LiteralExpression {
+            literal: LiteralType::Unknown,
+            value,
+        }) = &callee.kind
+        else {
+            return None;
+        };
+
+        match self.variable(value)? {
+            Variable {
+                function: Some(function),
+                type_: ParameterType::Function(parameters, return_type),
+                ..
+            } => Some((function, parameters.clone(), (**return_type).clone())),
+            _ => None,
+        }
+    }
+
+    /// The parameter an argument goes to: by position, into a trailing variadic parameter, or by name.
+    fn parameter_index(
+        parameters: &[Parameter],
+        position: usize,
+        argument: &Argument,
+    ) -> Option<usize> {
+        let fixed = parameters.len()
+            - usize::from(
+                parameters
+                    .last()
+                    .is_some_and(|parameter| parameter.variadic),
+            );
+
+        match &argument.name {
+            Some(name) => parameters
+                .iter()
+                .position(|parameter| &parameter.name == name),
+            None if position < fixed => Some(position),
+            None if fixed < parameters.len() => Some(fixed),
+            None => None,
+        }
+    }
+
+    /// The type an argument is checked against. Each left-over positional argument
+    /// is one element of the variadic list.
+    fn argument_type(
+        parameter: &Parameter,
+        type_: &ParameterType,
+        argument: &Argument,
+    ) -> ParameterType {
+        match type_ {
+            ParameterType::List(element) if parameter.variadic && argument.name.is_none() => {
+                (**element).clone()
+            }
+            type_ => type_.clone(),
+        }
+    }
+
+    /// The type arguments of a call to a generic function, as far as they can be inferred.
+    fn type_arguments(
+        &self,
+        function: &FunctionStatement,
+        types: &[ParameterType],
+        arguments: &[Argument],
+    ) -> HashMap<String, ParameterType> {
+        let mut bindings = HashMap::new();
+
+        for (position, argument) in arguments.iter().enumerate() {
+            if let Some(index) = Checker::parameter_index(&function.parameters, position, argument)
+            {
+                let expected =
+                    Checker::argument_type(&function.parameters[index], &types[index], argument);
+                // Conflicts are reported when the call itself is checked
+                let _ = expected.infer(&self.value_type(&argument.value), &mut bindings);
+            }
+        }
+
+        bindings
+    }
+
+    /// Matches the arguments of a call against the parameters of `function`, by position and then
+    /// by name, and instantiates its type parameters from the argument types.
+    fn arguments(
+        &mut self,
+        function: &'a FunctionStatement,
+        types: &[ParameterType],
+        arguments: &[Argument],
+        span: Span,
+    ) {
+        let name = function.name.as_str();
+        let parameters = &function.parameters;
+        let mut given = vec![false; parameters.len()];
+        let mut bindings = HashMap::new();
+
+        for (position, argument) in arguments.iter().enumerate() {
+            let Some(index) = Checker::parameter_index(parameters, position, argument) else {
+                match &argument.name {
+                    Some(argument_name) => {
+                        let help = suggest::closest(
+                            argument_name,
+                            parameters.iter().map(|parameter| parameter.name.as_str()),
+                        )
+                        .map(|suggestion| format!("Did you mean `{}`?", suggestion));
+
+                        self.error(
+                            ErrorCode::UnknownArgument,
+                            format!("'{}' has no parameter named '{}'", name, argument_name),
+                            String::from("Unknown parameter name"),
+                            argument_name,
+                            argument.span.clone(),
+                            help,
+                        );
+                    }
+                    // Only the first extra argument is reported
+                    None if position == parameters.len() => {
+                        let positional = arguments
+                            .iter()
+                            .filter(|argument| argument.name.is_none())
+                            .count();
+
+                        self.error(
+                            ErrorCode::WrongArgumentCount,
+                            format!(
+                                "'{}' takes {} argument(s), got {}",
+                                name,
+                                parameters.len(),
+                                positional
+                            ),
+                            String::from("This argument has no parameter to go to"),
+                            name,
+                            argument.span.clone(),
+                            None,
+                        );
+                    }
+                    None => {}
+                }
+                continue;
+            };
+
+            if argument.name.is_some() && given[index] {
+                self.error(
+                    ErrorCode::DuplicateArgument,
+                    format!(
+                        "Parameter '{}' of '{}' is given more than once",
+                        parameters[index].name, name
+                    ),
+                    String::from("This parameter already has a value"),
+                    &parameters[index].name,
+                    argument.span.clone(),
+                    Some(String::from("Remove one of the arguments")),
+                );
+                continue;
+            }
+            given[index] = true;
+
+            let expected = Checker::argument_type(&parameters[index], &types[index], argument);
+            let actual = self.value_type(&argument.value);
+
+            if let Err((type_parameter, bound, actual)) = expected.infer(&actual, &mut bindings) {
+                self.error(
+                    ErrorCode::InvalidArgumentType,
+                    format!(
+                        "'{}' is {} from an earlier argument of '{}', but this argument is {}",
+                        type_parameter, bound, name, actual
+                    ),
+                    format!("Expected {}", bound),
+                    &parameters[index].name,
+                    argument.span.clone(),
+                    None,
+                );
+                continue;
+            }
+
+            self.non_null(&argument.value, &expected.substitute(&bindings));
+        }
+
+        let missing: Vec<String> = parameters
+            .iter()
+            .zip(&given)
+            .filter(|(parameter, given)| {
+                !**given && parameter.default.is_none() && !parameter.variadic
+            })
+            .map(|(parameter, _)| format!("'{}'", parameter.name))
+            .collect();
+
+        if !missing.is_empty() {
+            self.error(
+                ErrorCode::MissingArgument,
+                format!("Call to '{}' is missing {}", name, missing.join(", ")),
+                String::from("Missing argument(s)"),
+                name,
+                span.clone(),
+                Some(String::from(
+                    "Pass them by position, or by name such as `name: value`",
+                )),
+            );
+        }
+
+        for type_parameter in &function.type_parameters {
+            let Some(type_) = bindings.get(&type_parameter.name) else {
+                continue;
+            };
+
+            for constraint in type_parameter
+                .constraints
+                .iter()
+                .filter_map(|constraint| Constraint::from_name(constraint))
+            {
+                if !self.satisfies(type_, constraint) {
+                    self.error(
+                        ErrorCode::UnsatisfiedConstraint,
+                        format!(
+                            "'{}' requires '{}: {}', but '{}' is {}",
+                            name, type_parameter.name, constraint, type_parameter.name, type_
+                        ),
+                        format!("{} is not {}", type_, constraint),
+                        name,
+                        span.clone(),
+                        None,
+                    );
+                }
+            }
+        }
+    }
+
+    fn satisfies(&self, type_: &ParameterType, constraint: Constraint) -> bool {
+        match type_ {
+            ParameterType::Parameter(name) => {
+                self.type_parameter(name).is_some_and(|type_parameter| {
+                    type_parameter.constraints.iter().any(|declared| {
+                        Constraint::from_name(declared)
+                            .is_some_and(|declared| constraint.implied_by(declared))
+                    })
+                })
+            }
+            type_ => constraint.satisfied_by(type_),
+        }
+    }
+
+    /// Finds the innermost type parameter called `name` of the generic functions being checked.
+    fn type_parameter(&self, name: &str) -> Option<&'a TypeParameter> {
+        self.generics
+            .iter()
+            .rev()
+            .flat_map(|type_parameters| type_parameters.iter())
+            .find(|type_parameter| type_parameter.name == name)
+    }
+
+    /// Checks the constraints named by the type parameters of a function.
+    fn type_parameters(&mut self, type_parameters: &'a [TypeParameter]) {
+        for (index, type_parameter) in type_parameters.iter().enumerate() {
+            if type_parameters[..index]
+                .iter()
+                .any(|earlier| earlier.name == type_parameter.name)
+            {
+                self.error(
+                    ErrorCode::DuplicateTypeParameter,
+                    format!(
+                        "Type parameter '{}' is declared more than once",
+                        type_parameter.name
+                    ),
+                    String::from("This type parameter was already declared"),
+                    &type_parameter.name,
+                    type_parameter.span.clone(),
+                    Some(String::from("Rename one of the type parameters")),
+                );
+            }
+
+            for constraint in &type_parameter.constraints {
+                if Constraint::from_name(constraint).is_none() {
+                    let help = suggest::closest(constraint, Constraint::NAMES)
+                        .map(|suggestion| format!("Did you mean `{}`?", suggestion));
+
+                    self.error(
+                        ErrorCode::UnknownConstraint,
+                        format!(
+                            "Unknown constraint '{}' on '{}'",
+                            constraint, type_parameter.name
+                        ),
+                        format!("Constraints are one of: {}", Constraint::NAMES.join(", ")),
+                        constraint,
+                        type_parameter.span.clone(),
+                        help,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reports comparing values of a type parameter that doesn't declare the constraint
+    /// the comparison needs, such as `a < b` where `a: T` without `T: Ord`.
+    fn comparison(&mut self, binary: &BinaryExpression) {
+        let constraint = match binary.operator.type_ {
+            TokenType::LessThan | TokenType::GreaterThan => Constraint::Ord,
+            TokenType::EqualsEquals | TokenType::BangEquals => Constraint::Eq,
+            _ => return,
+        };
+
+        // Anything can be checked for null
+        let null = |operand: &Expression| {
+            matches!(
+                &operand.kind,
+                ExpressionType::Literal(LiteralExpression {
+                    literal: LiteralType::Null,
+                    ..
+                })
+            )
+        };
+        if constraint == Constraint::Eq && (null(&binary.left) || null(&binary.right)) {
+            return;
+        }
+
+        for operand in [&binary.left, &binary.right] {
+            let type_ = self.value_type(operand);
+            if matches!(type_, ParameterType::Parameter(_)) && !self.satisfies(&type_, constraint) {
+                self.error(
+                    ErrorCode::UnsatisfiedConstraint,
+                    format!(
+                        "'{}' needs '{}: {}' to be compared with '{}'",
+                        type_, type_, constraint, binary.operator.value
+                    ),
+                    format!("{} is not {}", type_, constraint),
+                    operand.root_identifier().unwrap_or(""),
+                    operand.span.clone(),
+                    Some(format!(
+                        "Declare the type parameter as `<{}: {}>`",
+                        type_, constraint
+                    )),
+                );
+                return;
+            }
+        }
+    }
+
+    /// Checks an `@name(...)` call against the signature of a registered intrinsic.
+    fn intrinsic(&mut self, intrinsic: &'a IntrinsicExpression) {
+        for argument in &intrinsic.arguments {
+            self.expression(argument);
+        }
+
+        let name = &intrinsic.name;
+        let Some(signature) = self.intrinsics.get(name) else {
+            let help = self
+                .intrinsics
+                .suggest(name)
+                .map(|suggestion| format!("Did you mean `@{}`?", suggestion));
+
+            self.error(
+                ErrorCode::UnknownIntrinsic,
+                format!("Unknown intrinsic: '@{}'", name),
+                String::from("No intrinsic with this name is registered"),
+                name,
+                intrinsic.name_span.clone(),
+                help,
+            );
+            return;
+        };
+
+        if !signature.accepts_arity(intrinsic.arguments.len()) {
+            self.error(
+                ErrorCode::WrongArgumentCount,
+                format!(
+                    "'@{}' expects {} argument(s), got {}",
+                    name,
+                    signature.arity(),
+                    intrinsic.arguments.len()
+                ),
+                String::from("Called with the wrong number of arguments"),
+                name,
+                intrinsic.name_span.clone(),
+                None,
+            );
+            return;
+        }
+
+        for (index, argument) in intrinsic.arguments.iter().enumerate() {
+            let (Some(expected), actual) = (signature.parameter(index), self.value_type(argument))
+            else {
+                continue;
+            };
+
+            if !expected.accepts(&actual) {
+                self.error(
+                    ErrorCode::InvalidArgumentType,
+                    format!(
+                        "Argument {} of '@{}' should be {}, got {}",
+                        index + 1,
+                        name,
+                        expected,
+                        actual
+                    ),
+                    format!("This is {}", actual),
+                    name,
+                    argument.span.clone(),
+                    None,
+                );
+            }
+        }
+    }
+
+    /// The type of a value, as far as it can be told from literals and declared types.
+    fn value_type(&self, expression: &Expression) -> ParameterType {
+        match &expression.kind {
+            // The rest of an optional chain is skipped if it runs into null
+            _ if Checker::in_optional_chain(expression) => {
+                ParameterType::Optional(Box::new(ParameterType::Unknown))
+            }
+            ExpressionType::Literal(literal) => match literal.literal {
+                LiteralType::Integer => ParameterType::Integer,
+                LiteralType::Null => ParameterType::Optional(Box::new(ParameterType::Unknown)),
+                LiteralType::String => ParameterType::String,
+                LiteralType::Unknown => self
+                    .variable(&literal.value)
+                    .map_or(ParameterType::Unknown, |variable| variable.type_.clone()),
+            },
+            ExpressionType::Coalesce(coalesce) => match self.value_type(&coalesce.value) {
+                ParameterType::Optional(inner) => {
+                    match (*inner, self.value_type(&coalesce.default)) {
+                        (ParameterType::Unknown, default) => default,
+                        (inner, ParameterType::Optional(_)) => {
+                            ParameterType::Optional(Box::new(inner))
+                        }
+                        (inner, _) => inner,
+                    }
+                }
+                value => value,
+            },
+            ExpressionType::FunctionCall(call) => match self.signature(&call.callee) {
+                Some((function, types, return_type)) => {
+                    return_type.substitute(&self.type_arguments(function, &types, &call.arguments))
+                }
+                None => match self.value_type(&call.callee) {
+                    ParameterType::Function(_, return_type) => *return_type,
+                    _ => ParameterType::Unknown,
+                },
+            },
+            ExpressionType::Propagate(propagate) => match self.value_type(&propagate.value) {
+                ParameterType::Result(value, _) => *value,
+                _ => ParameterType::Unknown,
+            },
+            ExpressionType::Binary(binary) => match binary.operator.type_ {
+                TokenType::EqualsEquals
+                | TokenType::BangEquals
+                | TokenType::LessThan
+                | TokenType::GreaterThan
+                | TokenType::AmpersandAmpersand
+                | TokenType::PipePipe => ParameterType::Boolean,
+                _ => ParameterType::Unknown,
+            },
+            ExpressionType::List(list) => {
+                let mut types = list.elements.iter().map(|element| self.value_type(element));
+                let first = types.next().unwrap_or(ParameterType::Unknown);
+
+                if types.all(|type_| type_ == first) {
+                    ParameterType::List(Box::new(first))
+                } else {
+                    ParameterType::List(Box::new(ParameterType::Unknown))
+                }
+            }
+            ExpressionType::Record(_) => ParameterType::Map(
+                Box::new(ParameterType::String),
+                Box::new(ParameterType::Unknown),
+            ),
+            ExpressionType::Struct(struct_) => ParameterType::Struct(struct_.name.clone()),
+            // A variant with fields is constructed by calling it, as in `Status.Suspended("spam")`
+            ExpressionType::Member(member) => {
+                match (self.enum_name(member), self.variant(member)) {
+                    (Some(enum_), Some(variant)) => {
+                        let type_ = ParameterType::Enum(String::from(enum_));
+                        if variant.fields.is_empty() {
+                            type_
+                        } else {
+                            let fields = variant.fields.iter().map(|(_, type_)| type_.clone());
+                            ParameterType::Function(fields.collect(), Box::new(type_))
+                        }
+                    }
+                    _ => ParameterType::Unknown,
+                }
+            }
+            ExpressionType::Match(match_) => {
+                let mut types = match_.arms.iter().map(|arm| self.value_type(&arm.body));
+                let first = types.next().unwrap_or(ParameterType::Unknown);
+
+                if types.all(|type_| type_ == first) {
+                    first
+                } else {
+                    ParameterType::Unknown
+                }
+            }
+            ExpressionType::Unary(_) => ParameterType::Boolean,
+            ExpressionType::Intrinsic(intrinsic) => self
+                .intrinsics
+                .get(&intrinsic.name)
+                .map(|signature| signature.return_type.clone())
+                .unwrap_or(ParameterType::Unknown),
+            _ => ParameterType::Unknown,
+        }
+    }
+
+    /// Checks that `value?` is applied to a `result`, inside a function that returns
+    /// a `result` the error can be passed on through.
+    fn propagate(&mut self, value: &Expression, span: &Span) {
+        let error_type = match self.value_type(value) {
+            ParameterType::Result(_, error) => *error,
+            ParameterType::Unknown => ParameterType::Unknown,
+            actual => {
+                self.error(
+                    ErrorCode::InvalidPropagation,
+                    format!("`?` can only be applied to a result, got '{}'", actual),
+                    String::from("This is not a result"),
+                    "?",
+                    value.span.clone(),
+                    None,
+                );
+                return;
+            }
+        };
+
+        let (message, help) = match self.returns.last() {
+            None => (
+                String::from("`?` can only be used inside a function"),
+                String::from("Handle the error with `try { } catch (e) { }` instead"),
+            ),
+            Some(ParameterType::Result(_, expected)) if !expected.accepts(&error_type) => (
+                format!(
+                    "The error type '{}' doesn't match the function's error type '{}'",
+                    error_type, expected
+                ),
+                format!("Convert the error to '{}' before returning it", expected),
+            ),
+            Some(ParameterType::Result(..) | ParameterType::Unknown) => return,
+            Some(return_type) => (
+                format!(
+                    "`?` can't pass an error on from a function that returns '{}'",
+                    return_type
+                ),
+                String::from("Declare the return type as `result<T, E>`"),
+            ),
+        };
+
+        self.error(
+            ErrorCode::InvalidPropagation,
+            message,
+            String::from("The error is passed on here"),
+            "?",
+            span.end - 1..span.end,
+            Some(help),
+        );
+    }
+
+    /// Whether `expression` is part of a chain like `a?.b.c()`, which stops at the first null.
+    fn in_optional_chain(expression: &Expression) -> bool {
+        match &expression.kind {
+            ExpressionType::OptionalIndex(_) | ExpressionType::OptionalMember(_) => true,
+            ExpressionType::Member(member) => Checker::in_optional_chain(&member.object),
+            ExpressionType::Index(index) => Checker::in_optional_chain(&index.object),
+            ExpressionType::FunctionCall(call) => Checker::in_optional_chain(&call.callee),
+            _ => false,
+        }
+    }
+
+    /// Reports a value that may be null where it is used directly, such as `user` in `user.name`.
+    fn require_non_null(&mut self, value: &Expression) {
+        if Checker::in_optional_chain(value) {
+            return;
+        }
+
+        if let ParameterType::Optional(inner) = self.value_type(value) {
+            let name = value.root_identifier().unwrap_or("");
+            self.error(
+                ErrorCode::NullableValue,
+                format!("A value of type '{}?' may be null here", inner),
+                String::from("This may be null"),
+                name,
+                value.span.clone(),
+                Some(String::from(
+                    "Check it with `if value != null { ... }` first, or use `?.`, `?[` or `??`",
+                )),
+            );
+        }
+    }
+
+    /// Reports a value that may be null where a non-null `expected` type is declared.
+    fn non_null(&mut self, value: &Expression, expected: &ParameterType) {
+        if matches!(
+            expected,
+            ParameterType::Optional(_) | ParameterType::Unknown
+        ) {
+            return;
+        }
+
+        if let actual @ ParameterType::Optional(_) = self.value_type(value) {
+            let name = value.root_identifier().unwrap_or("");
+            self.error(
+                ErrorCode::NullableValue,
+                format!("Expected '{}', but got '{}', which may be null", expected, actual),
+                String::from("This may be null"),
+                name,
+                value.span.clone(),
+                Some(format!(
+                    "Check it with `if value != null {{ ... }}` first, give it a default with `??`, or declare the type as '{}?'",
+                    expected
+                )),
+            );
+        }
+    }
+
+    /// The variables that can't be null while `condition` is `when`, such as `x` in `x != null`.
+    fn narrowing(&self, condition: &'a Expression, when: bool) -> Vec<Variable<'a>> {
+        let ExpressionType::Binary(binary) = &condition.kind else {
+            return Vec::new();
+        };
+
+        match (binary.operator.type_, when) {
+            (TokenType::AmpersandAmpersand, true) | (TokenType::PipePipe, false) => {
+                let mut narrowed = self.narrowing(&binary.left, when);
+                narrowed.extend(self.narrowing(&binary.right, when));
+                narrowed
+            }
+            (TokenType::BangEquals, true) | (TokenType::EqualsEquals, false) => {
+                let name = match (&binary.left.kind, &binary.right.kind) {
+                    (ExpressionType::Literal(left), ExpressionType::Literal(right))
+                        if right.literal == LiteralType::Null =>
+                    {
+                        left
+                    }
+                    (ExpressionType::Literal(left), ExpressionType::Literal(right))
+                        if left.literal == LiteralType::Null =>
+                    {
+                        right
+                    }
+                    _ => return Vec::new(),
+                };
+
+                if name.literal != LiteralType::Unknown {
+                    return Vec::new();
+                }
+
+                match self.variable(&name.value) {
+                    Some(Variable {
+                        binding,
+                        type_: ParameterType::Optional(inner),
+                        ..
+                    }) => vec![Variable {
+                        name: &name.value,
+                        binding: *binding,
+                        type_: (**inner).clone(),
+                        function: None,
+                    }],
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Checks that the variable behind an assignment target was declared with `let mut`.
+    fn assignment(&mut self, target: &Expression) {
+        let Some(name) = target.root_identifier() else {
+            return;
+        };
+
+        let help = match self.lookup(name) {
+            // Undeclared names are left to name resolution
+            None | Some(Binding::Mutable) => return,
+            Some(Binding::Immutable) => {
+                format!("Declare it with `let mut {}` to allow assignment", name)
+            }
+            Some(Binding::Const) => String::from("Constants can never be reassigned"),
+            Some(Binding::Parameter) => format!("Copy it into a `let mut {}` binding first", name),
+            Some(Binding::Function) => String::from("Functions can't be reassigned"),
+        };
+
+        self.error(
+            ErrorCode::AssignmentToImmutable,
+            format!("Cannot assign to immutable binding '{}'", name),
+            String::from("This binding is not mutable"),
+            name,
+            target.span.clone(),
+            Some(help),
+        );
+    }
+
+    /// Reports the first part of a `const` value that can't be evaluated at compile time.
+    fn constant(&mut self, name: &str, value: &Expression) {
+        if let Some(expression) = self.non_constant(value) {
+            self.error(
+                ErrorCode::NonConstantExpression,
+                format!("The value of constant '{}' is not known at compile time", name),
+                String::from("This can't be evaluated at compile time"),
+                name,
+                expression.span.clone(),
+                Some(String::from(
+                    "Constants may only use literals, lists, records, enum variants, operators and other constants",
+                )),
+            );
+        }
+    }
+
+    fn non_constant<'e>(&self, expression: &'e Expression) -> Option<&'e Expression> {
+        match &expression.kind {
+            ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) => match self.lookup(value) {
+                Some(Binding::Const) => None,
+                _ => Some(expression),
+            },
+            ExpressionType::Literal(_) => None,
+            ExpressionType::Member(member) if self.variant(member).is_some() => None,
+            ExpressionType::FunctionCall(call) if self.callee_variant(&call.callee).is_some() => {
+                call.arguments
+                    .iter()
+                    .find_map(|argument| self.non_constant(&argument.value))
+            }
+            ExpressionType::Binary(binary) => self
+                .non_constant(&binary.left)
+                .or_else(|| self.non_constant(&binary.right)),
+            ExpressionType::Unary(unary) => self.non_constant(&unary.operand),
+            ExpressionType::List(list) => list
+                .elements
+                .iter()
+                .find_map(|element| self.non_constant(element)),
+            ExpressionType::Range(range) => range
+                .start
+                .iter()
+                .chain(&range.end)
+                .find_map(|part| self.non_constant(part)),
+            ExpressionType::Record(RecordExpression { fields })
+            | ExpressionType::Struct(StructExpression { fields, .. }) => fields
+                .iter()
+                .find_map(|field| self.non_constant(&field.value)),
+            _ => Some(expression),
+        }
+    }
+
+    /// Checks that a `Name { ... }` construction provides exactly the declared fields.
+    fn struct_construction(&mut self, name: &str, fields: &[RecordField], span: &Span) {
+        let Some(declaration) = self.structs.get(name).copied() else {
+            self.error(
+                ErrorCode::UnknownStruct,
+                format!("Unknown struct: '{}'", name),
+                String::from("No struct with this name is declared"),
+                name,
+                span.start..span.start + name.len(),
+                None,
+            );
+            return;
+        };
+
+        for field in fields {
+            if !declaration
+                .fields
+                .iter()
+                .any(|declared| declared.name == field.key)
+            {
+                self.error(
+                    ErrorCode::UnknownStructField,
+                    format!("Struct '{}' has no field '{}'", name, field.key),
+                    String::from("This field is not declared in the struct"),
+                    &field.key,
+                    field.key_span.clone(),
+                    Some(format!(
+                        "Declared fields: {}",
+                        declaration
+                            .fields
+                            .iter()
+                            .map(|declared| declared.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                );
+            }
+        }
+
+        for declared in &declaration.fields {
+            if !fields.iter().any(|field| field.key == declared.name) {
+                self.error(
+                    ErrorCode::MissingStructField,
+                    format!(
+                        "Missing field '{}' in construction of '{}'",
+                        declared.name, name
+                    ),
+                    format!("'{}' is not given a value here", declared.name),
+                    name,
+                    span.clone(),
+                    None,
+                );
+            }
+        }
+    }
+
+    /// The enum named by the object of a member expression such as `Status.Active`, unless a
+    /// variable shadows it.
+    fn enum_name<'e>(&self, member: &'e MemberExpression) -> Option<&'e str> {
+        match &member.object.kind {
+            ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) if self.enums.contains_key(value.as_str()) && self.variable(value).is_none() => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// The variant a member expression such as `Status.Active` names, if the enum has it.
+    fn variant(&self, member: &MemberExpression) -> Option<&Variant<'a>> {
+        let enum_ = self.enum_name(member)?;
+        self.enums[enum_]
+            .iter()
+            .find(|variant| variant.name == member.property)
+    }
+
+    /// The full name and declaration of the variant a call constructs, as in `Status.Suspended(...)`.
+    fn callee_variant(&self, callee: &Expression) -> Option<(String, Variant<'a>)> {
+        let ExpressionType::Member(member) = &callee.kind else {
+            return None;
+        };
+        let variant = self.variant(member)?.clone();
+        Some((
+            format!("{}.{}", self.enum_name(member)?, variant.name),
+            variant,
+        ))
+    }
+
+    fn unknown_variant(&mut self, enum_: &str, name: &str, span: &Span) {
+        let declared: Vec<&str> = self.enums[enum_]
+            .iter()
+            .map(|variant| variant.name)
+            .collect();
+        let help = match suggest::closest(name, declared.iter().copied()) {
+            Some(closest) => format!("Did you mean `{}.{}`?", enum_, closest),
+            None => format!("Declared variants: {}", declared.join(", ")),
+        };
+
+        self.error(
+            ErrorCode::UnknownEnumVariant,
+            format!("Enum '{}' has no variant '{}'", enum_, name),
+            String::from("No variant with this name is declared"),
+            name,
+            span.clone(),
+            Some(help),
+        );
+    }
+
+    /// Matches the arguments of a variant construction against its fields, by position and
+    /// then by name, like the arguments of a function call.
+    fn variant_construction(
+        &mut self,
+        name: &str,
+        variant: &Variant<'a>,
+        arguments: &[Argument],
+        span: &Span,
+    ) {
+        let mut given = vec![false; variant.fields.len()];
+
+        for (position, argument) in arguments.iter().enumerate() {
+            let index = match &argument.name {
+                Some(field) => variant
+                    .fields
+                    .iter()
+                    .position(|(declared, _)| declared == field),
+                None => (position < given.len()).then_some(position),
+            };
+
+            let Some(index) = index else {
+                match &argument.name {
+                    Some(field) => self.error(
+                        ErrorCode::UnknownArgument,
+                        format!("'{}' has no field named '{}'", name, field),
+                        String::from("Unknown field name"),
+                        field,
+                        argument.span.clone(),
+                        suggest::closest(
+                            field,
+                            variant.fields.iter().map(|(declared, _)| *declared),
+                        )
+                        .map(|suggestion| format!("Did you mean `{}`?", suggestion)),
+                    ),
+                    // Only the first extra argument is reported
+                    None if position == given.len() => self.error(
+                        ErrorCode::WrongArgumentCount,
+                        format!(
+                            "'{}' takes {} argument(s), got {}",
+                            name,
+                            given.len(),
+                            arguments.len()
+                        ),
+                        String::from("This argument has no field to go to"),
+                        name,
+                        argument.span.clone(),
+                        None,
+                    ),
+                    None => {}
+                }
+                continue;
+            };
+
+            if given[index] {
+                self.error(
+                    ErrorCode::DuplicateArgument,
+                    format!(
+                        "Field '{}' of '{}' is given more than once",
+                        variant.fields[index].0, name
+                    ),
+                    String::from("This field already has a value"),
+                    variant.fields[index].0,
+                    argument.span.clone(),
+                    Some(String::from("Remove one of the arguments")),
+                );
+                continue;
+            }
+            given[index] = true;
+
+            self.non_null(&argument.value, &variant.fields[index].1);
+        }
+
+        let missing: Vec<String> = variant
+            .fields
+            .iter()
+            .zip(&given)
+            .filter(|(_, given)| !**given)
+            .map(|((field, _), _)| format!("'{}'", field))
+            .collect();
+
+        if !missing.is_empty() {
+            self.error(
+                ErrorCode::MissingArgument,
+                format!(
+                    "Construction of '{}' is missing {}",
+                    name,
+                    missing.join(", ")
+                ),
+                String::from("Missing field(s)"),
+                name,
+                span.clone(),
+                Some(String::from(
+                    "Pass them by position, or by name such as `name: value`",
+                )),
+            );
+        }
+    }
+
+    /// Checks the patterns of a `match` against the matched value, then the guard and body of
+    /// each arm with the names its pattern binds, and then that the arms cover every value.
+    fn match_(&mut self, match_: &'a MatchExpression, span: &Span) {
+        self.expression(&match_.value);
+        let value = self.value_type(&match_.value);
+
+        let mut valid = true;
+        for arm in &match_.arms {
+            let mut bindings = Vec::new();
+            valid &= self.pattern(&arm.pattern, &value, &mut bindings);
+
+            self.scopes.push(bindings);
+            if let Some(guard) = &arm.guard {
+                self.expression(guard);
+            }
+            self.expression(&arm.body);
+            self.scopes.pop();
+        }
+
+        // Coverage can't be worked out from patterns that don't fit the value
+        if !valid {
+            return;
+        }
+
+        let coverage = exhaustiveness::check(&self.enums, &value, &match_.arms);
+
+        for index in coverage.unreachable {
+            let pattern = &match_.arms[index].pattern;
+            self.error(
+                ErrorCode::UnreachableMatchArm,
+                String::from("This arm is never reached"),
+                String::from("Every value this matches is matched by an arm before it"),
+                "",
+                pattern.span.clone(),
+                Some(String::from(
+                    "Remove the arm, or move it before the arms that cover it",
+                )),
+            );
+        }
+
+        if !coverage.missing.is_empty() {
+            let help = if coverage.missing == ["_"] {
+                String::from("Add a `_ => ...` arm for the values that aren't matched")
+            } else {
+                format!(
+                    "Add arms for {}, or a `_ => ...` arm",
+                    coverage.missing.join(", ")
+                )
+            };
+
+            self.error(
+                ErrorCode::NonExhaustiveMatch,
+                format!("The match doesn't cover {}", coverage.missing.join(", ")),
+                String::from("Not every value is matched by an arm"),
+                "match",
+                span.start..match_.value.span.end,
+                Some(help),
+            );
+        }
+    }
+
+    /// Checks that a pattern can match a value of type `expected`, and collects the names it
+    /// binds. Returns whether it can.
+    fn pattern(
+        &mut self,
+        pattern: &'a Pattern,
+        expected: &ParameterType,
+        bindings: &mut Vec<Variable<'a>>,
+    ) -> bool {
+        let actual = match &pattern.kind {
+            PatternType::Wildcard => return true,
+            PatternType::Binding { name } => {
+                bindings.push(Variable {
+                    name,
+                    binding: Binding::Immutable,
+                    type_: expected.clone(),
+                    function: None,
+                });
+                return true;
+            }
+            PatternType::Literal(literal) => match literal.literal {
+                LiteralType::Integer => ParameterType::Integer,
+                LiteralType::Null => ParameterType::Optional(Box::new(ParameterType::Unknown)),
+                LiteralType::String => ParameterType::String,
+                LiteralType::Unknown => ParameterType::Boolean,
+            },
+            PatternType::Variant(variant) => {
+                if !self.enums.contains_key(variant.enum_.as_str()) {
+                    self.error(
+                        ErrorCode::InvalidPattern,
+                        format!("Unknown enum: '{}'", variant.enum_),
+                        String::from("No enum with this name is declared"),
+                        &variant.enum_,
+                        pattern.span.clone(),
+                        None,
+                    );
+                    return false;
+                }
+                ParameterType::Enum(variant.enum_.clone())
+            }
+        };
+
+        if !expected.accepts(&actual) {
+            self.error(
+                ErrorCode::InvalidPattern,
+                format!(
+                    "A pattern of type '{}' can't match a value of type '{}'",
+                    actual, expected
+                ),
+                format!("This matches {}, not {}", actual, expected),
+                "",
+                pattern.span.clone(),
+                None,
+            );
+            return false;
+        }
+
+        let PatternType::Variant(variant) = &pattern.kind else {
+            return true;
+        };
+
+        let Some(declared) = self.enums[variant.enum_.as_str()]
+            .iter()
+            .find(|declared| declared.name == variant.variant)
+            .cloned()
+        else {
+            self.unknown_variant(&variant.enum_, &variant.variant, &pattern.span);
+            return false;
+        };
+
+        if declared.fields.len() != variant.fields.len() {
+            self.error(
+                ErrorCode::InvalidPattern,
+                format!(
+                    "'{}.{}' has {} field(s), but the pattern has {}",
+                    variant.enum_,
+                    variant.variant,
+                    declared.fields.len(),
+                    variant.fields.len()
+                ),
+                String::from("Wrong number of fields"),
+                &variant.variant,
+                pattern.span.clone(),
+                Some(String::from("Use `_` for the fields you don't need")),
+            );
+            return false;
+        }
+
+        let mut valid = true;
+        for ((_, type_), field) in declared.fields.iter().zip(&variant.fields) {
+            valid &= self.pattern(field, type_, bindings);
+        }
+        valid
+    }
+
+    fn error(
+        &mut self,
+        error_code: ErrorCode,
+        error_message: String,
+        span_message: String,
+        value: &str,
+        span: Span,
+        help: Option<String>,
+    ) {
+        self.errors.push(CompilerError {
+            error_code,
+            error_message,
+            span_message,
+            token: Token {
+                type_: TokenType::Identifier,
+                value: String::from(value),
+                span,
+            },
+            help,
+            info: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{
+        intrinsic::Intrinsic, lexer::Lexer, node::NodeIds, parser::Parser,
+    };
+    use crate::tests::before_each;
+
+    fn check(input: &str) -> Vec<ErrorCode> {
+        check_with(input, &IntrinsicRegistry::default())
+    }
+
+    fn check_with(input: &str, intrinsics: &IntrinsicRegistry) -> Vec<ErrorCode> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+
+        Checker::check(&ast, &[], intrinsics)
+            .into_iter()
+            .map(|error| error.error_code)
+            .collect()
+    }
+
+    #[test]
+    fn test_struct_types() {
+        before_each();
+        assert_eq!(
+            check("function f(u: User): list<User> { return [u]; } struct User { id: int }"),
+            vec![]
+        );
+        assert_eq!(
+            check("function f(u: Usr): int { return 1; }"),
+            vec![ErrorCode::InvalidParameterType]
+        );
+        assert_eq!(
+            check("function f(): map<string, Usr> { return {}; }"),
+            vec![ErrorCode::InvalidReturnType]
+        );
+    }
+
+    #[test]
+    fn test_type_arguments() {
+        before_each();
+        assert_eq!(
+            check("let x: map<string, list<int?>>? = {}; let f: fn((int, bool)) -> int = g;"),
+            vec![]
+        );
+        assert_eq!(
+            check("let x: map<string> = {};"),
+            vec![ErrorCode::WrongTypeArgumentCount]
+        );
+        assert_eq!(
+            check("let x: result<int> = @ok(1);"),
+            vec![ErrorCode::WrongTypeArgumentCount]
+        );
+        assert_eq!(
+            check("let x: int<string> = 1;"),
+            vec![ErrorCode::WrongTypeArgumentCount]
+        );
+        assert_eq!(
+            check("let x: list<nope>? = [];"),
+            vec![ErrorCode::InvalidParameterType]
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        before_each();
+        assert_eq!(
+            check("#[test] #[deprecated(\"use g\")] function f() {} #[index] struct S { id: int }"),
+            vec![]
+        );
+        assert_eq!(
+            check("#[inlined] function f() {}"),
+            vec![ErrorCode::UnknownAttribute]
+        );
+    }
+
+    #[test]
+    fn test_intrinsics() {
+        before_each();
+        assert_eq!(
+            check("let ok = f(); let xs = [1, 2]; let n = @len(xs); @print(n, @len(\"abc\") * 2); @assert(ok || n, \"len\");"),
+            vec![]
+        );
+        assert_eq!(
+            check("let xs = [1, 2]; let n = @len(xs); @print(n, @len(\"abc\") * 2); @assert(n == 2, \"len\");"),
+            vec![]
+        );
+        assert_eq!(check("@prnt(1);"), vec![ErrorCode::UnknownIntrinsic]);
+        assert_eq!(
+            check("let n = @len();"),
+            vec![ErrorCode::WrongArgumentCount]
+        );
+        assert_eq!(check("@print();"), vec![ErrorCode::WrongArgumentCount]);
+        assert_eq!(check("@panic(1);"), vec![ErrorCode::InvalidArgumentType]);
+        assert_eq!(
+            check("@assert(@len([]), \"empty\");"),
+            vec![ErrorCode::InvalidArgumentType]
+        );
+    }
+
+    #[test]
+    fn test_registered_intrinsics() {
+        before_each();
+        let mut intrinsics = IntrinsicRegistry::default();
+        intrinsics.register(Intrinsic::new(
+            "env",
+            vec![ParameterType::String],
+            ParameterType::Optional(Box::new(ParameterType::String)),
+        ));
+
+        assert_eq!(
+            check_with("let home = @env(\"HOME\");", &intrinsics),
+            vec![]
+        );
+        assert_eq!(
+            check("let home = @env(\"HOME\");"),
+            vec![ErrorCode::UnknownIntrinsic]
+        );
+    }
+
+    #[test]
+    fn test_struct_construction() {
+        before_each();
+        let declaration = "struct User { id: int, name: string }";
+
+        assert_eq!(
+            check(&format!(
+                "{} let u = User {{ id: 1, name: \"a\" }};",
+                declaration
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!(
+                "{} let u = User {{ id: 1, nme: \"a\" }};",
+                declaration
+            )),
+            vec![ErrorCode::UnknownStructField, ErrorCode::MissingStructField]
+        );
+        assert_eq!(
+            check("let u = Nobody { id: 1 };"),
+            vec![ErrorCode::UnknownStruct]
+        );
+        assert_eq!(
+            check(&format!("{} {}", declaration, declaration)),
+            vec![ErrorCode::DuplicateStructDeclaration]
+        );
+    }
+
+    #[test]
+    fn test_assignment_to_immutable() {
+        before_each();
+        assert_eq!(check("let mut x = 1; x = 2;"), vec![]);
+        assert_eq!(
+            check("let x = 1; x = 2;"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+        assert_eq!(
+            check("let rows = [{ id: 1 }]; rows[0].id = 2;"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+        assert_eq!(
+            check("const LIMIT = 10; LIMIT = 20;"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+        assert_eq!(
+            check("function f(n: int) { n = 1; }"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+        // Shadowing with a mutable binding in an inner scope is allowed
+        assert_eq!(check("let x = 1; { let mut x = 2; x = 3; }"), vec![]);
+        assert_eq!(
+            check("let x = 1; let y = if x > 0 { let mut x = 2; x = 3; x } else { x = 4; 0 };"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+    }
+
+    #[test]
+    fn test_for_loops() {
+        before_each();
+        assert_eq!(
+            check("let mut total = 0; for i in 0..10 { total = total + i; } for x in xs[1..] { }"),
+            vec![]
+        );
+        assert_eq!(
+            check("for i in 0..=10 { i = 2; }"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+    }
+
+    #[test]
+    fn test_null_safety() {
+        before_each();
+        let declarations =
+            "struct User { name: string } function find(id: int): User? { return null; }";
+
+        assert_eq!(
+            check(&format!(
+                "{} let user = find(1); let name = user?.name ?? \"?\"; let first = user?.name?[0];",
+                declarations
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!("{} let name = find(1).name;", declarations)),
+            vec![ErrorCode::NullableValue]
+        );
+        assert_eq!(
+            check(&format!("{} let user: User = find(1);", declarations)),
+            vec![ErrorCode::NullableValue]
+        );
+        assert_eq!(
+            check("function f(n: int?): int { return n; } function g(n: int?): int { n * 2 }"),
+            vec![ErrorCode::NullableValue, ErrorCode::NullableValue]
+        );
+        assert_eq!(
+            check("function f(n: int) { } let x: int? = null; f(x); f(x ?? 0);"),
+            vec![ErrorCode::NullableValue]
+        );
+    }
+
+    #[test]
+    fn test_null_narrowing() {
+        before_each();
+        assert_eq!(
+            check(
+                "function f(a: int?, b: int?): int { \
+                    if a != null && b != null { return a * b; } \
+                    if a == null || b == null { 0 } else { a + b } \
+                }"
+            ),
+            vec![]
+        );
+        assert_eq!(
+            check("function f(a: int?): int { let b = a != null && a > 0; if a == null { a * 2 } else { 0 } }"),
+            vec![ErrorCode::NullableValue]
+        );
+    }
+
+    #[test]
+    fn test_result_propagation() {
+        before_each();
+        let parse = "function parse(s: string): result<int, string> { @ok(1) }";
+
+        assert_eq!(
+            check(&format!(
+                "{} function double(s: string): result<int, string> {{ let n = parse(s)?; @ok(n * 2) }}",
+                parse
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!("{} let n = parse(\"1\")?;", parse)),
+            vec![ErrorCode::InvalidPropagation]
+        );
+        assert_eq!(
+            check(&format!(
+                "{} function f(s: string): int {{ parse(s)? }}",
+                parse
+            )),
+            vec![ErrorCode::InvalidPropagation]
+        );
+        assert_eq!(
+            check(&format!(
+                "{} function f(s: string): result<int, error> {{ parse(s)? }}",
+                parse
+            )),
+            vec![ErrorCode::InvalidPropagation]
+        );
+        assert_eq!(
+            check("function f(): result<int, string> { let n = 1?; @ok(n) }"),
+            vec![ErrorCode::InvalidPropagation]
+        );
+    }
+
+    #[test]
+    fn test_try_catch() {
+        before_each();
+        assert_eq!(
+            check("try { @panic(\"no\"); } catch (e) { @print(e); } let e = 1;"),
+            vec![]
+        );
+        assert_eq!(
+            check("try { } catch (e) { e = @error(\"other\"); }"),
+            vec![ErrorCode::AssignmentToImmutable]
+        );
+    }
+
+    #[test]
+    fn test_constant_values() {
+        before_each();
+        assert_eq!(
+            check("const BASE = 2; const LIMIT: int = BASE * 50; const NAMES = [\"a\", \"b\"]; const WINDOW = 0..LIMIT;"),
+            vec![]
+        );
+        assert_eq!(
+            check("let base = 2; const LIMIT = base * 50;"),
+            vec![ErrorCode::NonConstantExpression]
+        );
+        assert_eq!(
+            check("const LIMIT = compute();"),
+            vec![ErrorCode::NonConstantExpression]
+        );
+    }
+
+    #[test]
+    fn test_call_arguments() {
+        before_each();
+        let query = "function query(table: string, limit: int = 100, offset: int = 0) {}";
+
+        assert_eq!(
+            check(&format!(
+                "{} query(\"users\"); query(\"users\", 5); query(table: \"users\", offset: 10);",
+                query
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!("{} query(limit: 5);", query)),
+            vec![ErrorCode::MissingArgument]
+        );
+        assert_eq!(
+            check(&format!("{} query(\"users\", table: \"orders\");", query)),
+            vec![ErrorCode::DuplicateArgument]
+        );
+        assert_eq!(
+            check(&format!("{} query(\"users\", limt: 5);", query)),
+            vec![ErrorCode::UnknownArgument]
+        );
+        assert_eq!(
+            check(&format!("{} query(\"users\", 1, 2, 3);", query)),
+            vec![ErrorCode::WrongArgumentCount]
+        );
+    }
+
+    #[test]
+    fn test_variadic_parameters() {
+        before_each();
+        let sum = "function sum(first: int, ...rest: list<int>) {}";
+
+        assert_eq!(
+            check(&format!(
+                "{} sum(1); sum(1, 2, 3); sum(first: 1, rest: [2, 3]);",
+                sum
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!("{} let x: int? = null; sum(1, x);", sum)),
+            vec![ErrorCode::NullableValue]
+        );
+        assert_eq!(
+            check("function f(...rest: list<int>, last: int) {}"),
+            vec![ErrorCode::InvalidParameterOrder]
+        );
+        assert_eq!(
+            check("function f(...rest: int) {}"),
+            vec![ErrorCode::InvalidParameterType]
+        );
+        assert_eq!(
+            check("function f(limit: int = 1, table: string) {}"),
+            vec![ErrorCode::InvalidParameterOrder]
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        before_each();
+        let first =
+            "function first<T>(xs: list<T>): T? { if @len(xs) > 0 { xs[0] } else { null } }";
+        let max = "function max<T: Ord>(a: T, b: T): T { if a > b { a } else { b } }";
+
+        assert_eq!(
+            check(&format!(
+                "{} {} let n: int = max(1, 2); let s = first([\"a\"]);",
+                first, max
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!("{} let n: int = first([1, 2]);", first)),
+            vec![ErrorCode::NullableValue]
+        );
+        assert_eq!(
+            check(&format!("{} max(1, \"a\");", max)),
+            vec![ErrorCode::InvalidArgumentType]
+        );
+        assert_eq!(
+            check(&format!("{} max([1], [2]);", max)),
+            vec![ErrorCode::UnsatisfiedConstraint]
+        );
+        assert_eq!(
+            check("function max<T>(a: T, b: T): T { if a > b { a } else { b } }"),
+            vec![ErrorCode::UnsatisfiedConstraint]
+        );
+        assert_eq!(
+            check("function same<T: Ord>(a: T, b: T): bool { a == b }"),
+            vec![]
+        );
+        assert_eq!(
+            check("function f<T: Hash, T>(a: T) {}"),
+            vec![
+                ErrorCode::UnknownConstraint,
+                ErrorCode::DuplicateTypeParameter
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generators() {
+        before_each();
+        let rows = "function* rows(limit: int): iter<int> { for i in 0..limit { yield i; } }";
+
+        assert_eq!(
+            check(&format!(
+                "{} for row in rows(10) {{ let n: int = row; }} function* ticks() {{ yield; return; }}",
+                rows
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check("function* f(): iter<int> { let x: int? = null; yield x; }"),
+            vec![ErrorCode::NullableValue]
+        );
+        assert_eq!(
+            check("function* f(): iter<int> { return 1; }"),
+            vec![ErrorCode::InvalidReturnType]
+        );
+        assert_eq!(
+            check("function* f(): list<int> { yield 1; }"),
+            vec![ErrorCode::InvalidReturnType]
+        );
+        assert_eq!(
+            check("function f() { yield 1; }"),
+            vec![ErrorCode::InvalidYield]
+        );
+        assert_eq!(
+            check("function* f() { let g = || { yield 1; }; }"),
+            vec![ErrorCode::InvalidYield]
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        before_each();
+        let declaration = "enum Status { Active, Suspended(reason: string, days: int) }";
+
+        assert_eq!(
+            check(&format!(
+                "{} let a: Status = Status.Active; let s = Status.Suspended(\"spam\", days: 3); const C = Status.Active;",
+                declaration
+            )),
+            vec![]
+        );
+        assert_eq!(
+            check(&format!("{} let a = Status.Activ;", declaration)),
+            vec![ErrorCode::UnknownEnumVariant]
+        );
+        assert_eq!(
+            check(&format!(
+                "{} let s = Status.Suspended(\"spam\"); let t = Status.Suspended(\"a\", 1, 2);",
+                declaration
+            )),
+            vec![ErrorCode::MissingArgument, ErrorCode::WrongArgumentCount]
+        );
+        assert_eq!(
+            check(&format!(
+                "{} let s = Status.Suspended(reason: null, dayz: 1);",
+                declaration
+            )),
+            vec![
+                ErrorCode::NullableValue,
+                ErrorCode::UnknownArgument,
+                ErrorCode::MissingArgument
+            ]
+        );
+        assert_eq!(
+            check(&format!("{} enum Status {{ Closed }}", declaration)),
+            vec![ErrorCode::DuplicateEnumDeclaration]
+        );
+        assert_eq!(
+            check("enum Wrapper { Of(value: Missing) }"),
+            vec![ErrorCode::InvalidParameterType]
+        );
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        before_each();
+        let declaration = "enum Status { Active, Suspended(reason: string, days: int), Closed }";
+        let matched = |arms: &str| {
+            check(&format!(
+                "{} function f(s: Status): int {{ return match s {{ {} }}; }}",
+                declaration, arms
+            ))
+        };
+
+        assert_eq!(
+            matched("Status.Active => 1, Status.Suspended(_, 0) => 2, Status.Suspended(r, d) if d > 1 => 3, Status.Suspended(_, _) => 4, Status.Closed => 5"),
+            vec![]
+        );
+        assert_eq!(matched("Status.Active => 1, other => 2"), vec![]);
+        // A guarded arm doesn't cover the values it matches
+        assert_eq!(
+            matched("Status.Active => 1, Status.Suspended(_, d) if d > 1 => 2, Status.Closed => 3"),
+            vec![ErrorCode::NonExhaustiveMatch]
+        );
+        assert_eq!(
+            matched("_ => 1, Status.Active => 2"),
+            vec![ErrorCode::UnreachableMatchArm]
+        );
+        assert_eq!(
+            matched("Status.Suspended(_, 0) => 1, Status.Suspended(_, 0) => 2, _ => 3"),
+            vec![ErrorCode::UnreachableMatchArm]
+        );
+        assert_eq!(
+            matched("Status.Active(x) => 1, _ => 2"),
+            vec![ErrorCode::InvalidPattern]
+        );
+        assert_eq!(
+            matched("Status.Paused => 1, _ => 2"),
+            vec![ErrorCode::UnknownEnumVariant]
+        );
+        assert_eq!(
+            matched("\"active\" => 1, _ => 2"),
+            vec![ErrorCode::InvalidPattern]
+        );
+
+        assert_eq!(
+            check("let b = match 1 > 2 { true => 1, false => 0 };"),
+            vec![]
+        );
+        assert_eq!(
+            check("let b = match 1 > 2 { true => 1 };"),
+            vec![ErrorCode::NonExhaustiveMatch]
+        );
+        assert_eq!(
+            check("let n = match 3 { 1 => \"one\", 2 => \"two\" };"),
+            vec![ErrorCode::NonExhaustiveMatch]
+        );
+        assert_eq!(
+            check("function f(n: int?): int { return match n { null => 0, n => n }; }"),
+            vec![]
+        );
+    }
+}
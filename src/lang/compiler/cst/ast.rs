@@ -0,0 +1,553 @@
+//! Typed wrappers over [`SyntaxNode`]s, which name the parts of each kind of node.
+//!
+//! Every accessor returns `None` or nothing when that part is missing, since the tree may
+//! come from source with errors in it.
+
+use super::{SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::lang::compiler::token::TokenType;
+
+pub(crate) trait AstNode: Sized {
+    /// Wraps `node` if it has the right kind.
+    fn cast(node: SyntaxNode) -> Option<Self>;
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+macro_rules! ast_node {
+    ($($(#[$meta:meta])* $name:ident),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone)]
+            pub(crate) struct $name(SyntaxNode);
+
+            impl AstNode for $name {
+                fn cast(node: SyntaxNode) -> Option<$name> {
+                    (node.kind() == SyntaxKind::$name).then_some($name(node))
+                }
+
+                fn syntax(&self) -> &SyntaxNode {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+ast_node!(
+    Root,
+    Attribute,
+    Import,
+    Macro,
+    Function,
+    TypeParameter,
+    Parameter,
+    Let,
+    Const,
+    Struct,
+    StructField,
+    Enum,
+    EnumVariant,
+);
+
+/// Top-level statements that declare something, which are what an outline lists.
+#[derive(Debug, Clone)]
+pub(crate) enum Item {
+    Import(Import),
+    Macro(Macro),
+    Function(Function),
+    Let(Let),
+    Const(Const),
+    Struct(Struct),
+    Enum(Enum),
+}
+
+impl AstNode for Item {
+    fn cast(node: SyntaxNode) -> Option<Item> {
+        Some(match node.kind() {
+            SyntaxKind::Import => Item::Import(Import(node)),
+            SyntaxKind::Macro => Item::Macro(Macro(node)),
+            SyntaxKind::Function => Item::Function(Function(node)),
+            SyntaxKind::Let => Item::Let(Let(node)),
+            SyntaxKind::Const => Item::Const(Const(node)),
+            SyntaxKind::Struct => Item::Struct(Struct(node)),
+            SyntaxKind::Enum => Item::Enum(Enum(node)),
+            _ => return None,
+        })
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Item::Import(node) => node.syntax(),
+            Item::Macro(node) => node.syntax(),
+            Item::Function(node) => node.syntax(),
+            Item::Let(node) => node.syntax(),
+            Item::Const(node) => node.syntax(),
+            Item::Struct(node) => node.syntax(),
+            Item::Enum(node) => node.syntax(),
+        }
+    }
+}
+
+/// Any expression node.
+#[derive(Debug, Clone)]
+pub(crate) struct Expression(SyntaxNode);
+
+impl AstNode for Expression {
+    fn cast(node: SyntaxNode) -> Option<Expression> {
+        matches!(
+            node.kind(),
+            SyntaxKind::Literal
+                | SyntaxKind::Name
+                | SyntaxKind::Paren
+                | SyntaxKind::Unary
+                | SyntaxKind::Binary
+                | SyntaxKind::Assignment
+                | SyntaxKind::Range
+                | SyntaxKind::Coalesce
+                | SyntaxKind::Call
+                | SyntaxKind::Member
+                | SyntaxKind::Index
+                | SyntaxKind::Propagate
+                | SyntaxKind::List
+                | SyntaxKind::Record
+                | SyntaxKind::StructLiteral
+                | SyntaxKind::Closure
+                | SyntaxKind::If
+                | SyntaxKind::Match
+                | SyntaxKind::Intrinsic
+                | SyntaxKind::Yield
+                | SyntaxKind::Block
+        )
+        .then_some(Expression(node))
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        &self.0
+    }
+}
+
+/// Any type, such as `int`, `list<int>?` or `fn(int) -> bool`.
+#[derive(Debug, Clone)]
+pub(crate) struct Type(SyntaxNode);
+
+impl AstNode for Type {
+    fn cast(node: SyntaxNode) -> Option<Type> {
+        matches!(
+            node.kind(),
+            SyntaxKind::NamedType
+                | SyntaxKind::OptionalType
+                | SyntaxKind::TupleType
+                | SyntaxKind::FunctionType
+        )
+        .then_some(Type(node))
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        &self.0
+    }
+}
+
+fn token(node: &SyntaxNode, type_: TokenType) -> Option<SyntaxToken> {
+    node.tokens()
+        .find(|token| token.kind() == SyntaxKind::Token(type_))
+}
+
+fn child<N: AstNode>(node: &SyntaxNode) -> Option<N> {
+    node.children().find_map(N::cast)
+}
+
+fn children<N: AstNode>(node: &SyntaxNode) -> impl Iterator<Item = N> {
+    node.children().filter_map(N::cast)
+}
+
+/// The nodes of kind `N` inside the child list of kind `list`, such as the parameters of a
+/// function.
+fn list<N: AstNode>(node: &SyntaxNode, list: SyntaxKind) -> Vec<N> {
+    node.children()
+        .find(|node| node.kind() == list)
+        .map_or_else(Vec::new, |list| children(&list).collect())
+}
+
+/// Nodes that introduce a name, such as the `add` of `function add(...)`.
+pub(crate) trait HasName: AstNode {
+    fn name(&self) -> Option<SyntaxToken> {
+        token(self.syntax(), TokenType::Identifier)
+    }
+}
+
+impl HasName for Macro {}
+impl HasName for Function {}
+impl HasName for Parameter {}
+impl HasName for Let {}
+impl HasName for Const {}
+impl HasName for Struct {}
+impl HasName for StructField {}
+impl HasName for Enum {}
+impl HasName for EnumVariant {}
+
+/// Declarations, which can have attributes and be exported.
+pub(crate) trait Declaration: HasName {
+    fn attributes(&self) -> Vec<Attribute> {
+        children(self.syntax()).collect()
+    }
+
+    fn is_exported(&self) -> bool {
+        token(self.syntax(), TokenType::Export).is_some()
+    }
+
+    /// The attributes and `export` in front of the declaration, as `signature` shows them.
+    fn modifiers(&self) -> String {
+        let mut modifiers: Vec<String> = self
+            .attributes()
+            .iter()
+            .map(|attribute| attribute.syntax().text())
+            .collect();
+        if self.is_exported() {
+            modifiers.push(String::from("export"));
+        }
+
+        modifiers
+            .into_iter()
+            .map(|modifier| modifier + " ")
+            .collect()
+    }
+}
+
+impl Declaration for Function {}
+impl Declaration for Let {}
+impl Declaration for Const {}
+impl Declaration for Struct {}
+impl Declaration for Enum {}
+
+/// The text of a name, or nothing if it is missing.
+fn name(name: Option<SyntaxToken>) -> String {
+    name.map_or_else(String::new, |name| String::from(name.text()))
+}
+
+/// `: type` if there is a type, as written after a name.
+fn annotation(type_: Option<Type>) -> String {
+    type_.map_or_else(String::new, |type_| format!(": {}", type_.syntax()))
+}
+
+fn fields(fields: impl Iterator<Item = StructField>) -> String {
+    fields
+        .map(|field| format!("{}{}", name(field.name()), annotation(field.type_())))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Root {
+    /// The declarations of the file.
+    pub(crate) fn items(&self) -> impl Iterator<Item = Item> {
+        children(&self.0)
+    }
+
+    /// Each declaration of the file with where it is, one per line, as `--cst` prints them.
+    pub(crate) fn outline(&self) -> String {
+        self.items()
+            .map(|item| format!("{:?} {}\n", item.syntax().span(), item.signature()))
+            .collect()
+    }
+}
+
+impl Item {
+    /// The declaration without its body or value, such as `function add(a: int): int`.
+    pub(crate) fn signature(&self) -> String {
+        match self {
+            Item::Import(import) => {
+                let path = name(import.path());
+                match import.names() {
+                    Some(names) => {
+                        let names: Vec<&str> = names.iter().map(|name| name.text()).collect();
+                        format!("import {{ {} }} from {}", names.join(", "), path)
+                    }
+                    None => format!("import {}", path),
+                }
+            }
+            Item::Macro(macro_) => format!("macro {}!", name(macro_.name())),
+            Item::Function(function) => {
+                let mut signature = format!(
+                    "{}function{} {}",
+                    function.modifiers(),
+                    if function.is_generator() { "*" } else { "" },
+                    name(function.name())
+                );
+
+                let type_parameters: Vec<String> = function
+                    .type_parameters()
+                    .iter()
+                    .map(|parameter| parameter.syntax().text())
+                    .collect();
+                if !type_parameters.is_empty() {
+                    signature += &format!("<{}>", type_parameters.join(", "));
+                }
+
+                let parameters: Vec<String> = function
+                    .parameters()
+                    .iter()
+                    .map(|parameter| {
+                        format!(
+                            "{}{}{}{}",
+                            if parameter.is_variadic() { "..." } else { "" },
+                            name(parameter.name()),
+                            annotation(parameter.type_()),
+                            parameter.default().map_or_else(String::new, |default| {
+                                format!(" = {}", default.syntax())
+                            })
+                        )
+                    })
+                    .collect();
+                signature
+                    + &format!(
+                        "({}){}",
+                        parameters.join(", "),
+                        annotation(function.return_type())
+                    )
+            }
+            Item::Let(let_) => format!(
+                "{}let {}{}{}",
+                let_.modifiers(),
+                if let_.is_mutable() { "mut " } else { "" },
+                name(let_.name()),
+                annotation(let_.type_())
+            ),
+            Item::Const(const_) => format!(
+                "{}const {}{}",
+                const_.modifiers(),
+                name(const_.name()),
+                annotation(const_.type_())
+            ),
+            Item::Struct(struct_) => format!(
+                "{}struct {} {{ {} }}",
+                struct_.modifiers(),
+                name(struct_.name()),
+                fields(struct_.fields())
+            ),
+            Item::Enum(enum_) => {
+                let variants: Vec<String> = enum_
+                    .variants()
+                    .map(|variant| {
+                        let mut fields_ = variant.fields().peekable();
+                        match fields_.peek() {
+                            Some(_) => format!("{}({})", name(variant.name()), fields(fields_)),
+                            None => name(variant.name()),
+                        }
+                    })
+                    .collect();
+                format!(
+                    "{}enum {} {{ {} }}",
+                    enum_.modifiers(),
+                    name(enum_.name()),
+                    variants.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl Import {
+    /// The path string, quotes included.
+    pub(crate) fn path(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::StringLiteral)
+    }
+
+    /// The names between the braces of `import { a, b } from "..."`, if there are braces.
+    pub(crate) fn names(&self) -> Option<Vec<SyntaxToken>> {
+        self.0
+            .children()
+            .find(|node| node.kind() == SyntaxKind::ImportNames)
+            .map(|names| {
+                names
+                    .tokens()
+                    .filter(|token| token.kind() == SyntaxKind::Token(TokenType::Identifier))
+                    .collect()
+            })
+    }
+}
+
+impl Function {
+    /// Whether this is a generator, declared with `function*`.
+    pub(crate) fn is_generator(&self) -> bool {
+        token(&self.0, TokenType::Asterisk).is_some()
+    }
+
+    pub(crate) fn type_parameters(&self) -> Vec<TypeParameter> {
+        list(&self.0, SyntaxKind::TypeParameterList)
+    }
+
+    pub(crate) fn parameters(&self) -> Vec<Parameter> {
+        list(&self.0, SyntaxKind::ParameterList)
+    }
+
+    pub(crate) fn return_type(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl Parameter {
+    pub(crate) fn is_variadic(&self) -> bool {
+        token(&self.0, TokenType::DotDotDot).is_some()
+    }
+
+    pub(crate) fn type_(&self) -> Option<Type> {
+        child(&self.0)
+    }
+
+    pub(crate) fn default(&self) -> Option<Expression> {
+        child(&self.0)
+    }
+}
+
+impl Let {
+    pub(crate) fn is_mutable(&self) -> bool {
+        token(&self.0, TokenType::Mut).is_some()
+    }
+
+    pub(crate) fn type_(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl Const {
+    pub(crate) fn type_(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl Struct {
+    pub(crate) fn fields(&self) -> impl Iterator<Item = StructField> {
+        children(&self.0)
+    }
+}
+
+impl StructField {
+    pub(crate) fn type_(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl Enum {
+    pub(crate) fn variants(&self) -> impl Iterator<Item = EnumVariant> {
+        children(&self.0)
+    }
+}
+
+impl EnumVariant {
+    pub(crate) fn fields(&self) -> impl Iterator<Item = StructField> {
+        children(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::cst;
+    use crate::tests::before_each;
+
+    fn root(input: &str) -> Root {
+        let parse = cst::parse(input);
+        assert!(parse.errors.is_empty());
+        parse.root()
+    }
+
+    #[test]
+    fn test_typed_function() {
+        before_each();
+        let root = root(
+            "#[inline] export function add<T: Ord>(a: int, b: int = 1): int {\n    a + b // sum\n}",
+        );
+
+        let Some(Item::Function(function)) = root.items().next() else {
+            panic!("Expected function");
+        };
+        assert_eq!(function.name().unwrap().text(), "add");
+        assert!(function.is_exported());
+        assert_eq!(function.attributes()[0].syntax().text(), "#[inline]");
+        assert_eq!(function.type_parameters()[0].syntax().text(), "T: Ord");
+
+        let parameters: Vec<String> = function
+            .parameters()
+            .iter()
+            .map(|parameter| String::from(parameter.name().unwrap().text()))
+            .collect();
+        assert_eq!(parameters, vec!["a", "b"]);
+        assert_eq!(
+            function.parameters()[1].default().unwrap().syntax().text(),
+            "1"
+        );
+        assert_eq!(function.return_type().unwrap().syntax().text(), "int");
+    }
+
+    #[test]
+    fn test_typed_declarations() {
+        before_each();
+        let root = root(concat!(
+            "import { a, b } from \"lib.lit\";\n",
+            "let mut x: int? = f(1, limit: 2);\n",
+            "x;\n",
+            "struct User { id: int }\n",
+            "function* ids(...rest: list<int>) {}",
+        ));
+        let items: Vec<Item> = root.items().collect();
+        assert_eq!(items.len(), 4);
+
+        let Item::Import(import) = &items[0] else {
+            panic!("Expected import");
+        };
+        assert_eq!(import.path().unwrap().text(), "\"lib.lit\"");
+        assert_eq!(import.names().unwrap().len(), 2);
+
+        let Item::Let(let_) = &items[1] else {
+            panic!("Expected let");
+        };
+        assert!(let_.is_mutable());
+        assert_eq!(
+            let_.type_().unwrap().syntax().kind(),
+            SyntaxKind::OptionalType
+        );
+
+        let Item::Struct(struct_) = &items[2] else {
+            panic!("Expected struct");
+        };
+        let fields: Vec<StructField> = struct_.fields().collect();
+        assert_eq!(fields[0].name().unwrap().text(), "id");
+        assert_eq!(fields[0].type_().unwrap().syntax().text(), "int");
+
+        let Item::Function(function) = &items[3] else {
+            panic!("Expected function");
+        };
+        assert!(function.is_generator());
+        let parameter = &function.parameters()[0];
+        assert!(parameter.is_variadic());
+        assert_eq!(
+            parameter.type_().unwrap().syntax().kind(),
+            SyntaxKind::NamedType
+        );
+    }
+
+    #[test]
+    fn test_outline() {
+        before_each();
+        let root = root(concat!(
+            "import \"lib.lit\";\n",
+            "macro twice(x) { x + x }\n",
+            "#[deprecated(\"old\")] export const LIMIT: int = twice!(3);\n",
+            "enum Status { Active, Suspended(reason: string) }\n",
+            "function* ids<T>(...rest: list<T>, limit: int = 10): iter<T> {\n",
+            "    for id in rest { yield id; }\n",
+            "}\n",
+            "let mut total = 0;",
+        ));
+
+        assert_eq!(
+            root.outline(),
+            concat!(
+                "0..17 import \"lib.lit\"\n",
+                "18..42 macro twice!\n",
+                "43..100 #[deprecated(\"old\")] export const LIMIT: int\n",
+                "101..150 enum Status { Active, Suspended(reason: string) }\n",
+                "151..248 function* ids<T>(...rest: list<T>, limit: int = 10): iter<T>\n",
+                "249..267 let mut total\n",
+            )
+        );
+    }
+}
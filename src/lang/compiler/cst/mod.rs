@@ -0,0 +1,648 @@
+//! A lossless concrete syntax tree, for tools that need to see and rewrite the source exactly
+//! as it was written.
+//!
+//! The tree comes in two layers. Green nodes hold the kind and text of each piece of syntax,
+//! including whitespace, comments and tokens the parser couldn't make sense of, but not their
+//! positions, so identical subtrees can be shared. Red nodes ([`SyntaxNode`]) are built on
+//! demand on top of them and know where they are in the source. The typed wrappers in `ast`
+//! give names to the parts of each node.
+//!
+//! The tree is built by the same parser as the [`AST`](super::parser::AST) the checker works
+//! on, through a [`SyntaxSink`] on its token stream. Offsets are counted in characters, like
+//! token spans.
+
+pub(crate) mod ast;
+pub(crate) mod sink;
+
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use self::{
+    ast::{AstNode, Root},
+    sink::SyntaxSink,
+};
+use super::{
+    lexer::Lexer,
+    macros::{Expansion, MacroExpander},
+    statement::Statement,
+    token::{Span, Token, TokenStream, TokenType},
+    CompilerError,
+};
+
+/// Tokens that start a statement at the top level, where parsing picks up again after an error.
+const STATEMENT_STARTS: [TokenType; 9] = [
+    TokenType::Const,
+    TokenType::Enum,
+    TokenType::Export,
+    TokenType::Function,
+    TokenType::Hash,
+    TokenType::Import,
+    TokenType::Let,
+    TokenType::Macro,
+    TokenType::Struct,
+];
+
+/// A concrete syntax tree, along with the errors found while building it.
+pub(crate) struct Parse {
+    pub(crate) green: Rc<GreenNode>,
+    pub(crate) errors: Vec<CompilerError>,
+    /// The macro expansions the spans of `errors` may point into.
+    pub(crate) expansions: Vec<Expansion>,
+}
+
+impl Parse {
+    pub(crate) fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    /// The typed root of the tree.
+    pub(crate) fn root(&self) -> Root {
+        Root::cast(self.syntax()).expect("The tree always has a root node")
+    }
+}
+
+/// Parses `source` into a concrete syntax tree.
+///
+/// Unlike [`Compiler::parse_source`](super::Compiler::parse_source), it doesn't stop at the
+/// first error. The rest of a statement that fails to parse is wrapped in a
+/// [`SyntaxKind::Error`] node, so the tree always covers the whole source.
+pub(crate) fn parse(source: &str) -> Parse {
+    let mut lexer = Lexer::new();
+    lexer.lex(source);
+    let lexed: Vec<Token> = lexer.get_tokens_peekable().cloned().collect();
+
+    let mut errors = Vec::new();
+    let (tokens, expansions) =
+        match MacroExpander::expand(&mut TokenStream::new(&lexed), source.len()) {
+            Ok((tokens, expansions)) => (tokens, Some(expansions)),
+            Err(error) => {
+                errors.push(*error);
+                (lexed.clone(), None)
+            }
+        };
+
+    let sink = SyntaxSink::new(source, lexed, expansions.clone());
+    let mut tokens = TokenStream::with_syntax(&tokens, sink);
+    while tokens.peek().is_some() {
+        let remaining = tokens.as_slice().len();
+        if let Err(error) = Statement::parse_top_level(&mut tokens) {
+            errors.push(*error);
+            recover(&mut tokens, remaining);
+        }
+    }
+
+    Parse {
+        green: tokens.into_syntax().unwrap().finish(),
+        errors,
+        expansions: expansions.unwrap_or_default(),
+    }
+}
+
+/// Finishes the nodes of a statement that failed to parse, and wraps what is left of it in an
+/// error node, up to the next `;` or the start of the next declaration.
+fn recover(tokens: &mut TokenStream, remaining: usize) {
+    while tokens.syntax_depth() > 1 {
+        tokens.finish_node();
+    }
+
+    let mut depth = 0usize;
+    let stop = |tokens: &TokenStream, depth: usize| {
+        tokens.peek().is_none_or(|token| {
+            depth == 0
+                && STATEMENT_STARTS.contains(&token.type_)
+                && tokens.as_slice().len() < remaining
+        })
+    };
+    if stop(tokens, depth) {
+        return;
+    }
+
+    tokens.start_node(SyntaxKind::Error);
+    while !stop(tokens, depth) {
+        let Some(token) = tokens.next() else {
+            break;
+        };
+
+        match token.type_ {
+            TokenType::LeftParen
+            | TokenType::LeftBracket
+            | TokenType::LeftBrace
+            | TokenType::QuestionLeftBracket => depth += 1,
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                depth = depth.saturating_sub(1)
+            }
+            TokenType::Semicolon if depth == 0 => break,
+            _ => {}
+        }
+    }
+    tokens.finish_node();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum SyntaxKind {
+    /// A token produced by the lexer.
+    Token(TokenType),
+    Whitespace,
+    /// A `//` line comment, up to but not including the newline.
+    Comment,
+
+    Root,
+    /// Tokens that don't fit the grammar where they appear.
+    Error,
+    Attribute,
+    Import,
+    ImportNames,
+    /// `macro name(a, b) { ... }`, with its parameters and body kept as unparsed
+    /// [`SyntaxKind::TokenTree`]s.
+    Macro,
+    /// Balanced delimiters and everything between them, as in a macro body or call.
+    TokenTree,
+    Function,
+    TypeParameterList,
+    TypeParameter,
+    ParameterList,
+    Parameter,
+    Let,
+    Const,
+    Struct,
+    StructField,
+    Enum,
+    /// `Suspended(reason: string)`, whose fields are [`SyntaxKind::StructField`]s.
+    EnumVariant,
+    Block,
+    For,
+    While,
+    Break,
+    Continue,
+    Return,
+    Try,
+    ExpressionStatement,
+
+    NamedType,
+    TypeArgumentList,
+    OptionalType,
+    TupleType,
+    FunctionType,
+
+    Literal,
+    /// An identifier used as a value, such as `x` in `x + 1`.
+    Name,
+    Paren,
+    Unary,
+    Binary,
+    Assignment,
+    Range,
+    Coalesce,
+    Call,
+    ArgumentList,
+    Argument,
+    Member,
+    Index,
+    Propagate,
+    List,
+    Record,
+    RecordField,
+    StructLiteral,
+    Closure,
+    If,
+    Match,
+    MatchArm,
+    /// The left side of a match arm. A variant pattern has a pattern for each of its fields.
+    Pattern,
+    Intrinsic,
+    MacroCall,
+    Yield,
+}
+
+impl SyntaxKind {
+    pub(crate) fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+    }
+}
+
+/// A leaf of the green tree: its kind and exact source text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+    len: usize,
+}
+
+/// An inner node of the green tree. Its length is the sum of its children's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.len,
+        }
+    }
+}
+
+impl GreenNode {
+    fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Rc<GreenNode> {
+        let len = children.iter().map(GreenElement::len).sum();
+        Rc::new(GreenNode {
+            kind,
+            len,
+            children,
+        })
+    }
+
+    /// The source text this node covers, trivia included.
+    pub(crate) fn text(&self) -> String {
+        let mut text = String::new();
+        self.write_text(&mut text);
+        text
+    }
+
+    fn write_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(text),
+                GreenElement::Token(token) => text.push_str(&token.text),
+            }
+        }
+    }
+}
+
+/// Where a node will start if it's wrapped around what has been built since.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Checkpoint(usize);
+
+/// Builds a green tree bottom up. Tokens with the same kind and text share one allocation.
+#[derive(Debug, Default)]
+pub(crate) struct GreenBuilder {
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+    tokens: HashMap<(SyntaxKind, String), Rc<GreenToken>>,
+    /// Whether the last child is the node finished last, rather than one that was left out.
+    finished: bool,
+}
+
+impl GreenBuilder {
+    pub(crate) fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    /// Starts a node that takes in everything built since `checkpoint`, such as the left side
+    /// of a binary expression once its operator is seen.
+    pub(crate) fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        self.parents.push((kind, checkpoint.0));
+    }
+
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+
+    pub(crate) fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.finished = false;
+        let token = self
+            .tokens
+            .entry((kind, String::from(text)))
+            .or_insert_with(|| {
+                Rc::new(GreenToken {
+                    kind,
+                    text: String::from(text),
+                    len: text.chars().count(),
+                })
+            })
+            .clone();
+        self.children.push(GreenElement::Token(token));
+    }
+
+    /// Finishes the innermost node. An empty node other than the root is left out, such as one
+    /// around code that only exists in a macro expansion.
+    pub(crate) fn finish_node(&mut self) {
+        let (kind, first) = self.parents.pop().expect("No node to finish");
+        self.finished = first < self.children.len() || self.parents.is_empty();
+        if !self.finished {
+            return;
+        }
+
+        let children: Vec<GreenElement> = self.children.drain(first..).collect();
+        self.children
+            .push(GreenElement::Node(GreenNode::new(kind, children)));
+    }
+
+    /// Moves everything built since `checkpoint` into the start of the node finished last, such
+    /// as the attributes of the declaration after them.
+    pub(crate) fn attach(&mut self, checkpoint: Checkpoint) {
+        if !self.finished || checkpoint.0 >= self.children.len() {
+            return;
+        }
+        let Some(GreenElement::Node(node)) = self.children.pop() else {
+            unreachable!("The node finished last is the last child");
+        };
+
+        let mut children: Vec<GreenElement> = self.children.drain(checkpoint.0..).collect();
+        children.extend(node.children.iter().cloned());
+        self.children
+            .push(GreenElement::Node(GreenNode::new(node.kind, children)));
+    }
+
+    /// How many nodes are open.
+    pub(crate) fn depth(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Returns the root, which must be the only node left.
+    pub(crate) fn finish(mut self) -> Rc<GreenNode> {
+        assert!(self.parents.is_empty(), "Unfinished nodes");
+        match self.children.pop() {
+            Some(GreenElement::Node(root)) if self.children.is_empty() => root,
+            _ => panic!("Expected a single root node"),
+        }
+    }
+}
+
+/// A node of the red tree: a green node with its position in the source.
+#[derive(Clone)]
+pub(crate) struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SyntaxToken {
+    green: Rc<GreenToken>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub(crate) fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData { green, offset: 0 }))
+    }
+
+    pub(crate) fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub(crate) fn span(&self) -> Span {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.0.green.text()
+    }
+
+    /// Child nodes and tokens, trivia included, in source order.
+    pub(crate) fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: green.clone(),
+                            offset,
+                        })))
+                    }
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        offset,
+                    }),
+                };
+                offset += child.len();
+                element
+            })
+            .collect()
+    }
+
+    pub(crate) fn children(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+    }
+
+    /// The tokens directly inside this node, without trivia.
+    pub(crate) fn tokens(&self) -> impl Iterator<Item = SyntaxToken> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Token(token) if !token.kind().is_trivia() => Some(token),
+                _ => None,
+            })
+    }
+}
+
+impl SyntaxToken {
+    pub(crate) fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub(crate) fn span(&self) -> Span {
+        self.offset..self.offset + self.green.len
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    /// Prints the tree one element per line, indented by depth, as `--cst` shows it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write(f: &mut fmt::Formatter<'_>, node: &SyntaxNode, depth: usize) -> fmt::Result {
+            writeln!(
+                f,
+                "{:indent$}{:?}@{:?}",
+                "",
+                node.kind(),
+                node.span(),
+                indent = depth * 2
+            )?;
+
+            for child in node.children_with_tokens() {
+                match child {
+                    SyntaxElement::Node(child) => write(f, &child, depth + 1)?,
+                    SyntaxElement::Token(token) => writeln!(
+                        f,
+                        "{:indent$}{:?}@{:?} {:?}",
+                        "",
+                        token.kind(),
+                        token.span(),
+                        token.text(),
+                        indent = (depth + 1) * 2
+                    )?,
+                }
+            }
+
+            Ok(())
+        }
+
+        write(f, self, 0)
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{
+        cst::ast::{HasName, Item},
+        lexer::Lexer,
+        node::NodeIds,
+        Compiler,
+    };
+    use crate::lang::util::error_logger::ErrorLogger;
+    use crate::tests::before_each;
+
+    /// The node and every node inside it, in source order.
+    fn descendants(node: &SyntaxNode) -> Vec<SyntaxNode> {
+        let mut nodes = vec![node.clone()];
+        for child in node.children() {
+            nodes.extend(descendants(&child));
+        }
+        nodes
+    }
+
+    fn errors(node: &SyntaxNode) -> Vec<String> {
+        descendants(node)
+            .into_iter()
+            .filter(|node| node.kind() == SyntaxKind::Error)
+            .map(|node| node.text())
+            .collect()
+    }
+
+    #[test]
+    fn test_cst_lossless() {
+        before_each();
+
+        for entry in std::fs::read_dir("tests/e2e").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("lit") {
+                continue;
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            let name = path.display().to_string();
+            let parse = parse(&source);
+            let root = parse.syntax();
+
+            assert_eq!(root.text(), source, "{}", name);
+            assert!(
+                parse.errors.is_empty(),
+                "{}: {}",
+                name,
+                parse.errors[0].error_message
+            );
+            assert!(errors(&root).is_empty(), "{}", name);
+
+            // The typed layer sees the same declarations as the parser, unless macros add some
+            let error_logger = ErrorLogger::new(&name, &source);
+            let ast = Compiler::parse_source(
+                &mut Lexer::new(),
+                &source,
+                &error_logger,
+                &mut NodeIds::default(),
+            )
+            .unwrap_or_else(|err| panic!("Failed to parse {}: {}", name, err));
+            if !ast.expansions.is_empty() {
+                continue;
+            }
+
+            let expected: Vec<&str> = ast
+                .statements
+                .iter()
+                .filter_map(|statement| statement.kind.declared_name())
+                .collect();
+            let declared: Vec<String> = parse
+                .root()
+                .items()
+                .filter_map(|item| match item {
+                    Item::Function(function) => function.name(),
+                    Item::Let(let_) => let_.name(),
+                    Item::Const(const_) => const_.name(),
+                    Item::Struct(struct_) => struct_.name(),
+                    Item::Enum(enum_) => enum_.name(),
+                    _ => None,
+                })
+                .map(|name| String::from(name.text()))
+                .collect();
+            assert_eq!(declared, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_cst_trivia_and_spans() {
+        before_each();
+        let source = "// answer\nconst x = \"a\\n\"; // trailing\n";
+        let root = parse(source).syntax();
+        assert_eq!(root.text(), source);
+
+        let elements = root.children_with_tokens();
+        let SyntaxElement::Token(comment) = &elements[0] else {
+            panic!("Expected a leading comment");
+        };
+        assert_eq!(
+            (comment.kind(), comment.text()),
+            (SyntaxKind::Comment, "// answer")
+        );
+
+        let const_ = root.children().next().unwrap();
+        assert_eq!(const_.kind(), SyntaxKind::Const);
+        assert_eq!(const_.span(), 10..26);
+
+        // String tokens keep their escapes as written
+        let string = const_
+            .children()
+            .find(|node| node.kind() == SyntaxKind::Literal)
+            .unwrap();
+        assert_eq!(string.text(), "\"a\\n\"");
+    }
+
+    #[test]
+    fn test_cst_error_recovery() {
+        before_each();
+        let source = "let x = ;\nfunction f(a b) { ) }\nconst y = 2;\nlet z = \"open";
+        let parse = parse(source);
+        let root = parse.syntax();
+
+        assert_eq!(root.text(), source);
+        assert_eq!(errors(&root), vec![") { ) }"]);
+        assert_eq!(parse.errors.len(), 3);
+
+        let kinds: Vec<SyntaxKind> = root.children().map(|node| node.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SyntaxKind::Let,
+                SyntaxKind::Function,
+                SyntaxKind::Error,
+                SyntaxKind::Const,
+                SyntaxKind::Let
+            ]
+        );
+    }
+}
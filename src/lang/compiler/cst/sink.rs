@@ -0,0 +1,306 @@
+use std::{ops::Range, rc::Rc};
+
+use super::{Checkpoint, GreenBuilder, GreenNode, SyntaxKind};
+use crate::lang::compiler::{
+    macros::Expansion,
+    token::{Token, TokenType},
+};
+
+/// Builds the concrete syntax tree from what [`Parser`] does with the token stream.
+///
+/// The parser reports where nodes start and finish, and every token it consumes. The sink fills
+/// in everything the parser doesn't see: whitespace and comments, macro definitions, and macro
+/// calls as they were written rather than the code they expand to.
+///
+/// [`Parser`]: crate::lang::compiler::parser::Parser
+#[derive(Debug)]
+pub(crate) struct SyntaxSink {
+    source: Vec<char>,
+    /// The tokens as lexed, before macros were expanded.
+    lexed: Vec<Token>,
+    /// The next token of `lexed` to add to the tree.
+    next: usize,
+    /// How much of the source has been added to the tree.
+    offset: usize,
+    /// Macro definitions and calls, as ranges of `lexed`. The parser never sees them as written.
+    macros: Vec<(Range<usize>, SyntaxKind)>,
+    expansions: Vec<Expansion>,
+    builder: GreenBuilder,
+}
+
+impl SyntaxSink {
+    /// `expansions` are the macro expansions in the tokens the parser gets, or `None` if they
+    /// weren't expanded, as when expansion failed. Macros are then parsed like any other code.
+    pub(crate) fn new(
+        source: &str,
+        lexed: Vec<Token>,
+        expansions: Option<Vec<Expansion>>,
+    ) -> SyntaxSink {
+        let macros = match expansions {
+            Some(_) => SyntaxSink::macros(&lexed),
+            None => Vec::new(),
+        };
+
+        let mut builder = GreenBuilder::default();
+        builder.start_node(SyntaxKind::Root);
+
+        SyntaxSink {
+            source: source.chars().collect(),
+            lexed,
+            next: 0,
+            offset: 0,
+            macros,
+            expansions: expansions.unwrap_or_default(),
+            builder,
+        }
+    }
+
+    /// Finds the macro definitions and calls the same way [`MacroExpander`] does.
+    ///
+    /// [`MacroExpander`]: crate::lang::compiler::macros::MacroExpander
+    fn macros(tokens: &[Token]) -> Vec<(Range<usize>, SyntaxKind)> {
+        let mut macros = Vec::new();
+        let mut position = 0;
+
+        while position < tokens.len() {
+            let kind = match tokens[position].type_ {
+                TokenType::Macro => SyntaxKind::Macro,
+                TokenType::Identifier
+                    if tokens.get(position + 1).map(|token| token.type_)
+                        == Some(TokenType::Bang)
+                        && tokens.get(position + 2).map(|token| token.type_)
+                            == Some(TokenType::LeftParen) =>
+                {
+                    SyntaxKind::MacroCall
+                }
+                _ => {
+                    position += 1;
+                    continue;
+                }
+            };
+
+            // A definition ends with its body and a call with its arguments
+            let open = match kind {
+                SyntaxKind::Macro => tokens[position..]
+                    .iter()
+                    .position(|token| token.type_ == TokenType::LeftBrace)
+                    .map_or(tokens.len(), |open| position + open),
+                _ => position + 2,
+            };
+            let end = SyntaxSink::group_end(tokens, open);
+
+            macros.push((position..end, kind));
+            position = end;
+        }
+
+        macros
+    }
+
+    /// The position after the bracket that closes the one at `open`, or the end of `tokens`.
+    fn group_end(tokens: &[Token], open: usize) -> usize {
+        let mut depth = 0usize;
+
+        for (position, token) in tokens.iter().enumerate().skip(open) {
+            match token.type_ {
+                TokenType::LeftParen
+                | TokenType::LeftBracket
+                | TokenType::LeftBrace
+                | TokenType::QuestionLeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return position + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        tokens.len()
+    }
+
+    /// The part of the source a macro definition or call covers.
+    fn macro_span(&self, range: &Range<usize>) -> Range<usize> {
+        self.lexed[range.start].span.start..self.lexed[range.end - 1].span.end
+    }
+
+    /// Where `token` is in the source: where it was lexed, or the start of the macro call it was
+    /// expanded from. Arguments keep their spans in the call, but are part of the call in the tree.
+    fn source_start(&self, token: &Token) -> usize {
+        let start = if token.span.start >= self.source.len() {
+            Expansion::resolve(&self.expansions, &token.span).0.start
+        } else {
+            token.span.start
+        };
+
+        self.macros
+            .iter()
+            .find(|(range, kind)| {
+                *kind == SyntaxKind::MacroCall && self.macro_span(range).contains(&start)
+            })
+            .map_or(start, |(range, _)| self.lexed[range.start].span.start)
+    }
+
+    /// Adds a token the parser consumed, along with anything before it the parser skipped.
+    ///
+    /// A token from a macro expansion only adds what is before the call. The call itself is
+    /// added once the parser gets past it.
+    pub(crate) fn token(&mut self, token: &Token) {
+        let start = self.source_start(token);
+        if start == token.span.start {
+            self.flush(start + 1);
+        } else {
+            self.flush(start);
+        }
+    }
+
+    /// Prepares for a node that starts at `next`, the token the parser will consume next, by
+    /// adding everything before it.
+    fn before_node(&mut self, next: Option<&Token>) {
+        if let Some(next) = next {
+            let start = self.source_start(next);
+            self.flush(start);
+            self.trivia(start);
+        }
+    }
+
+    pub(crate) fn start_node(&mut self, kind: SyntaxKind, next: Option<&Token>) {
+        self.before_node(next);
+        self.builder.start_node(kind);
+    }
+
+    pub(crate) fn checkpoint(&mut self, next: Option<&Token>) -> Checkpoint {
+        self.before_node(next);
+        self.builder.checkpoint()
+    }
+
+    pub(crate) fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        self.builder.start_node_at(checkpoint, kind);
+    }
+
+    pub(crate) fn finish_node(&mut self) {
+        self.builder.finish_node();
+    }
+
+    pub(crate) fn attach(&mut self, checkpoint: Checkpoint) {
+        self.builder.attach(checkpoint);
+    }
+
+    /// How many nodes are open, the root included.
+    pub(crate) fn depth(&self) -> usize {
+        self.builder.depth()
+    }
+
+    /// Adds the rest of the source and returns the root.
+    pub(crate) fn finish(mut self) -> Rc<GreenNode> {
+        while self.builder.depth() > 1 {
+            self.builder.finish_node();
+        }
+
+        self.flush(usize::MAX);
+        self.trivia(self.source.len());
+        self.builder.finish_node();
+        self.builder.finish()
+    }
+
+    /// Adds the tokens of `lexed` that start before `end`, grouping macros into nodes.
+    fn flush(&mut self, end: usize) {
+        while let Some(token) = self.lexed.get(self.next) {
+            if token.span.start >= end {
+                break;
+            }
+
+            let macro_ = self
+                .macros
+                .iter()
+                .find(|(range, _)| range.start == self.next)
+                .cloned();
+
+            match macro_ {
+                Some((range, kind)) => {
+                    self.trivia(token.span.start);
+                    self.builder.start_node(kind);
+                    self.token_trees(range.end);
+                    self.builder.finish_node();
+                }
+                None => self.bump(),
+            }
+        }
+    }
+
+    /// Adds the tokens of `lexed` up to `end` as they are, with each pair of brackets in a
+    /// [`SyntaxKind::TokenTree`].
+    fn token_trees(&mut self, end: usize) {
+        let mut depth = 0;
+
+        while self.next < end {
+            let type_ = self.lexed[self.next].type_;
+
+            if matches!(
+                type_,
+                TokenType::LeftParen
+                    | TokenType::LeftBracket
+                    | TokenType::LeftBrace
+                    | TokenType::QuestionLeftBracket
+            ) {
+                self.trivia(self.lexed[self.next].span.start);
+                self.builder.start_node(SyntaxKind::TokenTree);
+                depth += 1;
+            }
+
+            self.bump();
+
+            if depth > 0
+                && matches!(
+                    type_,
+                    TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+                )
+            {
+                self.builder.finish_node();
+                depth -= 1;
+            }
+        }
+
+        for _ in 0..depth {
+            self.builder.finish_node();
+        }
+    }
+
+    /// Adds the next token of `lexed`, after the trivia before it.
+    fn bump(&mut self) {
+        let token = &self.lexed[self.next];
+        let span = token.span.clone();
+        let kind = SyntaxKind::Token(token.type_);
+        self.next += 1;
+
+        self.trivia(span.start);
+        let text: String = self.source[span.clone()].iter().collect();
+        self.builder.token(kind, &text);
+        self.offset = span.end;
+    }
+
+    /// Adds the whitespace and comments up to `end`.
+    fn trivia(&mut self, end: usize) {
+        while self.offset < end {
+            let start = self.offset;
+            let kind = if self.source[start..end].starts_with(&['/', '/']) {
+                while self.offset < end && self.source[self.offset] != '\n' {
+                    self.offset += 1;
+                }
+                SyntaxKind::Comment
+            } else if self.source[start].is_whitespace() {
+                while self.offset < end && self.source[self.offset].is_whitespace() {
+                    self.offset += 1;
+                }
+                SyntaxKind::Whitespace
+            } else {
+                // The lexer turns anything else into a token, so this is never reached
+                self.offset += 1;
+                SyntaxKind::Token(TokenType::Unknown)
+            };
+
+            let text: String = self.source[start..self.offset].iter().collect();
+            self.builder.token(kind, &text);
+        }
+    }
+}
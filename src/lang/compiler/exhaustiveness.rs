@@ -0,0 +1,307 @@
+//! Coverage checks for `match` expressions: whether every value is matched by some arm, and
+//! whether each arm matches any value the arms before it don't.
+//!
+//! Both come down to the usefulness check from "Warnings for pattern matching" (Maranget, 2007).
+//! A row of patterns is useful after a matrix of rows if some values match it but none of the
+//! rows. An arm is unreachable if its pattern isn't useful after the arms before it, and a match
+//! is exhaustive if a wildcard isn't useful after all of its arms.
+
+use std::collections::HashMap;
+
+use super::{
+    expression::{LiteralType, MatchArm},
+    pattern::{Pattern, PatternType},
+    types::ParameterType,
+};
+
+/// A variant of an enum, with the names and types of its fields in order.
+#[derive(Debug, Clone)]
+pub(crate) struct Variant<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) fields: Vec<(&'a str, ParameterType)>,
+}
+
+/// The variants of every enum in scope, by enum name.
+pub(crate) type Enums<'a> = HashMap<&'a str, Vec<Variant<'a>>>;
+
+/// The problems found with the arms of a `match`.
+#[derive(Debug, Default)]
+pub(crate) struct Coverage {
+    /// The arms that can never be the first to match, by position.
+    pub(crate) unreachable: Vec<usize>,
+    /// Patterns for the values no arm matches, such as `Status.Suspended(_)`, or just `_` if
+    /// they can't be listed. Empty if the match is exhaustive.
+    pub(crate) missing: Vec<String>,
+}
+
+/// Matches anything and binds nothing. Bindings are treated the same way.
+static WILDCARD: Pattern = Pattern {
+    kind: PatternType::Wildcard,
+    span: 0..0,
+};
+
+/// What a pattern requires of a value, besides its fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constructor<'p> {
+    /// An enum and one of its variants.
+    Variant(&'p str, &'p str),
+    /// A literal, by its kind and value.
+    Literal(&'p LiteralType, &'p str),
+}
+
+/// A constructor that values of a type can have, with the types of its fields.
+struct Entry<'p> {
+    constructor: Constructor<'p>,
+    fields: Vec<ParameterType>,
+}
+
+/// A row of patterns, tested against the values at the same positions.
+type Row<'p> = Vec<&'p Pattern>;
+
+/// Checks the arms of a `match` on a value of type `value`. Guarded arms only count as
+/// covering values for the arms after them if their guard is known to hold, which it never is.
+///
+/// The patterns are expected to be valid for `value` and for the enums they name.
+pub(crate) fn check<'p>(
+    enums: &'p Enums<'p>,
+    value: &ParameterType,
+    arms: &'p [MatchArm],
+) -> Coverage {
+    let usefulness = Usefulness { enums };
+    let mut coverage = Coverage::default();
+    let mut rows: Vec<Row> = Vec::new();
+
+    for (index, arm) in arms.iter().enumerate() {
+        if !usefulness.useful(&rows, &[&arm.pattern], std::slice::from_ref(value)) {
+            coverage.unreachable.push(index);
+        }
+
+        if arm.guard.is_none() {
+            rows.push(vec![&arm.pattern]);
+        }
+    }
+
+    if usefulness.useful(&rows, &[&WILDCARD], std::slice::from_ref(value)) {
+        coverage.missing = usefulness.missing(&rows, value);
+    }
+
+    coverage
+}
+
+struct Usefulness<'p> {
+    enums: &'p Enums<'p>,
+}
+
+impl<'p> Usefulness<'p> {
+    /// Whether some values match `row` but none of `rows`. `types` are the types of the values
+    /// at each position of the row.
+    fn useful(&self, rows: &[Row<'p>], row: &[&'p Pattern], types: &[ParameterType]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+
+        if let Some(constructor) = constructor(head) {
+            let field_types = self.field_types(constructor, fields(head).len());
+            let row: Row = fields(head).iter().chain(rest.iter().copied()).collect();
+            return self.useful(
+                &specialize(rows, constructor, field_types.len()),
+                &row,
+                &[field_types, types[1..].to_vec()].concat(),
+            );
+        }
+
+        let heads = heads(rows);
+        match self.signature(&types[0], &heads) {
+            // A wildcard is useful if it is useful for any of the constructors
+            Some(signature) if complete(&signature, &heads) => signature.into_iter().any(|entry| {
+                let row: Row = std::iter::repeat_n(&WILDCARD, entry.fields.len())
+                    .chain(rest.iter().copied())
+                    .collect();
+                self.useful(
+                    &specialize(rows, entry.constructor, entry.fields.len()),
+                    &row,
+                    &[entry.fields, types[1..].to_vec()].concat(),
+                )
+            }),
+            // Otherwise some constructor isn't listed, and the rows with a wildcard first decide
+            _ => self.useful(&default(rows), rest, &types[1..]),
+        }
+    }
+
+    /// Lists the constructors of the matched value that no arm covers completely.
+    fn missing(&self, rows: &[Row<'p>], value: &ParameterType) -> Vec<String> {
+        let Some(signature) = self.signature(value, &heads(rows)) else {
+            return vec![String::from("_")];
+        };
+
+        let missing: Vec<String> = signature
+            .into_iter()
+            .filter(|entry| {
+                let row: Row = vec![&WILDCARD; entry.fields.len()];
+                self.useful(
+                    &specialize(rows, entry.constructor, entry.fields.len()),
+                    &row,
+                    &entry.fields,
+                )
+            })
+            .map(|entry| describe(entry.constructor, entry.fields.len()))
+            .collect();
+
+        if missing.is_empty() {
+            vec![String::from("_")]
+        } else {
+            missing
+        }
+    }
+
+    /// Every constructor a value of `type_` can have, if there are finitely many.
+    ///
+    /// The type of a value isn't always known, so the constructors in `heads` are used to tell
+    /// which enum it is, or that it is a boolean or may be null.
+    fn signature(
+        &self,
+        type_: &ParameterType,
+        heads: &[Constructor<'p>],
+    ) -> Option<Vec<Entry<'p>>> {
+        let null = Constructor::Literal(&LiteralType::Null, "null");
+        let (nullable, type_) = match type_ {
+            ParameterType::Optional(inner) => (true, &**inner),
+            type_ => (heads.contains(&null), type_),
+        };
+
+        let enum_ = match type_ {
+            ParameterType::Enum(name) => self
+                .enums
+                .get_key_value(name.as_str())
+                .map(|(name, _)| *name),
+            _ => heads.iter().find_map(|head| match head {
+                Constructor::Variant(enum_, _) => Some(*enum_),
+                Constructor::Literal(..) => None,
+            }),
+        };
+        let boolean = *type_ == ParameterType::Boolean
+            || heads
+                .iter()
+                .any(|head| matches!(head, Constructor::Literal(LiteralType::Unknown, _)));
+
+        let mut signature: Vec<Entry> = if let Some(enum_) = enum_ {
+            self.enums
+                .get(enum_)?
+                .iter()
+                .map(|variant| Entry {
+                    constructor: Constructor::Variant(enum_, variant.name),
+                    fields: variant
+                        .fields
+                        .iter()
+                        .map(|(_, type_)| type_.clone())
+                        .collect(),
+                })
+                .collect()
+        } else if boolean {
+            ["true", "false"]
+                .into_iter()
+                .map(|value| Entry {
+                    constructor: Constructor::Literal(&LiteralType::Unknown, value),
+                    fields: Vec::new(),
+                })
+                .collect()
+        } else {
+            return None;
+        };
+
+        if nullable {
+            signature.push(Entry {
+                constructor: null,
+                fields: Vec::new(),
+            });
+        }
+
+        Some(signature)
+    }
+
+    /// The types of the fields of a constructor, which has `arity` fields.
+    fn field_types(&self, constructor: Constructor, arity: usize) -> Vec<ParameterType> {
+        let declared = match constructor {
+            Constructor::Variant(enum_, name) => self
+                .enums
+                .get(enum_)
+                .and_then(|variants| variants.iter().find(|variant| variant.name == name)),
+            Constructor::Literal(..) => None,
+        };
+
+        match declared {
+            Some(variant) => variant
+                .fields
+                .iter()
+                .map(|(_, type_)| type_.clone())
+                .collect(),
+            None => vec![ParameterType::Unknown; arity],
+        }
+    }
+}
+
+fn constructor(pattern: &Pattern) -> Option<Constructor<'_>> {
+    match &pattern.kind {
+        PatternType::Wildcard | PatternType::Binding { .. } => None,
+        PatternType::Literal(literal) => {
+            Some(Constructor::Literal(&literal.literal, &literal.value))
+        }
+        PatternType::Variant(variant) => {
+            Some(Constructor::Variant(&variant.enum_, &variant.variant))
+        }
+    }
+}
+
+fn fields(pattern: &Pattern) -> &[Pattern] {
+    match &pattern.kind {
+        PatternType::Variant(variant) => &variant.fields,
+        _ => &[],
+    }
+}
+
+/// The constructors of the first patterns of the rows.
+fn heads<'p>(rows: &[Row<'p>]) -> Vec<Constructor<'p>> {
+    rows.iter().filter_map(|row| constructor(row[0])).collect()
+}
+
+fn complete(signature: &[Entry], heads: &[Constructor]) -> bool {
+    signature
+        .iter()
+        .all(|entry| heads.contains(&entry.constructor))
+}
+
+/// The rows for values built with `constructor`, with its `arity` fields in place of the first
+/// pattern.
+fn specialize<'p>(rows: &[Row<'p>], constructor: Constructor, arity: usize) -> Vec<Row<'p>> {
+    rows.iter()
+        .filter_map(|row| {
+            let (head, rest) = row.split_first()?;
+            let fields: Row = match self::constructor(head) {
+                Some(other) if other == constructor => fields(head).iter().collect(),
+                Some(_) => return None,
+                None => vec![&WILDCARD; arity],
+            };
+            Some(fields.into_iter().chain(rest.iter().copied()).collect())
+        })
+        .collect()
+}
+
+/// The rows for values whose constructor no row lists, which only the rows starting with a
+/// wildcard match.
+fn default<'p>(rows: &[Row<'p>]) -> Vec<Row<'p>> {
+    rows.iter()
+        .filter(|row| constructor(row[0]).is_none())
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+fn describe(constructor: Constructor, arity: usize) -> String {
+    match constructor {
+        Constructor::Variant(enum_, variant) if arity == 0 => format!("{}.{}", enum_, variant),
+        Constructor::Variant(enum_, variant) => {
+            format!("{}.{}({})", enum_, variant, vec!["_"; arity].join(", "))
+        }
+        Constructor::Literal(LiteralType::String, value) => format!("\"{}\"", value),
+        Constructor::Literal(_, value) => String::from(value),
+    }
+}
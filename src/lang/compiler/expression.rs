@@ -1,54 +1,223 @@
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use crate::lang::util::vec::{Unshift, UnshiftExpect};
 
 use super::{
+    attribute::Attribute,
+    cst::SyntaxKind,
+    node::Node,
+    parser::AST,
+    pattern::Pattern,
+    statement::{BlockStatement, FunctionStatement, Parameter, Statement, StatementType},
     token::{Span, Token, TokenStream, TokenType},
+    types::TypeExpr,
+    visit::{walk_ast_mut, walk_block_mut, walk_expression_mut, walk_statement_mut, VisitorMut},
     CompilerError, CompilerResult, ErrorCode,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub(crate) struct Expression {
-    pub(crate) expression: ExpressionType,
-    pub(crate) span: Span,
-}
+pub(crate) type Expression = Node<ExpressionType>;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
 pub(crate) enum ExpressionType {
+    Assignment(AssignmentExpression),
     Binary(BinaryExpression),
+    /// A block used as a value, such as `{ let t = f(); t * t }`.
+    Block(BlockStatement),
+    Closure(ClosureExpression),
+    /// `a ?? b`, which is `b` if `a` is null.
+    Coalesce(CoalesceExpression),
     FunctionCall(FunctionCallExpression),
+    If(IfExpression),
+    Index(IndexExpression),
+    Intrinsic(IntrinsicExpression),
+    List(ListExpression),
     Literal(LiteralExpression),
+    Match(MatchExpression),
+    Member(MemberExpression),
+    /// `a?[i]`, which is null if `a` is null.
+    OptionalIndex(IndexExpression),
+    /// `a?.b`, which is null if `a` is null.
+    OptionalMember(MemberExpression),
+    /// `a?`, which returns the error of a `result` from the enclosing function.
+    Propagate(PropagateExpression),
+    Range(RangeExpression),
+    Record(RecordExpression),
+    Struct(StructExpression),
+    Unary(UnaryExpression),
+    Yield(YieldExpression),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct AssignmentExpression {
+    pub(crate) target: Box<Expression>,
+    pub(crate) value: Box<Expression>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct BinaryExpression {
     pub(crate) left: Box<Expression>,
     pub(crate) operator: Token,
     pub(crate) right: Box<Expression>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ClosureExpression {
+    pub(crate) parameters: Vec<Parameter>,
+    pub(crate) return_type: Option<TypeExpr>,
+    pub(crate) body: ClosureBody,
+    /// Names used in the body that are bound outside of the closure, in order of first use.
+    pub(crate) captures: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub(crate) enum ClosureBody {
+    Expression(Box<Expression>),
+    Block(BlockStatement),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct CoalesceExpression {
+    pub(crate) value: Box<Expression>,
+    pub(crate) default: Box<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct FunctionCallExpression {
+    pub(crate) callee: Box<Expression>,
+    pub(crate) arguments: Vec<Argument>,
+}
+
+/// A call argument, either positional (`5`) or named (`limit: 5`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Argument {
+    pub(crate) name: Option<String>,
+    pub(crate) value: Expression,
+    /// Covers the name as well as the value.
+    pub(crate) span: Span,
+}
+
+/// `if condition { ... } else { ... }`, whose value is the tail value of the branch that runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct IfExpression {
+    pub(crate) condition: Box<Expression>,
+    pub(crate) then_branch: BlockStatement,
+    /// A block, or another `if` expression for `else if`.
+    pub(crate) else_branch: Option<Box<Expression>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct IndexExpression {
+    pub(crate) object: Box<Expression>,
+    pub(crate) index: Box<Expression>,
+}
+
+/// A call to a compiler intrinsic, such as `@len(xs)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct IntrinsicExpression {
     pub(crate) name: String,
+    pub(crate) name_span: Span,
     pub(crate) arguments: Vec<Expression>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// `match value { pattern => result, ... }`, whose value is the result of the first arm that
+/// matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct MatchExpression {
+    pub(crate) value: Box<Expression>,
+    pub(crate) arms: Vec<MatchArm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct MatchArm {
+    pub(crate) pattern: Pattern,
+    /// `pattern if condition => ...`, where the condition can use the names the pattern binds.
+    /// The arm only matches if it holds.
+    pub(crate) guard: Option<Expression>,
+    pub(crate) body: Expression,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct MemberExpression {
+    pub(crate) object: Box<Expression>,
+    pub(crate) property: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ListExpression {
+    pub(crate) elements: Vec<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct LiteralExpression {
     pub(crate) literal: LiteralType,
     pub(crate) value: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// `a..b`, `a..=b`, `..b` or `a..`. Indexing with a range, such as `xs[1..3]`, takes a slice.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct RangeExpression {
+    pub(crate) start: Option<Box<Expression>>,
+    pub(crate) end: Option<Box<Expression>>,
+    /// Whether `end` is part of the range, as in `a..=b`.
+    pub(crate) inclusive: bool,
+}
+
+/// Because `?.` is optional chaining, the value of `f()?` needs parentheses to access
+/// a member: `(f()?).name`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct PropagateExpression {
+    pub(crate) value: Box<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct RecordExpression {
+    pub(crate) fields: Vec<RecordField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct RecordField {
+    pub(crate) key: String,
+    pub(crate) key_span: Span,
+    pub(crate) value: Expression,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct StructExpression {
+    pub(crate) name: String,
+    pub(crate) fields: Vec<RecordField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct UnaryExpression {
+    pub(crate) operator: Token,
+    pub(crate) operand: Box<Expression>,
+}
+
+/// `yield value` inside a generator function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct YieldExpression {
+    pub(crate) value: Option<Box<Expression>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) enum LiteralType {
     Unknown,
     Integer,
+    Null,
+    String,
 }
 
-impl Into<LiteralType> for TokenType {
-    fn into(self) -> LiteralType {
-        match self {
+impl From<TokenType> for LiteralType {
+    fn from(token_type: TokenType) -> LiteralType {
+        match token_type {
             TokenType::IntegerLiteral => LiteralType::Integer,
+            TokenType::Null => LiteralType::Null,
+            TokenType::StringLiteral => LiteralType::String,
             _ => LiteralType::Unknown,
         }
     }
@@ -56,189 +225,1397 @@ impl Into<LiteralType> for TokenType {
 
 impl Expression {
     pub(crate) fn parse(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        Ok(Expression::assignment(tokens)?)
+        if tokens.next_matches(TokenType::Yield) {
+            return Expression::yield_(tokens);
+        }
+
+        Expression::assignment(tokens)
+    }
+
+    /// `yield value` hands a value to the loop consuming a generator, and suspends it until the
+    /// next value is asked for. The value can be left out, as in `yield;`.
+    fn yield_(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::Yield);
+        let keyword = tokens.unshift_expect(TokenType::Yield)?.clone();
+
+        let value = if Expression::at_end(tokens) {
+            None
+        } else {
+            Some(Box::new(Expression::parse(tokens)?))
+        };
+
+        let end = value.as_ref().map_or(keyword.span.end, |value| value.span.end);
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::Yield(YieldExpression { value }),
+            keyword.span.start..end,
+        ))
+    }
+
+    /// Whether nothing that can start an expression follows, as in `xs[1..]` or `yield;`.
+    fn at_end(tokens: &mut TokenStream) -> bool {
+        tokens.peek().is_none()
+            || tokens.next_matches_any(&[
+                TokenType::Comma,
+                TokenType::RightBrace,
+                TokenType::RightBracket,
+                TokenType::RightParen,
+                TokenType::Semicolon,
+            ])
     }
 
     fn assignment(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        let mut expr = Expression::or(tokens)?;
+        let checkpoint = tokens.checkpoint();
+        let expr = Expression::range(tokens)?;
+
+        if let Some(equals) = tokens.unshift_if(TokenType::Equals) {
+            let equals = equals.clone();
+            tokens.start_node_at(checkpoint, SyntaxKind::Assignment);
+
+            if expr.root_identifier().is_none() {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::InvalidAssignmentTarget,
+                    error_message: String::from("Invalid assignment target"),
+                    span_message: String::from("Only variables, fields and indices can be assigned to"),
+                    token: Token {
+                        span: expr.span.start..equals.span.start,
+                        ..equals
+                    },
+                    help: None,
+                    info: None,
+                }));
+            }
+
+            // Assignment is right-associative, so `a = b = c` assigns `c` to both
+            let value = Expression::assignment(tokens)?;
+            let span = expr.span.start..value.span.end;
+            tokens.finish_node();
 
-        if tokens.next_matches(TokenType::Equals) {
-            todo!()
+            return Ok(Expression::new(
+                ExpressionType::Assignment(AssignmentExpression {
+                    target: Box::new(expr),
+                    value: Box::new(value),
+                }),
+                span,
+            ));
         }
 
         Ok(expr)
     }
 
-    fn or(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        let mut expr = Expression::and(tokens)?;
+    /// The variable an assignment target ultimately refers to, such as `rows` in `rows[0].name`.
+    ///
+    /// Returns `None` if the expression can't be assigned to.
+    pub(crate) fn root_identifier(&self) -> Option<&str> {
+        match &self.kind {
+            ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) => Some(value),
+            ExpressionType::Member(member) => member.object.root_identifier(),
+            ExpressionType::Index(index) => index.object.root_identifier(),
+            _ => None,
+        }
+    }
 
-        while tokens.next_matches(TokenType::PipePipe) {
-            todo!()
+    fn range(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        const OPERATORS: [TokenType; 2] = [TokenType::DotDot, TokenType::DotDotEquals];
+
+        let checkpoint = tokens.checkpoint();
+        let start = if tokens.next_matches_any(&OPERATORS) {
+            None
+        } else {
+            Some(Expression::coalesce(tokens)?)
+        };
+
+        if !tokens.next_matches_any(&OPERATORS) {
+            // `start` is always parsed when there is no range operator
+            return Ok(start.unwrap());
         }
 
-        Ok(expr)
+        tokens.start_node_at(checkpoint, SyntaxKind::Range);
+        let operator = tokens.unshift_expect_any(&OPERATORS)?.clone();
+        let inclusive = operator.type_ == TokenType::DotDotEquals;
+
+        // The end is left out if nothing that can start an expression follows, as in `xs[1..]`
+        let end = if Expression::at_end(tokens) {
+            None
+        } else {
+            Some(Expression::coalesce(tokens)?)
+        };
+
+        if inclusive && end.is_none() {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::InvalidRange,
+                error_message: String::from("An inclusive range needs an end"),
+                span_message: String::from("Expected an expression after '..='"),
+                token: operator,
+                help: Some(String::from("Use `a..` for a range without an end")),
+                info: None,
+            }));
+        }
+
+        let span = start.as_ref().map_or(operator.span.start, |start| start.span.start)
+            ..end.as_ref().map_or(operator.span.end, |end| end.span.end);
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::Range(RangeExpression {
+                start: start.map(Box::new),
+                end: end.map(Box::new),
+                inclusive,
+            }),
+            span,
+        ))
     }
 
-    fn and(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        let mut expr = Expression::equality(tokens)?;
+    /// `a ?? b ?? c` is right-associative, so the first value that isn't null is used.
+    fn coalesce(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        let checkpoint = tokens.checkpoint();
+        let value = Expression::or(tokens)?;
 
-        while tokens.next_matches(TokenType::AmpersandAmpersand) {
-            todo!()
+        if tokens.unshift_if(TokenType::QuestionQuestion).is_none() {
+            return Ok(value);
         }
 
-        Ok(expr)
+        tokens.start_node_at(checkpoint, SyntaxKind::Coalesce);
+        let default = Expression::coalesce(tokens)?;
+        tokens.finish_node();
+        let span = value.span.start..default.span.end;
+
+        Ok(Expression::new(
+            ExpressionType::Coalesce(CoalesceExpression {
+                value: Box::new(value),
+                default: Box::new(default),
+            }),
+            span,
+        ))
     }
 
-    fn equality(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        let mut expr = Expression::comparison(tokens)?;
+    fn or(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        let checkpoint = tokens.checkpoint();
+        let mut expr = Expression::and(tokens)?;
+
+        while tokens.next_matches(TokenType::PipePipe) {
+            tokens.start_node_at(checkpoint, SyntaxKind::Binary);
+            let operator = tokens.unshift_expect(TokenType::PipePipe)?.clone();
+            let right = Expression::and(tokens)?;
+            tokens.finish_node();
 
-        while tokens.next_matches_any(&[TokenType::BangEquals, TokenType::EqualsEquals]) {
-            todo!()
+            let span = expr.span.start..right.span.end;
+
+            expr = Expression::new(
+                ExpressionType::Binary(BinaryExpression {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                }),
+                span,
+            );
         }
 
         Ok(expr)
     }
 
-    fn comparison(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        let mut expr = Expression::term(tokens)?;
+    fn and(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        Expression::binary(
+            tokens,
+            &[TokenType::AmpersandAmpersand],
+            Expression::equality,
+        )
+    }
 
-        // TODO: Add comparison operators when implementation requires it
-        // while tokens.next_matches_any(&[todo!()]) {
-        //     todo!()
-        // }
+    fn equality(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        Expression::binary(
+            tokens,
+            &[TokenType::BangEquals, TokenType::EqualsEquals],
+            Expression::comparison,
+        )
+    }
 
-        Ok(expr)
+    fn comparison(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        Expression::binary(
+            tokens,
+            &[TokenType::GreaterThan, TokenType::LessThan],
+            Expression::term,
+        )
     }
 
     fn term(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        let mut expr = Expression::factor(tokens)?;
+        Expression::binary(tokens, &[TokenType::Minus, TokenType::Plus], Expression::factor)
+    }
+
+    /// Parses a left-associative chain of `operators`, with operands parsed by `operand`.
+    fn binary(
+        tokens: &mut TokenStream,
+        operators: &[TokenType],
+        operand: fn(&mut TokenStream) -> CompilerResult<Expression>,
+    ) -> CompilerResult<Expression> {
+        let checkpoint = tokens.checkpoint();
+        let mut expr = operand(tokens)?;
 
-        // TODO: Add term operators when implementation requires it
-        // while tokens.next_matches_any(&[todo!()]) {
-        //     todo!()
-        // }
+        while tokens.next_matches_any(operators) {
+            tokens.start_node_at(checkpoint, SyntaxKind::Binary);
+            let operator = tokens.unshift_expect_any(operators)?.clone();
+            let right = operand(tokens)?;
+            tokens.finish_node();
+
+            let span = expr.span.start..right.span.end;
+
+            expr = Expression::new(
+                ExpressionType::Binary(BinaryExpression {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                }),
+                span,
+            );
+        }
 
         Ok(expr)
     }
 
     fn factor(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        // TODO: Change back to unary when implementation requires it
-        let mut expr = Expression::call(tokens)?;
+        let checkpoint = tokens.checkpoint();
+        let mut expr = Expression::unary(tokens)?;
 
         while tokens.next_matches_any(&[TokenType::Asterisk, TokenType::Slash]) {
+            tokens.start_node_at(checkpoint, SyntaxKind::Binary);
             let operator = tokens
                 .unshift_expect_any(&[TokenType::Asterisk, TokenType::Slash])?
                 .clone();
-            // TODO: Change back to unary when implementation requires it
-            let right = Expression::call(tokens)?;
+            let right = Expression::unary(tokens)?;
+            tokens.finish_node();
 
             // Assign span here so `expr` can be moved into the binary expression box
             let span = expr.span.start..right.span.end;
 
-            expr = Expression {
-                expression: ExpressionType::Binary(BinaryExpression {
+            expr = Expression::new(
+                ExpressionType::Binary(BinaryExpression {
                     left: Box::new(expr),
                     operator,
                     right: Box::new(right),
                 }),
                 span,
-            };
+            );
         }
 
         Ok(expr)
     }
 
-    fn unary(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        if tokens.next_matches_any(&[todo!()]) {
-            todo!()
+    /// Parses a call argument. Named arguments (`name: value`) can't be followed by positional ones.
+    fn argument(tokens: &mut TokenStream, previous: &[Argument]) -> CompilerResult<Argument> {
+        tokens.start_node(SyntaxKind::Argument);
+        if tokens.next_matches(TokenType::Identifier) && tokens.nth_matches(1, TokenType::Colon) {
+            let name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+            tokens.unshift_expect(TokenType::Colon)?;
+            let value = Expression::parse(tokens)?;
+            tokens.finish_node();
+
+            return Ok(Argument {
+                name: Some(name.value),
+                span: name.span.start..value.span.end,
+                value,
+            });
         }
 
-        Expression::call(tokens)
+        let first = tokens.peek().map(|token| (*token).clone());
+        let value = Expression::parse(tokens)?;
+        tokens.finish_node();
+        if let Some(mut token) = first.filter(|_| previous.iter().any(|argument| argument.name.is_some())) {
+            token.span = value.span.clone();
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::PositionalAfterNamed,
+                error_message: String::from("Positional argument follows a named argument"),
+                span_message: String::from("This argument has no name"),
+                token,
+                help: Some(String::from("Move positional arguments before named ones, or name this one")),
+                info: None,
+            }));
+        }
+
+        Ok(Argument {
+            span: value.span.clone(),
+            name: None,
+            value,
+        })
+    }
+
+    /// `!value`, which negates a boolean.
+    fn unary(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        let checkpoint = tokens.checkpoint();
+        let Some(operator) = tokens.unshift_if(TokenType::Bang).cloned() else {
+            return Expression::call(tokens);
+        };
+
+        tokens.start_node_at(checkpoint, SyntaxKind::Unary);
+        let operand = Expression::unary(tokens)?;
+        tokens.finish_node();
+        let span = operator.span.start..operand.span.end;
+
+        Ok(Expression::new(
+            ExpressionType::Unary(UnaryExpression {
+                operator,
+                operand: Box::new(operand),
+            }),
+            span,
+        ))
     }
 
     fn call(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        // TODO: Change back to primary when implementation requires it
-        let mut expr = Expression::literal(tokens)?;
+        let checkpoint = tokens.checkpoint();
+        let mut expr = Expression::primary(tokens)?;
+
+        loop {
+            let suffix = tokens.checkpoint();
+
+            if tokens.unshift_if(TokenType::LeftParen).is_some() {
+                tokens.start_node_at(checkpoint, SyntaxKind::Call);
+                tokens.start_node_at(suffix, SyntaxKind::ArgumentList);
+                let mut arguments = Vec::new();
+                while !tokens.next_matches(TokenType::RightParen) {
+                    arguments.push(Expression::argument(tokens, &arguments)?);
+
+                    if !tokens.next_matches(TokenType::RightParen) {
+                        tokens.unshift_expect(TokenType::Comma)?;
+                    }
+                }
+
+                let end = tokens.unshift_expect(TokenType::RightParen)?;
+                let span = expr.span.start..end.span.end;
+                tokens.finish_node();
+                tokens.finish_node();
+
+                expr = Expression::new(
+                    ExpressionType::FunctionCall(FunctionCallExpression {
+                        callee: Box::new(expr),
+                        arguments,
+                    }),
+                    span,
+                );
+            } else if let Some(question) = tokens.unshift_if(TokenType::Question) {
+                let span = expr.span.start..question.span.end;
+                tokens.start_node_at(checkpoint, SyntaxKind::Propagate);
+                tokens.finish_node();
+
+                expr = Expression::new(
+                    ExpressionType::Propagate(PropagateExpression {
+                        value: Box::new(expr),
+                    }),
+                    span,
+                );
+            } else if tokens.next_matches_any(&[TokenType::Dot, TokenType::QuestionDot]) {
+                let optional = tokens.unshift_expect_any(&[TokenType::Dot, TokenType::QuestionDot])?.type_
+                    == TokenType::QuestionDot;
+                let property = tokens.unshift_expect(TokenType::Identifier)?.clone();
+                let span = expr.span.start..property.span.end;
+                tokens.start_node_at(checkpoint, SyntaxKind::Member);
+                tokens.finish_node();
 
+                let member = MemberExpression {
+                    object: Box::new(expr),
+                    property: property.value,
+                };
+
+                expr = Expression::new(
+                    if optional {
+                        ExpressionType::OptionalMember(member)
+                    } else {
+                        ExpressionType::Member(member)
+                    },
+                    span,
+                );
+            } else if tokens.next_matches_any(&[TokenType::LeftBracket, TokenType::QuestionLeftBracket]) {
+                let optional = tokens
+                    .unshift_expect_any(&[TokenType::LeftBracket, TokenType::QuestionLeftBracket])?
+                    .type_
+                    == TokenType::QuestionLeftBracket;
+                tokens.start_node_at(checkpoint, SyntaxKind::Index);
+                let index = Expression::parse(tokens)?;
+                let end = tokens.unshift_expect(TokenType::RightBracket)?;
+                let span = expr.span.start..end.span.end;
+                tokens.finish_node();
+
+                let index = IndexExpression {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                };
+
+                expr = Expression::new(
+                    if optional {
+                        ExpressionType::OptionalIndex(index)
+                    } else {
+                        ExpressionType::Index(index)
+                    },
+                    span,
+                );
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn primary(tokens: &mut TokenStream) -> CompilerResult<Expression> {
         if tokens.next_matches(TokenType::LeftParen) {
-            tokens.unshift_expect(TokenType::LeftParen)?;
-            let mut arguments = Vec::new();
-            while !tokens.next_matches(TokenType::RightParen) {
+            tokens.start_node(SyntaxKind::Paren);
+            let start = tokens.unshift_expect(TokenType::LeftParen)?.span.start;
+            let expr = Expression::parse(tokens)?;
+            let end = tokens.unshift_expect(TokenType::RightParen)?.span.end;
+            tokens.finish_node();
+
+            // Keep the inner node, but widen its span to cover the parentheses
+            return Ok(Expression {
+                span: start..end,
+                ..expr
+            });
+        }
+
+        if tokens.next_matches(TokenType::LeftBracket) {
+            return Expression::list(tokens);
+        }
+
+        if tokens.next_matches_any(&[TokenType::Pipe, TokenType::PipePipe]) {
+            return Expression::closure(tokens);
+        }
+
+        if tokens.next_matches(TokenType::At) {
+            return Expression::intrinsic(tokens);
+        }
+
+        if tokens.next_matches(TokenType::If) {
+            return Expression::if_(tokens);
+        }
+
+        if tokens.next_matches(TokenType::Match) {
+            return Expression::match_(tokens);
+        }
+
+        if Expression::is_record_start(tokens, 0) {
+            return Expression::record(tokens);
+        }
+
+        if tokens.next_matches(TokenType::LeftBrace) {
+            let (block, span) = Expression::block(tokens)?;
+            return Ok(Expression::new(ExpressionType::Block(block), span));
+        }
+
+        if tokens.next_matches(TokenType::Identifier) && Expression::is_record_start(tokens, 1) {
+            return Expression::struct_(tokens);
+        }
+
+        Expression::literal(tokens)
+    }
+
+    /// A brace starts a record literal only if it is empty (`{}`) or its first
+    /// entry is a `key:` pair. Any other brace belongs to a block.
+    fn is_record_start(tokens: &mut TokenStream, offset: usize) -> bool {
+        tokens.nth_matches(offset, TokenType::LeftBrace)
+            && (tokens.nth_matches(offset + 1, TokenType::RightBrace)
+                || ((tokens.nth_matches(offset + 1, TokenType::Identifier)
+                    || tokens.nth_matches(offset + 1, TokenType::StringLiteral))
+                    && tokens.nth_matches(offset + 2, TokenType::Colon)))
+    }
+
+    fn struct_(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::StructLiteral);
+        let name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        let record = Expression::record(tokens)?;
+        tokens.finish_node();
+
+        let fields = match record.kind {
+            ExpressionType::Record(RecordExpression { fields }) => fields,
+            _ => unreachable!(),
+        };
+
+        Ok(Expression::new(
+            ExpressionType::Struct(StructExpression {
+                name: name.value,
+                fields,
+            }),
+            name.span.start..record.span.end,
+        ))
+    }
+
+    fn record(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::Record);
+        let start = tokens.unshift_expect(TokenType::LeftBrace)?.span.start;
+
+        let mut fields: Vec<RecordField> = Vec::new();
+        while !tokens.next_matches(TokenType::RightBrace) {
+            tokens.start_node(SyntaxKind::RecordField);
+            let key = tokens
+                .unshift_expect_any(&[TokenType::Identifier, TokenType::StringLiteral])?
+                .clone();
+
+            if fields.iter().any(|field| field.key == key.value) {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::DuplicateRecordKey,
+                    error_message: format!("Duplicate record key: '{}'", key.value),
+                    span_message: String::from("This key was already defined in this record"),
+                    token: key,
+                    help: Some(String::from("Remove or rename one of the duplicate keys")),
+                    info: None,
+                }));
+            }
 
-                arguments.push(Expression::parse(tokens)?);
+            tokens.unshift_expect(TokenType::Colon)?;
+            let value = Expression::parse(tokens)?;
+            let span = key.span.start..value.span.end;
+            tokens.finish_node();
 
-                if !tokens.next_matches(TokenType::RightParen) {
-                    tokens.unshift_expect(TokenType::Comma)?;
+            fields.push(RecordField {
+                key: key.value,
+                key_span: key.span,
+                value,
+                span,
+            });
+
+            // A comma directly before the closing brace is allowed
+            if !tokens.next_matches(TokenType::RightBrace) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
+        }
+
+        let end = tokens.unshift_expect(TokenType::RightBrace)?.span.end;
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::Record(RecordExpression { fields }),
+            start..end,
+        ))
+    }
+
+    /// Parses `|a: int, b| a * b`, `|| 42` or `|x: int| -> int { return x; }`.
+    fn closure(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::Closure);
+        let mut parameters = Vec::new();
+
+        let start = match tokens.unshift_if(TokenType::PipePipe) {
+            Some(token) => token.span.start,
+            None => {
+                tokens.start_node(SyntaxKind::ParameterList);
+                let start = tokens.unshift_expect(TokenType::Pipe)?.span.start;
+
+                while !tokens.next_matches(TokenType::Pipe) {
+                    parameters.push(Statement::parameter(tokens)?);
+
+                    if !tokens.next_matches(TokenType::Pipe) {
+                        tokens.unshift_expect(TokenType::Comma)?;
+                    }
                 }
+
+                tokens.unshift_expect(TokenType::Pipe)?;
+                tokens.finish_node();
+                start
             }
+        };
+
+        let mut return_type = None;
+
+        // An explicit return type requires a block body, so the type can't run into the body
+        let (body, end) = if tokens.unshift_if(TokenType::Arrow).is_some() {
+            return_type = Some(TypeExpr::parse(tokens)?);
+            Expression::closure_block(tokens)?
+        } else if tokens.next_matches(TokenType::LeftBrace) && !Expression::is_record_start(tokens, 0) {
+            Expression::closure_block(tokens)?
+        } else {
+            let body = Expression::parse(tokens)?;
+            let end = body.span.end;
+            (ClosureBody::Expression(Box::new(body)), end)
+        };
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::Closure(ClosureExpression {
+                parameters,
+                return_type,
+                body,
+                // Filled in by `Captures` once the whole file is parsed
+                captures: Vec::new(),
+            }),
+            start..end,
+        ))
+    }
+
+    fn closure_block(tokens: &mut TokenStream) -> CompilerResult<(ClosureBody, usize)> {
+        let (block, span) = Expression::block(tokens)?;
+        Ok((ClosureBody::Block(block), span.end))
+    }
+
+    fn block(tokens: &mut TokenStream) -> CompilerResult<(BlockStatement, Span)> {
+        let statement = Statement::block(tokens)?;
+        match statement.kind {
+            StatementType::Block(block) => Ok((block, statement.span)),
+            _ => unreachable!(),
+        }
+    }
 
-            tokens.unshift_expect(TokenType::RightParen)?;
+    pub(crate) fn if_(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::If);
+        let start = tokens.unshift_expect(TokenType::If)?.span.start;
+        let condition = Expression::condition(tokens, "if")?;
+        let (then_branch, span) = Expression::block(tokens)?;
 
-            let span = expr.span.start..tokens.peek().unwrap().span.end;
-            let name = match &expr.expression {
-                ExpressionType::Literal(LiteralExpression { value, .. }) => value.clone(),
-                _ => unreachable!(),
+        let mut end = span.end;
+        let mut else_branch = None;
+
+        if tokens.unshift_if(TokenType::Else).is_some() {
+            let branch = if tokens.next_matches(TokenType::If) {
+                Expression::if_(tokens)?
+            } else {
+                let (block, span) = Expression::block(tokens)?;
+                Expression::new(ExpressionType::Block(block), span)
             };
 
-            expr = Expression {
-                expression: ExpressionType::FunctionCall(FunctionCallExpression {
-                    name,
-                    arguments,
-                }),
-                span,
+            end = branch.span.end;
+            else_branch = Some(Box::new(branch));
+        }
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::If(IfExpression {
+                condition: Box::new(condition),
+                then_branch,
+                else_branch,
+            }),
+            start..end,
+        ))
+    }
+
+    /// Parses `match value { pattern => result, pattern if guard => result }`. Arms are separated
+    /// by commas, and a comma after the last arm is allowed.
+    pub(crate) fn match_(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::Match);
+        let start = tokens.unshift_expect(TokenType::Match)?.span.start;
+        let value = Expression::condition(tokens, "match")?;
+        tokens.unshift_expect(TokenType::LeftBrace)?;
+
+        let mut arms = Vec::new();
+        while !tokens.next_matches(TokenType::RightBrace) {
+            tokens.start_node(SyntaxKind::MatchArm);
+            let pattern = Pattern::parse(tokens)?;
+
+            let guard = if tokens.unshift_if(TokenType::If).is_some() {
+                Some(Expression::parse(tokens)?)
+            } else {
+                None
             };
+
+            tokens.unshift_expect(TokenType::FatArrow)?;
+            let body = Expression::parse(tokens)?;
+            tokens.finish_node();
+
+            arms.push(MatchArm {
+                span: pattern.span.start..body.span.end,
+                pattern,
+                guard,
+                body,
+            });
+
+            if !tokens.next_matches(TokenType::RightBrace) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
+        }
+
+        let end = tokens.unshift_expect(TokenType::RightBrace)?.span.end;
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::Match(MatchExpression {
+                value: Box::new(value),
+                arms,
+            }),
+            start..end,
+        ))
+    }
+
+    /// Parses the expression between a `keyword` such as `if` and its body, which ends at the
+    /// first `{` outside of brackets.
+    ///
+    /// Otherwise `if ready {}` would be read as constructing a struct named `ready`.
+    pub(crate) fn condition(tokens: &mut TokenStream, keyword: &str) -> CompilerResult<Expression> {
+        let mut depth = 0usize;
+        let length = tokens
+            .as_slice()
+            .iter()
+            .take_while(|token| {
+                match token.type_ {
+                    TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                    TokenType::RightParen | TokenType::RightBracket => {
+                        depth = depth.saturating_sub(1)
+                    }
+                    TokenType::LeftBrace if depth == 0 => return false,
+                    _ => {}
+                }
+                true
+            })
+            .count();
+
+        if length == 0 {
+            let token = tokens.peek().cloned().unwrap_or_else(Token::invalid);
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::InvalidExpression,
+                error_message: format!("Expected an expression after '{}'", keyword),
+                span_message: String::from("The body starts here"),
+                token,
+                help: Some(format!("Write the expression between `{}` and `{{`", keyword)),
+                info: None,
+            }));
+        }
+
+        // Parse only up to the body, but in place, so the tokens are only consumed once
+        let end = tokens.limit(length);
+        let expr = Expression::parse(tokens);
+        let rest = tokens.peek();
+        tokens.unlimit(end);
+        let expr = expr?;
+
+        if let Some(token) = rest {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::UnexpectedToken,
+                error_message: format!("Unexpected token after '{}': '{}'", keyword, token.value),
+                span_message: String::from("Expected '{' to start the body"),
+                token: token.clone(),
+                help: None,
+                info: None,
+            }));
         }
 
         Ok(expr)
     }
 
-    fn primary(tokens: &mut TokenStream) -> CompilerResult<Expression> {
-        if tokens.next_matches_any(&[todo!()]) {
-            todo!()
+    fn intrinsic(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::Intrinsic);
+        let start = tokens.unshift_expect(TokenType::At)?.span.start;
+        let name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        tokens.start_node(SyntaxKind::ArgumentList);
+        tokens.unshift_expect(TokenType::LeftParen)?;
+
+        let mut arguments = Vec::new();
+        while !tokens.next_matches(TokenType::RightParen) {
+            arguments.push(Expression::parse(tokens)?);
+            if !tokens.next_matches(TokenType::RightParen) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
         }
+        let end = tokens.unshift_expect(TokenType::RightParen)?.span.end;
+        tokens.finish_node();
+        tokens.finish_node();
 
-        if tokens.next_matches(TokenType::LeftParen) {
-            todo!()
+        Ok(Expression::new(
+            ExpressionType::Intrinsic(IntrinsicExpression {
+                name: name.value,
+                name_span: name.span,
+                arguments,
+            }),
+            start..end,
+        ))
+    }
+
+    fn list(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        tokens.start_node(SyntaxKind::List);
+        let start = tokens.unshift_expect(TokenType::LeftBracket)?.span.start;
+
+        let mut elements = Vec::new();
+        while !tokens.next_matches(TokenType::RightBracket) {
+            elements.push(Expression::parse(tokens)?);
+
+            // A comma directly before the closing bracket is allowed
+            if !tokens.next_matches(TokenType::RightBracket) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
         }
 
-        Expression::literal(tokens)
+        let end = tokens.unshift_expect(TokenType::RightBracket)?.span.end;
+        tokens.finish_node();
+
+        Ok(Expression::new(
+            ExpressionType::List(ListExpression { elements }),
+            start..end,
+        ))
     }
 
     fn literal(tokens: &mut TokenStream) -> CompilerResult<Expression> {
         if let Some(token) = tokens.peek() {
             match token.type_ {
-                TokenType::IntegerLiteral | TokenType::Identifier => {
+                TokenType::IntegerLiteral
+                | TokenType::StringLiteral
+                | TokenType::Identifier
+                | TokenType::Null => {
+                    tokens.start_node(match token.type_ {
+                        TokenType::Identifier => SyntaxKind::Name,
+                        _ => SyntaxKind::Literal,
+                    });
                     let token = tokens.unshift().unwrap().clone();
-                    Ok(Expression {
-                        expression: ExpressionType::Literal(LiteralExpression {
-                            literal: token.type_.into(),
-                            value: token.value,
-                        }),
-                        span: token.span,
-                    })
+                    tokens.finish_node();
+                    Ok(Expression::new(ExpressionType::Literal(LiteralExpression {
+                        literal: token.type_.into(),
+                        value: token.value,
+                    }), token.span))
                 }
-                _ => Err(CompilerError {
+                _ => Err(Box::new(CompilerError {
                     error_code: ErrorCode::InvalidExpression,
                     error_message: format!("Invalid expression: got '{}'", token.value),
                     span_message: String::from(""),
                     token: tokens.unshift().unwrap().clone(),
                     help: Some(String::from(
-                        "Expected one of: \n- Integer literal\n- TODO: More exprected tokens",
+                        "Expected one of: \n- Integer literal\n- String literal\n- TODO: More exprected tokens",
                     )),
                     info: None,
-                }),
+                })),
             }
         } else {
-            Err(CompilerError {
+            Err(Box::new(CompilerError {
                 error_code: ErrorCode::UnexpectedToken,
-                error_message: format!("End of file reached while parsing expression"),
+                error_message: String::from("End of file reached while parsing expression"),
                 span_message: String::from(""),
                 token: Token::invalid(), // TODO: Store the last token somewhere so we can use it in error messages
                 help: Some(String::from(
                     "Expected an expression, but reached the end of the file",
                 )),
                 info: None,
+            }))
+        }
+    }
+}
+
+/// Records the variables each closure captures from its environment, which are the names
+/// its body uses that are bound outside of it. Functions and enums are referred to by name
+/// rather than captured, so they are left out.
+///
+/// This runs once the whole file is parsed, so closures know about every function of the file.
+pub(crate) struct Captures {
+    scopes: Vec<CaptureScope>,
+    /// The captures of the closures being visited, innermost last.
+    closures: Vec<Vec<String>>,
+}
+
+struct CaptureScope {
+    /// The names bound in the scope, and whether each is a variable rather than a function.
+    names: Vec<(String, bool)>,
+    /// How many closures the scope is nested in.
+    depth: usize,
+}
+
+impl Captures {
+    pub(crate) fn record(ast: &mut AST) {
+        let mut captures = Captures {
+            scopes: Vec::new(),
+            closures: Vec::new(),
+        };
+
+        captures.push(&ast.statements);
+        walk_ast_mut(&mut captures, ast);
+    }
+
+    /// Pushes a scope for a block, where its functions can be called before they are declared.
+    fn push(&mut self, statements: &[Statement]) {
+        let names = statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementType::Function(function) => Some((function.name.clone(), false)),
+                StatementType::Enum(enum_) => Some((enum_.name.clone(), false)),
+                _ => None,
             })
+            .collect();
+
+        self.scopes.push(CaptureScope {
+            names,
+            depth: self.closures.len(),
+        });
+    }
+
+    /// Pushes a scope with `names` bound as variables.
+    fn push_variables(&mut self, names: Vec<String>) {
+        self.scopes.push(CaptureScope {
+            names: names.into_iter().map(|name| (name, true)).collect(),
+            depth: self.closures.len(),
+        });
+    }
+
+    fn bind(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.names.push((String::from(name), true));
+        }
+    }
+
+    fn use_(&mut self, name: &str) {
+        let binding = self.scopes.iter().rev().find_map(|scope| {
+            scope
+                .names
+                .iter()
+                .rev()
+                .find(|(bound, _)| bound == name)
+                .map(|(_, variable)| (scope.depth, *variable))
+        });
+
+        let depth = match binding {
+            Some((depth, true)) => depth,
+            Some((_, false)) => return,
+            // Bound outside of the file, such as by an import
+            None => 0,
+        };
+
+        // Every closure between the use and the binding captures the name
+        for captures in &mut self.closures[depth..] {
+            if !captures.iter().any(|captured| captured == name) {
+                captures.push(String::from(name));
+            }
+        }
+    }
+
+    /// Binds the parameters in order in a new scope, which the caller pops.
+    fn parameters(&mut self, parameters: &mut [Parameter]) {
+        self.push_variables(Vec::new());
+        for parameter in parameters {
+            self.visit_parameter_mut(parameter);
+            self.bind(&parameter.name);
         }
     }
 }
+
+impl VisitorMut for Captures {
+    fn visit_block_mut(&mut self, block: &mut BlockStatement) {
+        self.push(&block.statements);
+        walk_block_mut(self, block);
+        self.scopes.pop();
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementType::Const(const_) => {
+                self.visit_expression_mut(&mut const_.value);
+                self.bind(&const_.name);
+            }
+            StatementType::Let(let_) => {
+                if let Some(value) = &mut let_.value {
+                    self.visit_expression_mut(value);
+                }
+                self.bind(&let_.name);
+            }
+            StatementType::For(for_) => {
+                self.visit_expression_mut(&mut for_.iterable);
+                self.push_variables(vec![for_.binding.clone()]);
+                self.visit_block_mut(&mut for_.body);
+                self.scopes.pop();
+            }
+            StatementType::Try(try_) => {
+                self.visit_block_mut(&mut try_.body);
+                self.push_variables(vec![try_.binding.clone()]);
+                self.visit_block_mut(&mut try_.handler);
+                self.scopes.pop();
+            }
+            _ => walk_statement_mut(self, statement),
+        }
+    }
+
+    fn visit_function_mut(&mut self, function: &mut FunctionStatement) {
+        self.parameters(&mut function.parameters);
+        self.visit_block_mut(&mut function.body);
+        self.scopes.pop();
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        match &mut expression.kind {
+            ExpressionType::Closure(closure) => {
+                self.closures.push(Vec::new());
+                self.parameters(&mut closure.parameters);
+                match &mut closure.body {
+                    ClosureBody::Expression(body) => self.visit_expression_mut(body),
+                    ClosureBody::Block(block) => self.visit_block_mut(block),
+                }
+                self.scopes.pop();
+                closure.captures = self.closures.pop().unwrap_or_default();
+            }
+            ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) => self.use_(value),
+            ExpressionType::Match(match_) => {
+                self.visit_expression_mut(&mut match_.value);
+                for arm in &mut match_.arms {
+                    let names = arm.pattern.bindings().into_iter();
+                    self.push_variables(names.map(|(name, _)| String::from(name)).collect());
+                    if let Some(guard) = &mut arm.guard {
+                        self.visit_expression_mut(guard);
+                    }
+                    self.visit_expression_mut(&mut arm.body);
+                    self.scopes.pop();
+                }
+            }
+            _ => walk_expression_mut(self, expression),
+        }
+    }
+
+    // Attribute arguments name options, such as `cfg` predicates, rather than values
+    fn visit_attribute_mut(&mut self, _attribute: &mut Attribute) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{lexer::Lexer, node::NodeIds, parser::Parser};
+    use crate::tests::before_each;
+
+    #[test]
+    fn test_postfix_chain() {
+        before_each();
+        let input = "obj.method(x)[0](y)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+
+        assert_eq!(expr.span, 0..19);
+
+        let ExpressionType::FunctionCall(call) = expr.kind else {
+            panic!("Expected function call");
+        };
+        assert_eq!(call.arguments.len(), 1);
+        assert_eq!(call.callee.span, 0..16);
+
+        let ExpressionType::Index(index) = call.callee.kind else {
+            panic!("Expected index expression");
+        };
+        assert_eq!(index.index.span, 14..15);
+        assert_eq!(index.object.span, 0..13);
+
+        let ExpressionType::FunctionCall(call) = index.object.kind else {
+            panic!("Expected function call");
+        };
+        assert_eq!(call.callee.span, 0..10);
+
+        let ExpressionType::Member(member) = call.callee.kind else {
+            panic!("Expected member expression");
+        };
+        assert_eq!(member.property, "method");
+        assert_eq!(member.object.span, 0..3);
+    }
+
+    #[test]
+    fn test_record() {
+        before_each();
+        let input = "{ name: \"a\", age: 3 }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+
+        assert_eq!(expr.span, 0..21);
+
+        let ExpressionType::Record(record) = expr.kind else {
+            panic!("Expected record expression");
+        };
+        assert_eq!(record.fields.len(), 2);
+        assert_eq!(record.fields[0].key, "name");
+        assert_eq!(record.fields[0].key_span, 2..6);
+        assert_eq!(record.fields[0].span, 2..11);
+        assert_eq!(record.fields[1].key, "age");
+        assert_eq!(record.fields[1].span, 13..19);
+    }
+
+    #[test]
+    fn test_record_duplicate_key() {
+        before_each();
+        let input = "{ id: 1, id: 2 }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let Err(error) = Expression::parse(&mut lexer.get_tokens_peekable()) else {
+            panic!("Expected duplicate key error");
+        };
+
+        assert_eq!(error.error_code, ErrorCode::DuplicateRecordKey);
+        assert_eq!(error.token.span, 9..11);
+    }
+
+    #[test]
+    fn test_closure_captures() {
+        before_each();
+        let input = "|x: int| { let y = x; return y * factor * offset(x); }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+
+        assert_eq!(expr.span, 0..54);
+
+        let ExpressionType::Closure(closure) = expr.kind else {
+            panic!("Expected closure expression");
+        };
+        assert_eq!(closure.parameters.len(), 1);
+        assert_eq!(closure.parameters[0].span, 1..7);
+
+        let captures = |input: &str| {
+            let mut lexer = Lexer::new();
+            lexer.lex(input);
+            let ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+                .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+
+            let StatementType::Let(let_) = &ast.statements.last().unwrap().kind else {
+                panic!("Expected let statement");
+            };
+            let Some(Expression {
+                kind: ExpressionType::Closure(closure),
+                ..
+            }) = &let_.value
+            else {
+                panic!("Expected closure expression");
+            };
+            closure.captures.clone()
+        };
+
+        // Functions are called by name, not captured
+        assert_eq!(
+            captures(&format!(
+                "function offset(n: int) {{ return n; }} let f = {};",
+                input
+            )),
+            vec!["factor"]
+        );
+        // Unless a variable of the same name shadows the function
+        assert_eq!(
+            captures("function n() { return 1; } let n = 2; let f = || n;"),
+            vec!["n"]
+        );
+        // Outer closures capture what their nested closures do
+        assert_eq!(captures("let a = 1; let f = |b: int| || a + b;"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_if_expression() {
+        before_each();
+        let input = "if ready {} else if n > 0 { n } else { 0 }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|err| {
+            panic!("Failed to parse expression: {}", err.error_message);
+        });
+
+        assert_eq!(expr.span, 0..42);
+
+        let ExpressionType::If(if_) = expr.kind else {
+            panic!("Expected if expression");
+        };
+        // `ready {}` must not be parsed as constructing a struct
+        assert_eq!(if_.condition.span, 3..8);
+        assert!(if_.then_branch.statements.is_empty());
+        assert!(if_.then_branch.tail.is_none());
+
+        let ExpressionType::If(else_if) = if_.else_branch.unwrap().kind else {
+            panic!("Expected else if");
+        };
+        assert!(matches!(else_if.condition.kind, ExpressionType::Binary(_)));
+        assert_eq!(else_if.then_branch.tail.unwrap().span, 28..29);
+        assert_eq!(else_if.else_branch.unwrap().span, 37..42);
+    }
+
+    #[test]
+    fn test_block_tail() {
+        before_each();
+        let input = "{ let t = f(); t * t }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|err| {
+            panic!("Failed to parse expression: {}", err.error_message);
+        });
+
+        assert_eq!(expr.span, 0..22);
+
+        let ExpressionType::Block(block) = expr.kind else {
+            panic!("Expected block expression");
+        };
+        assert_eq!(block.statements.len(), 1);
+        assert_eq!(block.tail.unwrap().span, 15..20);
+    }
+
+    #[test]
+    fn test_ranges() {
+        before_each();
+        let input = "xs[1..=n - 1][..2][i..]";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|err| {
+            panic!("Failed to parse expression: {}", err.error_message);
+        });
+
+        let ExpressionType::Index(open_end) = expr.kind else {
+            panic!("Expected index expression");
+        };
+        let ExpressionType::Range(range) = open_end.index.kind else {
+            panic!("Expected range expression");
+        };
+        assert_eq!(open_end.index.span, 19..22);
+        assert!(range.start.is_some() && range.end.is_none());
+
+        let ExpressionType::Index(open_start) = open_end.object.kind else {
+            panic!("Expected index expression");
+        };
+        assert_eq!(open_start.index.span, 14..17);
+
+        let ExpressionType::Index(inclusive) = open_start.object.kind else {
+            panic!("Expected index expression");
+        };
+        let ExpressionType::Range(range) = inclusive.index.kind else {
+            panic!("Expected range expression");
+        };
+        assert!(range.inclusive);
+        assert_eq!(range.end.unwrap().span, 7..12);
+
+        lexer.lex("xs[1..=]");
+        let Err(error) = Expression::parse(&mut lexer.get_tokens_peekable()) else {
+            panic!("Expected inclusive range error");
+        };
+        assert_eq!(error.error_code, ErrorCode::InvalidRange);
+    }
+
+    #[test]
+    fn test_null_safety_operators() {
+        before_each();
+        let input = "a?.b?[0] ?? c ?? 1";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|err| {
+            panic!("Failed to parse expression: {}", err.error_message);
+        });
+
+        assert_eq!(expr.span, 0..18);
+
+        let ExpressionType::Coalesce(coalesce) = expr.kind else {
+            panic!("Expected coalesce expression");
+        };
+        // `??` is right-associative
+        assert_eq!(coalesce.default.span, 12..18);
+        assert!(matches!(
+            coalesce.default.kind,
+            ExpressionType::Coalesce(_)
+        ));
+
+        let ExpressionType::OptionalIndex(index) = coalesce.value.kind else {
+            panic!("Expected optional index expression");
+        };
+        assert_eq!(index.index.span, 6..7);
+
+        let ExpressionType::OptionalMember(member) = index.object.kind else {
+            panic!("Expected optional member expression");
+        };
+        assert_eq!(member.property, "b");
+        assert_eq!(member.object.span, 0..1);
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        before_each();
+        let input = "query(\"users\", limit: 5)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+
+        let ExpressionType::FunctionCall(call) = expr.kind else {
+            panic!("Expected function call");
+        };
+        assert_eq!(call.arguments.len(), 2);
+        assert_eq!(call.arguments[0].name, None);
+        assert_eq!(call.arguments[1].name.as_deref(), Some("limit"));
+        assert_eq!(call.arguments[1].span, 15..23);
+        assert_eq!(call.arguments[1].value.span, 22..23);
+
+        let mut lexer = Lexer::new();
+        lexer.lex("query(limit: 5, \"users\")");
+        let Err(error) = Expression::parse(&mut lexer.get_tokens_peekable()) else {
+            panic!("Expected positional after named error");
+        };
+        assert_eq!(error.error_code, ErrorCode::PositionalAfterNamed);
+        assert_eq!(error.token.span, 16..23);
+    }
+
+    #[test]
+    fn test_yield() {
+        before_each();
+        let input = "yield a ?? 1";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+
+        assert_eq!(expr.span, 0..12);
+        let ExpressionType::Yield(yield_) = expr.kind else {
+            panic!("Expected yield expression");
+        };
+        let Some(value) = yield_.value else {
+            panic!("Expected a yielded value");
+        };
+        assert!(matches!(value.kind, ExpressionType::Coalesce(_)));
+
+        let mut lexer = Lexer::new();
+        lexer.lex("yield;");
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+        assert_eq!(expr.kind, ExpressionType::Yield(YieldExpression { value: None }));
+        assert_eq!(expr.span, 0..5);
+    }
+
+    #[test]
+    fn test_unary() {
+        before_each();
+        let input = "!ready && !!done";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|_| {
+            panic!("Failed to parse expression");
+        });
+
+        let ExpressionType::Binary(binary) = expr.kind else {
+            panic!("Expected binary expression");
+        };
+        assert_eq!(binary.left.span, 0..6);
+        assert!(matches!(binary.left.kind, ExpressionType::Unary(_)));
+
+        let ExpressionType::Unary(unary) = binary.right.kind else {
+            panic!("Expected unary expression");
+        };
+        assert_eq!(unary.operator.type_, TokenType::Bang);
+        assert_eq!(unary.operand.span, 11..16);
+    }
+
+    #[test]
+    fn test_match() {
+        before_each();
+        let input = "match s { Status.Suspended(r) if r == \"x\" => 1, n => n, }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let expr = Expression::parse(&mut lexer.get_tokens_peekable()).unwrap_or_else(|err| {
+            panic!("Failed to parse expression: {}", err.error_message);
+        });
+
+        assert_eq!(expr.span, 0..57);
+
+        let ExpressionType::Match(match_) = expr.kind else {
+            panic!("Expected match expression");
+        };
+        // `s {` must not be parsed as constructing a struct
+        assert_eq!(match_.value.span, 6..7);
+        assert_eq!(match_.arms.len(), 2);
+
+        let guarded = &match_.arms[0];
+        assert_eq!(guarded.pattern.span, 10..29);
+        assert!(matches!(
+            guarded.guard.as_ref().map(|guard| &guard.kind),
+            Some(ExpressionType::Binary(_))
+        ));
+        assert_eq!(guarded.body.span, 45..46);
+
+        let binding = &match_.arms[1];
+        assert!(binding.guard.is_none());
+        assert_eq!(binding.pattern.bindings()[0].0, "n");
+    }
+}
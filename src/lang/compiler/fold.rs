@@ -0,0 +1,361 @@
+//! Rewriting the AST by value, the owning counterpart of [`super::visit`].
+//!
+//! Each `fold_*` method of [`Fold`] defaults to the matching `fold_*` function, which folds the
+//! children of the node and rebuilds it. Like the walkers, these match every node variant
+//! without a wildcard.
+
+use super::{
+    attribute::Attribute,
+    expression::{ClosureBody, Expression, ExpressionType, RecordField},
+    parser::AST,
+    statement::{BlockStatement, FunctionStatement, Parameter, Statement, StatementType},
+};
+
+/// Rebuilds the AST from the nodes each `fold_*` method returns.
+pub(crate) trait Fold: Sized {
+    fn fold_ast(&mut self, ast: AST) -> AST {
+        fold_ast(self, ast)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        fold_statement(self, statement)
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        fold_expression(self, expression)
+    }
+
+    fn fold_block(&mut self, block: BlockStatement) -> BlockStatement {
+        fold_block(self, block)
+    }
+
+    fn fold_function(&mut self, function: FunctionStatement) -> FunctionStatement {
+        fold_function(self, function)
+    }
+
+    fn fold_parameter(&mut self, parameter: Parameter) -> Parameter {
+        fold_parameter(self, parameter)
+    }
+
+    fn fold_attribute(&mut self, attribute: Attribute) -> Attribute {
+        fold_attribute(self, attribute)
+    }
+}
+
+pub(crate) fn fold_ast<F: Fold>(folder: &mut F, ast: AST) -> AST {
+    AST {
+        statements: ast
+            .statements
+            .into_iter()
+            .map(|statement| folder.fold_statement(statement))
+            .collect(),
+        ..ast
+    }
+}
+
+pub(crate) fn fold_statement<F: Fold>(folder: &mut F, statement: Statement) -> Statement {
+    let kind = match statement.kind {
+        StatementType::Unknown => StatementType::Unknown,
+        StatementType::Import(import) => StatementType::Import(import),
+        StatementType::Block(block) => StatementType::Block(folder.fold_block(block)),
+        StatementType::Const(mut const_) => {
+            const_.attributes = fold_attributes(folder, const_.attributes);
+            const_.value = folder.fold_expression(const_.value);
+            StatementType::Const(const_)
+        }
+        StatementType::Enum(mut enum_) => {
+            enum_.attributes = fold_attributes(folder, enum_.attributes);
+            StatementType::Enum(enum_)
+        }
+        StatementType::Expression(expression) => {
+            StatementType::Expression(folder.fold_expression(expression))
+        }
+        StatementType::For(mut for_) => {
+            for_.iterable = folder.fold_expression(for_.iterable);
+            for_.body = folder.fold_block(for_.body);
+            StatementType::For(for_)
+        }
+        StatementType::Let(mut let_) => {
+            let_.attributes = fold_attributes(folder, let_.attributes);
+            let_.value = let_.value.map(|value| folder.fold_expression(value));
+            StatementType::Let(let_)
+        }
+        StatementType::Function(function) => {
+            StatementType::Function(folder.fold_function(function))
+        }
+        StatementType::Return(mut return_) => {
+            return_.value = return_.value.map(|value| folder.fold_expression(value));
+            StatementType::Return(return_)
+        }
+        StatementType::Intrinsic(mut intrinsic) => {
+            intrinsic.arguments = fold_expressions(folder, intrinsic.arguments);
+            StatementType::Intrinsic(intrinsic)
+        }
+        StatementType::Struct(mut struct_) => {
+            struct_.attributes = fold_attributes(folder, struct_.attributes);
+            StatementType::Struct(struct_)
+        }
+        StatementType::Try(mut try_) => {
+            try_.body = folder.fold_block(try_.body);
+            try_.handler = folder.fold_block(try_.handler);
+            StatementType::Try(try_)
+        }
+    };
+
+    Statement { kind, ..statement }
+}
+
+pub(crate) fn fold_expression<F: Fold>(folder: &mut F, expression: Expression) -> Expression {
+    let kind = match expression.kind {
+        ExpressionType::Assignment(mut assignment) => {
+            assignment.target = fold_boxed(folder, assignment.target);
+            assignment.value = fold_boxed(folder, assignment.value);
+            ExpressionType::Assignment(assignment)
+        }
+        ExpressionType::Binary(mut binary) => {
+            binary.left = fold_boxed(folder, binary.left);
+            binary.right = fold_boxed(folder, binary.right);
+            ExpressionType::Binary(binary)
+        }
+        ExpressionType::Block(block) => ExpressionType::Block(folder.fold_block(block)),
+        ExpressionType::Closure(mut closure) => {
+            closure.parameters = closure
+                .parameters
+                .into_iter()
+                .map(|parameter| folder.fold_parameter(parameter))
+                .collect();
+            closure.body = match closure.body {
+                ClosureBody::Expression(body) => ClosureBody::Expression(fold_boxed(folder, body)),
+                ClosureBody::Block(block) => ClosureBody::Block(folder.fold_block(block)),
+            };
+            ExpressionType::Closure(closure)
+        }
+        ExpressionType::Coalesce(mut coalesce) => {
+            coalesce.value = fold_boxed(folder, coalesce.value);
+            coalesce.default = fold_boxed(folder, coalesce.default);
+            ExpressionType::Coalesce(coalesce)
+        }
+        ExpressionType::FunctionCall(mut call) => {
+            call.callee = fold_boxed(folder, call.callee);
+            call.arguments = call
+                .arguments
+                .into_iter()
+                .map(|mut argument| {
+                    argument.value = folder.fold_expression(argument.value);
+                    argument
+                })
+                .collect();
+            ExpressionType::FunctionCall(call)
+        }
+        ExpressionType::If(mut if_) => {
+            if_.condition = fold_boxed(folder, if_.condition);
+            if_.then_branch = folder.fold_block(if_.then_branch);
+            if_.else_branch = if_.else_branch.map(|branch| fold_boxed(folder, branch));
+            ExpressionType::If(if_)
+        }
+        ExpressionType::Index(mut index) => {
+            index.object = fold_boxed(folder, index.object);
+            index.index = fold_boxed(folder, index.index);
+            ExpressionType::Index(index)
+        }
+        ExpressionType::OptionalIndex(mut index) => {
+            index.object = fold_boxed(folder, index.object);
+            index.index = fold_boxed(folder, index.index);
+            ExpressionType::OptionalIndex(index)
+        }
+        ExpressionType::Intrinsic(mut intrinsic) => {
+            intrinsic.arguments = fold_expressions(folder, intrinsic.arguments);
+            ExpressionType::Intrinsic(intrinsic)
+        }
+        ExpressionType::List(mut list) => {
+            list.elements = fold_expressions(folder, list.elements);
+            ExpressionType::List(list)
+        }
+        ExpressionType::Literal(literal) => ExpressionType::Literal(literal),
+        ExpressionType::Match(mut match_) => {
+            match_.value = fold_boxed(folder, match_.value);
+            match_.arms = match_
+                .arms
+                .into_iter()
+                .map(|mut arm| {
+                    arm.guard = arm.guard.map(|guard| folder.fold_expression(guard));
+                    arm.body = folder.fold_expression(arm.body);
+                    arm
+                })
+                .collect();
+            ExpressionType::Match(match_)
+        }
+        ExpressionType::Member(mut member) => {
+            member.object = fold_boxed(folder, member.object);
+            ExpressionType::Member(member)
+        }
+        ExpressionType::OptionalMember(mut member) => {
+            member.object = fold_boxed(folder, member.object);
+            ExpressionType::OptionalMember(member)
+        }
+        ExpressionType::Propagate(mut propagate) => {
+            propagate.value = fold_boxed(folder, propagate.value);
+            ExpressionType::Propagate(propagate)
+        }
+        ExpressionType::Range(mut range) => {
+            range.start = range.start.map(|start| fold_boxed(folder, start));
+            range.end = range.end.map(|end| fold_boxed(folder, end));
+            ExpressionType::Range(range)
+        }
+        ExpressionType::Record(mut record) => {
+            record.fields = fold_fields(folder, record.fields);
+            ExpressionType::Record(record)
+        }
+        ExpressionType::Struct(mut struct_) => {
+            struct_.fields = fold_fields(folder, struct_.fields);
+            ExpressionType::Struct(struct_)
+        }
+        ExpressionType::Unary(mut unary) => {
+            unary.operand = fold_boxed(folder, unary.operand);
+            ExpressionType::Unary(unary)
+        }
+        ExpressionType::Yield(mut yield_) => {
+            yield_.value = yield_.value.map(|value| fold_boxed(folder, value));
+            ExpressionType::Yield(yield_)
+        }
+    };
+
+    Expression { kind, ..expression }
+}
+
+pub(crate) fn fold_block<F: Fold>(folder: &mut F, block: BlockStatement) -> BlockStatement {
+    BlockStatement {
+        statements: block
+            .statements
+            .into_iter()
+            .map(|statement| folder.fold_statement(statement))
+            .collect(),
+        tail: block.tail.map(|tail| fold_boxed(folder, tail)),
+    }
+}
+
+pub(crate) fn fold_function<F: Fold>(
+    folder: &mut F,
+    mut function: FunctionStatement,
+) -> FunctionStatement {
+    function.attributes = fold_attributes(folder, function.attributes);
+    function.parameters = function
+        .parameters
+        .into_iter()
+        .map(|parameter| folder.fold_parameter(parameter))
+        .collect();
+    function.body = folder.fold_block(function.body);
+    function
+}
+
+pub(crate) fn fold_parameter<F: Fold>(folder: &mut F, mut parameter: Parameter) -> Parameter {
+    parameter.default = parameter
+        .default
+        .map(|default| folder.fold_expression(default));
+    parameter
+}
+
+pub(crate) fn fold_attribute<F: Fold>(folder: &mut F, mut attribute: Attribute) -> Attribute {
+    attribute.arguments = fold_expressions(folder, attribute.arguments);
+    attribute
+}
+
+/// Folds the expression in place, so its box is reused.
+fn fold_boxed<F: Fold>(folder: &mut F, mut expression: Box<Expression>) -> Box<Expression> {
+    *expression = folder.fold_expression(*expression);
+    expression
+}
+
+fn fold_expressions<F: Fold>(folder: &mut F, expressions: Vec<Expression>) -> Vec<Expression> {
+    expressions
+        .into_iter()
+        .map(|expression| folder.fold_expression(expression))
+        .collect()
+}
+
+fn fold_attributes<F: Fold>(folder: &mut F, attributes: Vec<Attribute>) -> Vec<Attribute> {
+    attributes
+        .into_iter()
+        .map(|attribute| folder.fold_attribute(attribute))
+        .collect()
+}
+
+fn fold_fields<F: Fold>(folder: &mut F, fields: Vec<RecordField>) -> Vec<RecordField> {
+    fields
+        .into_iter()
+        .map(|mut field| {
+            field.value = folder.fold_expression(field.value);
+            field
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{
+        expression::UnaryExpression, lexer::Lexer, node::NodeIds, parser::Parser,
+    };
+    use crate::tests::before_each;
+
+    fn parse(input: &str) -> AST {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message))
+    }
+
+    /// Replaces `!!x` with `x`.
+    struct DoubleNegation;
+
+    impl Fold for DoubleNegation {
+        fn fold_expression(&mut self, expression: Expression) -> Expression {
+            let expression = fold_expression(self, expression);
+            match expression.kind {
+                ExpressionType::Unary(outer) => match outer.operand.kind {
+                    ExpressionType::Unary(inner) => *inner.operand,
+                    kind => Expression {
+                        kind: ExpressionType::Unary(UnaryExpression {
+                            operand: Box::new(Expression {
+                                kind,
+                                ..*outer.operand
+                            }),
+                            ..outer
+                        }),
+                        ..expression
+                    },
+                },
+                kind => Expression { kind, ..expression },
+            }
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        before_each();
+        let ast = DoubleNegation.fold_ast(parse("let a = !!b; let c = { !!!d };"));
+
+        let values: Vec<&Expression> = ast
+            .statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementType::Let(let_) => let_.value.as_ref(),
+                _ => None,
+            })
+            .collect();
+
+        assert!(
+            matches!(&values[0].kind, ExpressionType::Literal(literal) if literal.value == "b")
+        );
+
+        let ExpressionType::Block(block) = &values[1].kind else {
+            panic!("Expected block");
+        };
+        let Some(ExpressionType::Unary(unary)) = block.tail.as_ref().map(|tail| &tail.kind) else {
+            panic!("Expected a single negation");
+        };
+        assert!(
+            matches!(&unary.operand.kind, ExpressionType::Literal(literal) if literal.value == "d")
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::{fs, path::Path};
+
+use super::{
+    expression::{
+        Expression, ExpressionType, IntrinsicExpression, ListExpression, LiteralExpression,
+        LiteralType,
+    },
+    fold::{fold_expression, fold_statement, Fold},
+    node::{Node, NodeId, NodeIds},
+    parser::AST,
+    statement::{Statement, StatementType},
+    token::{Span, Token, TokenType},
+    CompilerError, CompilerResult, ErrorCode,
+};
+
+/// The intrinsics that embed a file's contents at compile time.
+pub(crate) const INCLUDE_INTRINSICS: &[&str] = &["include_str", "include_bytes"];
+
+/// Replaces `@include_str("path")` with a string literal and `@include_bytes("path")` with a
+/// list of integer literals, reading the files relative to `directory`.
+///
+/// This runs on the AST once `#[cfg(...)]` attributes are evaluated, so declarations that are
+/// compiled out never read their files, and the checker only ever sees the literals.
+pub(crate) struct IncludeExpander<'a> {
+    directory: &'a Path,
+    ids: &'a mut NodeIds,
+    errors: Vec<CompilerError>,
+}
+
+impl<'a> IncludeExpander<'a> {
+    /// Expands every include in `ast`, giving the literals fresh ids from `ids`.
+    pub(crate) fn expand(
+        ast: AST,
+        directory: &'a Path,
+        ids: &'a mut NodeIds,
+    ) -> (AST, Vec<CompilerError>) {
+        let mut expander = IncludeExpander {
+            directory,
+            ids,
+            errors: Vec::new(),
+        };
+        let ast = expander.fold_ast(ast);
+        (ast, expander.errors)
+    }
+
+    /// Reads the file named by the path argument of an include and returns the literal for it,
+    /// which takes over the `id` of the include.
+    fn include(
+        &mut self,
+        intrinsic: &IntrinsicExpression,
+        id: NodeId,
+        span: &Span,
+    ) -> CompilerResult<Expression> {
+        let [Node {
+            kind:
+                ExpressionType::Literal(LiteralExpression {
+                    literal: LiteralType::String,
+                    value: path,
+                }),
+            span: path_span,
+            ..
+        }] = intrinsic.arguments.as_slice()
+        else {
+            return Err(IncludeExpander::invalid(intrinsic, span));
+        };
+
+        let path_token = Token {
+            type_: TokenType::StringLiteral,
+            value: path.clone(),
+            span: path_span.clone(),
+        };
+        let resolved = self.directory.join(path);
+
+        let bytes = fs::read(&resolved).map_err(|err| CompilerError {
+            error_code: ErrorCode::IncludeNotFound,
+            error_message: format!("Could not read '{}': {}", path, err),
+            span_message: format!("Resolved to '{}'", resolved.display()),
+            token: path_token.clone(),
+            help: Some(String::from(
+                "Include paths are relative to the file containing the include",
+            )),
+            info: None,
+        })?;
+
+        let kind = if intrinsic.name == "include_str" {
+            let contents = String::from_utf8(bytes).map_err(|_| CompilerError {
+                error_code: ErrorCode::InvalidInclude,
+                error_message: format!("'{}' is not valid UTF-8", path),
+                span_message: String::from("This file can't be included as a string"),
+                token: path_token,
+                help: Some(String::from("Use '@include_bytes' for binary files")),
+                info: None,
+            })?;
+
+            literal(LiteralType::String, contents)
+        } else {
+            let elements = bytes
+                .iter()
+                .map(|byte| Node {
+                    id: self.ids.next(),
+                    kind: literal(LiteralType::Integer, byte.to_string()),
+                    span: span.clone(),
+                })
+                .collect();
+
+            ExpressionType::List(ListExpression { elements })
+        };
+
+        Ok(Node {
+            id,
+            kind,
+            span: span.clone(),
+        })
+    }
+
+    fn invalid(intrinsic: &IntrinsicExpression, span: &Span) -> Box<CompilerError> {
+        Box::new(CompilerError {
+            error_code: ErrorCode::InvalidInclude,
+            error_message: format!("'@{}' expects a single string literal path", intrinsic.name),
+            span_message: String::from("Expected a path such as (\"data.txt\")"),
+            token: Token {
+                type_: TokenType::Identifier,
+                value: intrinsic.name.clone(),
+                span: span.clone(),
+            },
+            help: None,
+            info: None,
+        })
+    }
+}
+
+impl Fold for IncludeExpander<'_> {
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        match statement.kind {
+            StatementType::Intrinsic(intrinsic) if is_include(&intrinsic) => {
+                let id = self.ids.next();
+                let expression = self.fold_expression(Node {
+                    id,
+                    kind: ExpressionType::Intrinsic(intrinsic),
+                    span: statement.span.clone(),
+                });
+
+                Statement {
+                    kind: StatementType::Expression(expression),
+                    ..statement
+                }
+            }
+            _ => fold_statement(self, statement),
+        }
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        match &expression.kind {
+            ExpressionType::Intrinsic(intrinsic) if is_include(intrinsic) => {
+                match self.include(intrinsic, expression.id, &expression.span) {
+                    Ok(literal) => literal,
+                    Err(error) => {
+                        self.errors.push(*error);
+                        expression
+                    }
+                }
+            }
+            _ => fold_expression(self, expression),
+        }
+    }
+}
+
+fn is_include(intrinsic: &IntrinsicExpression) -> bool {
+    INCLUDE_INTRINSICS.contains(&intrinsic.name.as_str())
+}
+
+fn literal(literal: LiteralType, value: String) -> ExpressionType {
+    ExpressionType::Literal(LiteralExpression { literal, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{cfg::Cfg, lexer::Lexer, parser::Parser};
+    use crate::tests::before_each;
+
+    fn expand(input: &str, flags: &[&str]) -> (AST, Vec<ErrorCode>) {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let mut ids = NodeIds::default();
+        let mut ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut ids)
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+
+        let flags: Vec<String> = flags.iter().map(|flag| String::from(*flag)).collect();
+        assert!(Cfg::from_flags(&flags).strip(&mut ast).is_empty());
+
+        let (ast, errors) = IncludeExpander::expand(ast, Path::new("tests/e2e"), &mut ids);
+        (
+            ast,
+            errors.into_iter().map(|error| error.error_code).collect(),
+        )
+    }
+
+    fn value(ast: &AST) -> &ExpressionType {
+        let StatementType::Const(const_) = &ast.statements[0].kind else {
+            panic!("Expected a const statement");
+        };
+        &const_.value.kind
+    }
+
+    #[test]
+    fn test_include() {
+        before_each();
+        let (ast, errors) = expand("const s = @include_str(\"data/greeting.txt\");", &[]);
+        assert!(errors.is_empty());
+        assert_eq!(
+            value(&ast),
+            &literal(LiteralType::String, String::from("hello\n"))
+        );
+
+        let (ast, errors) = expand("const b = @include_bytes(\"data/greeting.txt\");", &[]);
+        assert!(errors.is_empty());
+        let ExpressionType::List(list) = value(&ast) else {
+            panic!("Expected a list");
+        };
+        let values: Vec<&ExpressionType> = list.elements.iter().map(|byte| &byte.kind).collect();
+        let expected: Vec<ExpressionType> = [104, 101, 108, 108, 111, 10]
+            .iter()
+            .map(|byte: &u8| literal(LiteralType::Integer, byte.to_string()))
+            .collect();
+        assert_eq!(values, expected.iter().collect::<Vec<_>>());
+
+        // The bytes get ids of their own
+        let mut ids: Vec<NodeId> = list.elements.iter().map(|byte| byte.id).collect();
+        ids.push(ast.statements[0].id);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 7);
+    }
+
+    #[test]
+    fn test_include_errors() {
+        before_each();
+        let (_, errors) = expand(
+            "@include_str(\"data/missing.txt\"); @include_bytes(s);",
+            &[],
+        );
+        assert_eq!(
+            errors,
+            vec![ErrorCode::IncludeNotFound, ErrorCode::InvalidInclude]
+        );
+
+        let (ast, errors) = expand("@print(\"include_str\");", &[]);
+        assert!(errors.is_empty());
+        assert!(matches!(
+            ast.statements[0].kind,
+            StatementType::Intrinsic(_)
+        ));
+    }
+
+    #[test]
+    fn test_include_behind_cfg() {
+        before_each();
+        let input = "#[cfg(prod)] const SCHEMA = @include_str(\"data/prod_only.sql\");";
+
+        // A compiled out declaration never reads its file
+        let (ast, errors) = expand(input, &[]);
+        assert!(errors.is_empty());
+        assert!(ast.statements.is_empty());
+
+        let (_, errors) = expand(input, &["prod"]);
+        assert_eq!(errors, vec![ErrorCode::IncludeNotFound]);
+    }
+}
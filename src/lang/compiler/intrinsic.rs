@@ -0,0 +1,139 @@
+use super::types::ParameterType;
+use crate::lang::util::suggest;
+
+/// The signature of an `@name(...)` intrinsic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Intrinsic {
+    pub(crate) name: String,
+    /// `ParameterType::Unknown` accepts an argument of any type.
+    pub(crate) parameters: Vec<ParameterType>,
+    /// Whether the last parameter can be repeated any number of times.
+    pub(crate) variadic: bool,
+    pub(crate) return_type: ParameterType,
+}
+
+impl Intrinsic {
+    pub(crate) fn new(
+        name: &str,
+        parameters: Vec<ParameterType>,
+        return_type: ParameterType,
+    ) -> Intrinsic {
+        Intrinsic {
+            name: String::from(name),
+            parameters,
+            variadic: false,
+            return_type,
+        }
+    }
+
+    pub(crate) fn variadic(mut self) -> Intrinsic {
+        self.variadic = true;
+        self
+    }
+
+    pub(crate) fn accepts_arity(&self, arguments: usize) -> bool {
+        if self.variadic {
+            arguments >= self.parameters.len()
+        } else {
+            arguments == self.parameters.len()
+        }
+    }
+
+    /// The parameter an argument at `index` is checked against.
+    pub(crate) fn parameter(&self, index: usize) -> Option<&ParameterType> {
+        match self.parameters.get(index) {
+            Some(parameter) => Some(parameter),
+            None if self.variadic => self.parameters.last(),
+            None => None,
+        }
+    }
+
+    /// Describes how many arguments the intrinsic takes, such as `1` or `at least 1`.
+    pub(crate) fn arity(&self) -> String {
+        if self.variadic {
+            format!("at least {}", self.parameters.len())
+        } else {
+            self.parameters.len().to_string()
+        }
+    }
+}
+
+/// The intrinsics that `@name(...)` calls are checked against.
+///
+/// `IntrinsicRegistry::default()` contains the built-in intrinsics. Embedders
+/// can add their own with [`IntrinsicRegistry::register`] before compiling.
+#[derive(Debug, Clone)]
+pub(crate) struct IntrinsicRegistry {
+    intrinsics: Vec<Intrinsic>,
+}
+
+impl Default for IntrinsicRegistry {
+    fn default() -> Self {
+        let mut registry = IntrinsicRegistry {
+            intrinsics: Vec::new(),
+        };
+
+        let unit = ParameterType::Tuple(Vec::new());
+
+        registry.register(
+            Intrinsic::new("print", vec![ParameterType::Unknown], unit.clone()).variadic(),
+        );
+        registry.register(Intrinsic::new(
+            "len",
+            vec![ParameterType::Unknown],
+            ParameterType::Integer,
+        ));
+        registry.register(Intrinsic::new(
+            "assert",
+            vec![ParameterType::Boolean, ParameterType::String],
+            unit.clone(),
+        ));
+        registry.register(Intrinsic::new("panic", vec![ParameterType::String], unit));
+
+        let any_result = ParameterType::Result(
+            Box::new(ParameterType::Unknown),
+            Box::new(ParameterType::Unknown),
+        );
+        registry.register(Intrinsic::new(
+            "ok",
+            vec![ParameterType::Unknown],
+            any_result.clone(),
+        ));
+        registry.register(Intrinsic::new(
+            "err",
+            vec![ParameterType::Unknown],
+            any_result,
+        ));
+        registry.register(Intrinsic::new(
+            "error",
+            vec![ParameterType::String],
+            ParameterType::Error,
+        ));
+
+        registry
+    }
+}
+
+impl IntrinsicRegistry {
+    /// Adds an intrinsic, replacing any intrinsic with the same name.
+    pub(crate) fn register(&mut self, intrinsic: Intrinsic) {
+        self.intrinsics.retain(|known| known.name != intrinsic.name);
+        self.intrinsics.push(intrinsic);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Intrinsic> {
+        self.intrinsics
+            .iter()
+            .find(|intrinsic| intrinsic.name == name)
+    }
+
+    /// The registered intrinsic with the name closest to `name`, if any is close enough.
+    pub(crate) fn suggest(&self, name: &str) -> Option<&str> {
+        suggest::closest(
+            name,
+            self.intrinsics
+                .iter()
+                .map(|intrinsic| intrinsic.name.as_str()),
+        )
+    }
+}
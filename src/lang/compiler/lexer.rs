@@ -41,14 +41,102 @@ impl<'a> Iterator for Lexer<'a> {
                 String::from(","),
                 start_position..self.position
             )),
+            Some('.') => {
+                if self.chars.next_if_eq(&'.').is_none() {
+                    Some(make_token!(
+                        Dot,
+                        String::from("."),
+                        start_position..self.position
+                    ))
+                } else if self.chars.next_if_eq(&'.').is_some() {
+                    self.position += 2;
+                    Some(make_token!(
+                        DotDotDot,
+                        String::from("..."),
+                        start_position..self.position
+                    ))
+                } else if self.chars.next_if_eq(&'=').is_some() {
+                    self.position += 2;
+                    Some(make_token!(
+                        DotDotEquals,
+                        String::from("..="),
+                        start_position..self.position
+                    ))
+                } else {
+                    self.position += 1;
+                    Some(make_token!(
+                        DotDot,
+                        String::from(".."),
+                        start_position..self.position
+                    ))
+                }
+            }
             Some(':') => Some(make_token!(
                 Colon,
                 String::from(":"),
                 start_position..self.position
             )),
-            Some('=') => Some(make_token!(
-                Equals,
-                String::from("="),
+            Some('=') => {
+                if self.chars.next_if_eq(&'=').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        EqualsEquals,
+                        String::from("=="),
+                        start_position..self.position
+                    ))
+                } else if self.chars.next_if_eq(&'>').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        FatArrow,
+                        String::from("=>"),
+                        start_position..self.position
+                    ))
+                } else {
+                    Some(make_token!(
+                        Equals,
+                        String::from("="),
+                        start_position..self.position
+                    ))
+                }
+            }
+            // A lone `!` is not an operator yet, so it is lexed as an unknown token
+            Some('!') if self.chars.next_if_eq(&'=').is_some() => {
+                self.position += 1;
+                Some(make_token!(
+                    BangEquals,
+                    String::from("!="),
+                    start_position..self.position
+                ))
+            }
+            Some('!') => Some(make_token!(
+                Bang,
+                String::from("!"),
+                start_position..self.position
+            )),
+            Some('&') => {
+                if self.chars.next_if_eq(&'&').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        AmpersandAmpersand,
+                        String::from("&&"),
+                        start_position..self.position
+                    ))
+                } else {
+                    Some(make_token!(
+                        Ampersand,
+                        String::from("&"),
+                        start_position..self.position
+                    ))
+                }
+            }
+            Some('>') => Some(make_token!(
+                GreaterThan,
+                String::from(">"),
+                start_position..self.position
+            )),
+            Some('#') => Some(make_token!(
+                Hash,
+                String::from("#"),
                 start_position..self.position
             )),
             Some('{') => Some(make_token!(
@@ -56,16 +144,98 @@ impl<'a> Iterator for Lexer<'a> {
                 String::from("{"),
                 start_position..self.position
             )),
+            Some('[') => Some(make_token!(
+                LeftBracket,
+                String::from("["),
+                start_position..self.position
+            )),
             Some('(') => Some(make_token!(
                 LeftParen,
                 String::from("("),
                 start_position..self.position
             )),
+            Some('<') => Some(make_token!(
+                LessThan,
+                String::from("<"),
+                start_position..self.position
+            )),
+            Some('-') => {
+                if self.chars.next_if_eq(&'>').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        Arrow,
+                        String::from("->"),
+                        start_position..self.position
+                    ))
+                } else {
+                    Some(make_token!(
+                        Minus,
+                        String::from("-"),
+                        start_position..self.position
+                    ))
+                }
+            }
+            Some('|') => {
+                if self.chars.next_if_eq(&'|').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        PipePipe,
+                        String::from("||"),
+                        start_position..self.position
+                    ))
+                } else {
+                    Some(make_token!(
+                        Pipe,
+                        String::from("|"),
+                        start_position..self.position
+                    ))
+                }
+            }
+            Some('+') => Some(make_token!(
+                Plus,
+                String::from("+"),
+                start_position..self.position
+            )),
+            Some('?') => {
+                if self.chars.next_if_eq(&'?').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        QuestionQuestion,
+                        String::from("??"),
+                        start_position..self.position
+                    ))
+                } else if self.chars.next_if_eq(&'.').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        QuestionDot,
+                        String::from("?."),
+                        start_position..self.position
+                    ))
+                } else if self.chars.next_if_eq(&'[').is_some() {
+                    self.position += 1;
+                    Some(make_token!(
+                        QuestionLeftBracket,
+                        String::from("?["),
+                        start_position..self.position
+                    ))
+                } else {
+                    Some(make_token!(
+                        Question,
+                        String::from("?"),
+                        start_position..self.position
+                    ))
+                }
+            }
             Some(')') => Some(make_token!(
                 RightParen,
                 String::from(")"),
                 start_position..self.position
             )),
+            Some(']') => Some(make_token!(
+                RightBracket,
+                String::from("]"),
+                start_position..self.position
+            )),
             Some('}') => Some(make_token!(
                 RightBrace,
                 String::from("}"),
@@ -81,7 +251,36 @@ impl<'a> Iterator for Lexer<'a> {
                 String::from("/"),
                 start_position..self.position
             )),
-            Some(c) if c.is_ascii_alphabetic() => {
+            Some('"') => {
+                let mut value = String::new();
+                loop {
+                    match self.read_char() {
+                        Some('"') => {
+                            break Some(make_token!(
+                                StringLiteral,
+                                value,
+                                start_position..self.position
+                            ))
+                        }
+                        Some('\\') => match self.read_char() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => continue,
+                        },
+                        Some(c) => value.push(c),
+                        // Unterminated string literals are reported as unknown tokens
+                        None => {
+                            break Some(make_token!(
+                                Unknown,
+                                format!("\"{}", value),
+                                start_position..self.position
+                            ))
+                        }
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
                 let str = self.continue_while(c, |c| c.is_ascii_alphanumeric() || *c == '_');
                 let ident = str.into_iter().collect::<String>();
 
@@ -115,7 +314,7 @@ impl<'a> Iterator for Lexer<'a> {
                 String::from(c),
                 start_position..self.position
             )),
-            None => return None,
+            None => None,
         }
     }
 }
@@ -129,7 +328,7 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    pub(crate) fn lex(&mut self, input: &'a str) -> () {
+    pub(crate) fn lex(&mut self, input: &'a str) {
         self.tokens.clear();
         self.chars = input.chars().peekable();
         while let Some(token) = self.next() {
@@ -137,9 +336,21 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn skip_whitespace(&mut self) -> () {
-        while let Some(_) = self.chars.next_if(|c| c.is_whitespace()) {
-            self.position += 1;
+    /// Skips whitespace and `//` line comments, which never become tokens.
+    fn skip_whitespace(&mut self) {
+        loop {
+            while self.chars.next_if(|c| c.is_whitespace()).is_some() {
+                self.position += 1;
+            }
+
+            let mut ahead = self.chars.clone();
+            if ahead.next() != Some('/') || ahead.next() != Some('/') {
+                break;
+            }
+
+            while self.chars.next_if(|c| *c != '\n').is_some() {
+                self.position += 1;
+            }
         }
     }
 
@@ -161,8 +372,8 @@ impl<'a> Lexer<'a> {
         v
     }
 
-    pub(crate) fn get_tokens_peekable(&mut self) -> TokenStream {
-        self.tokens.iter_mut().peekable()
+    pub(crate) fn get_tokens_peekable(&self) -> TokenStream<'_> {
+        TokenStream::new(&self.tokens)
     }
 }
 
@@ -199,4 +410,72 @@ mod tests {
         assert_eq!(lexer.tokens[4].value, ";");
         assert_eq!(lexer.tokens[4].span, 9..10);
     }
+
+    #[test]
+    fn test_comments() {
+        before_each();
+        let input = "// leading\nlet x = 5 / 2; // trailing\n//";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+
+        let types: Vec<TokenType> = lexer.tokens.iter().map(|token| token.type_).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Let,
+                TokenType::Identifier,
+                TokenType::Equals,
+                TokenType::IntegerLiteral,
+                TokenType::Slash,
+                TokenType::IntegerLiteral,
+                TokenType::Semicolon,
+            ]
+        );
+        assert_eq!(lexer.tokens[0].span, 11..14);
+    }
+
+    #[test]
+    fn test_contextual_keywords() {
+        before_each();
+        let mut lexer = Lexer::new();
+        lexer.lex("import { a } from \"a.lit\"; let from = 1;");
+
+        // `from` is only special inside imports, so it lexes as a name
+        let from: Vec<TokenType> = lexer
+            .tokens
+            .iter()
+            .filter(|token| token.value == "from")
+            .map(|token| token.type_)
+            .collect();
+        assert_eq!(from, vec![TokenType::Identifier, TokenType::Identifier]);
+    }
+
+    #[test]
+    fn test_match_tokens() {
+        before_each();
+        let mut lexer = Lexer::new();
+        lexer.lex("match s { _ => 1, _rest => 2 }");
+
+        let tokens: Vec<(TokenType, &str)> = lexer
+            .tokens
+            .iter()
+            .map(|token| (token.type_, token.value.as_str()))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenType::Match, "match"),
+                (TokenType::Identifier, "s"),
+                (TokenType::LeftBrace, "{"),
+                (TokenType::Identifier, "_"),
+                (TokenType::FatArrow, "=>"),
+                (TokenType::IntegerLiteral, "1"),
+                (TokenType::Comma, ","),
+                (TokenType::Identifier, "_rest"),
+                (TokenType::FatArrow, "=>"),
+                (TokenType::IntegerLiteral, "2"),
+                (TokenType::RightBrace, "}"),
+            ]
+        );
+    }
 }
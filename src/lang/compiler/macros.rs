@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+
+use crate::lang::util::{suggest, vec::UnshiftExpect};
+
+use super::{
+    token::{Span, Token, TokenStream, TokenType},
+    CompilerError, CompilerResult, ErrorCode,
+};
+
+/// How deeply macro calls may expand into further macro calls before expansion gives up.
+const MAX_DEPTH: usize = 64;
+
+/// A declarative macro, `macro name(a, b) { ... }`, whose body is pasted in place of each
+/// `name!(x, y)` call with the parameters replaced by the arguments.
+#[derive(Debug, Clone)]
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+    /// The source between the braces of the body.
+    body_span: Span,
+}
+
+/// A single expansion of a macro call.
+///
+/// Tokens pasted from the macro body get spans in a virtual range past the end of the source,
+/// starting at `offset`, so errors in expanded code can be traced back with [`Expansion::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Expansion {
+    pub(crate) name: String,
+    /// The `name!(...)` call, which is itself virtual if it came from another expansion.
+    pub(crate) call_span: Span,
+    /// The body of the macro definition in the source.
+    pub(crate) definition: Span,
+    pub(crate) offset: usize,
+}
+
+impl Expansion {
+    fn contains(&self, position: usize) -> bool {
+        (self.offset..=self.offset + self.definition.len()).contains(&position)
+    }
+
+    /// Maps a span that may lie in expanded code back to the source. Also returns the macro
+    /// definitions the span was expanded from, innermost first.
+    pub(crate) fn resolve(expansions: &[Expansion], span: &Span) -> (Span, Vec<(String, Span)>) {
+        let mut span = span.clone();
+        let mut traces = Vec::new();
+
+        // Call spans always lie in an earlier expansion or in the source, so this ends
+        loop {
+            let start = expansions
+                .iter()
+                .find(|expansion| expansion.contains(span.start));
+            let end = expansions
+                .iter()
+                .find(|expansion| expansion.contains(span.end));
+
+            span = match (start, end) {
+                (None, None) => return (span, traces),
+                (Some(start), Some(end)) if start == end => {
+                    let base = start.definition.start;
+                    traces.push((
+                        start.name.clone(),
+                        base + span.start - start.offset..base + span.end - start.offset,
+                    ));
+                    start.call_span.clone()
+                }
+                // A span that covers both expanded code and the code around the call
+                (start, end) => {
+                    for expansion in start.iter().chain(end.iter()) {
+                        traces.push((expansion.name.clone(), expansion.definition.clone()));
+                    }
+                    start.map_or(span.start, |start| start.call_span.start)
+                        ..end.map_or(span.end, |end| end.call_span.end)
+                }
+            };
+        }
+    }
+}
+
+pub(crate) struct MacroExpander {
+    macros: HashMap<String, Macro>,
+    expansions: Vec<Expansion>,
+    next_offset: usize,
+}
+
+impl MacroExpander {
+    /// Removes the macro definitions from `tokens` and expands every `name!(...)` call.
+    ///
+    /// Macros can be called before they are defined. `source_length` is where the virtual
+    /// spans of expanded tokens start.
+    pub(crate) fn expand(
+        tokens: &mut TokenStream,
+        source_length: usize,
+    ) -> CompilerResult<(Vec<Token>, Vec<Expansion>)> {
+        let mut expander = MacroExpander {
+            macros: HashMap::new(),
+            expansions: Vec::new(),
+            next_offset: source_length + 1,
+        };
+
+        let mut rest = Vec::new();
+        while let Some(token) = tokens.peek() {
+            if token.type_ == TokenType::Macro {
+                expander.definition(tokens)?;
+            } else {
+                rest.extend(tokens.next().cloned());
+            }
+        }
+
+        let expanded = expander.tokens(&rest, 0)?;
+        Ok((expanded, expander.expansions))
+    }
+
+    fn definition(&mut self, tokens: &mut TokenStream) -> CompilerResult<()> {
+        tokens.unshift_expect(TokenType::Macro)?;
+        let name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        tokens.unshift_expect(TokenType::LeftParen)?;
+
+        let mut parameters = Vec::new();
+        while !tokens.next_matches(TokenType::RightParen) {
+            parameters.push(tokens.unshift_expect(TokenType::Identifier)?.value.clone());
+
+            if !tokens.next_matches(TokenType::RightParen) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
+        }
+        tokens.unshift_expect(TokenType::RightParen)?;
+
+        let open = tokens.unshift_expect(TokenType::LeftBrace)?.clone();
+        let (body, close) = MacroExpander::group(tokens, &open)?;
+
+        if self.macros.contains_key(&name.value) {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::DuplicateMacro,
+                error_message: format!("Macro '{}' is defined more than once", name.value),
+                span_message: String::from("This macro was already defined"),
+                token: name,
+                help: Some(String::from("Rename one of the macros")),
+                info: None,
+            }));
+        }
+
+        self.macros.insert(
+            name.value,
+            Macro {
+                parameters,
+                body,
+                body_span: open.span.end..close.span.start,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn tokens(&mut self, tokens: &[Token], depth: usize) -> CompilerResult<Vec<Token>> {
+        let mut output = Vec::new();
+        let mut stream = TokenStream::new(tokens);
+
+        while let Some(token) = stream.next() {
+            if token.type_ == TokenType::Identifier
+                && stream.next_matches(TokenType::Bang)
+                && stream.nth_matches(1, TokenType::LeftParen)
+            {
+                let expanded = self.call(token, &mut stream, depth)?;
+
+                // `name!(...);` is fine when the expansion already ends with a statement
+                if expanded
+                    .last()
+                    .is_some_and(|last| last.type_ == TokenType::Semicolon)
+                {
+                    stream.unshift_if(TokenType::Semicolon);
+                }
+                output.extend(expanded);
+            } else {
+                output.push(token.clone());
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn call(
+        &mut self,
+        name: &Token,
+        tokens: &mut TokenStream,
+        depth: usize,
+    ) -> CompilerResult<Vec<Token>> {
+        tokens.unshift_expect(TokenType::Bang)?;
+        let open = tokens.unshift_expect(TokenType::LeftParen)?.clone();
+        let (inner, close) = MacroExpander::group(tokens, &open)?;
+        let call_span = name.span.start..close.span.end;
+        let call = Token {
+            span: call_span.clone(),
+            ..name.clone()
+        };
+
+        let Some(macro_) = self.macros.get(&name.value).cloned() else {
+            let help = suggest::closest(&name.value, self.macros.keys().map(String::as_str))
+                .map(|suggestion| format!("Did you mean `{}!`?", suggestion));
+
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::UnknownMacro,
+                error_message: format!("Unknown macro: '{}!'", name.value),
+                span_message: String::from("No macro with this name is defined"),
+                token: call,
+                help,
+                info: None,
+            }));
+        };
+
+        let arguments = MacroExpander::arguments(&inner);
+        if arguments.len() != macro_.parameters.len() {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::WrongArgumentCount,
+                error_message: format!(
+                    "'{}!' expects {} argument(s), got {}",
+                    name.value,
+                    macro_.parameters.len(),
+                    arguments.len()
+                ),
+                span_message: String::from("Called with the wrong number of arguments"),
+                token: call,
+                help: Some(format!(
+                    "The macro is defined as `macro {}({})`",
+                    name.value,
+                    macro_.parameters.join(", ")
+                )),
+                info: None,
+            }));
+        }
+
+        if depth >= MAX_DEPTH {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::MacroRecursion,
+                error_message: format!(
+                    "Expanding '{}!' nests more than {} macro calls deep",
+                    name.value, MAX_DEPTH
+                ),
+                span_message: String::from("This call keeps expanding into itself"),
+                token: call,
+                help: Some(String::from(
+                    "Make sure the macro doesn't call itself unconditionally",
+                )),
+                info: None,
+            }));
+        }
+
+        let index = self.expansions.len();
+        let offset = self.next_offset;
+        self.next_offset += macro_.body_span.len() + 1;
+        self.expansions.push(Expansion {
+            name: name.value.clone(),
+            call_span,
+            definition: macro_.body_span.clone(),
+            offset,
+        });
+
+        let hygienic = MacroExpander::bound_names(&macro_);
+        let mut expanded = Vec::new();
+
+        for token in &macro_.body {
+            if token.type_ == TokenType::Identifier {
+                if let Some(position) = macro_.parameters.iter().position(|p| *p == token.value) {
+                    expanded.extend(MacroExpander::argument(&arguments[position]));
+                    continue;
+                }
+            }
+
+            let mut token = token.clone();
+            token.span = offset + token.span.start - macro_.body_span.start
+                ..offset + token.span.end - macro_.body_span.start;
+
+            // Names bound inside the body can't clash with names at the call site
+            if token.type_ == TokenType::Identifier && hygienic.contains(&token.value) {
+                token.value = format!("{}#{}", token.value, index);
+            }
+
+            expanded.push(token);
+        }
+
+        self.tokens(&expanded, depth + 1)
+    }
+
+    /// The tokens up to the bracket that closes `open`, and that closing bracket.
+    fn group(tokens: &mut TokenStream, open: &Token) -> CompilerResult<(Vec<Token>, Token)> {
+        let mut inner = Vec::new();
+        let mut depth = 0;
+
+        for token in tokens.by_ref() {
+            match token.type_ {
+                TokenType::LeftParen
+                | TokenType::LeftBracket
+                | TokenType::LeftBrace
+                | TokenType::QuestionLeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+                    if depth == 0 =>
+                {
+                    return Ok((inner, token.clone()));
+                }
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth -= 1
+                }
+                _ => {}
+            }
+
+            inner.push(token.clone());
+        }
+
+        Err(Box::new(CompilerError {
+            error_code: ErrorCode::NoTokensLeft,
+            error_message: format!("'{}' is never closed", open.value),
+            span_message: String::from("This bracket has no closing bracket"),
+            token: open.clone(),
+            help: None,
+            info: None,
+        }))
+    }
+
+    /// Splits the tokens between the parentheses of a call at the commas outside brackets.
+    fn arguments(tokens: &[Token]) -> Vec<Vec<Token>> {
+        let mut arguments = vec![Vec::new()];
+        let mut depth = 0;
+
+        for token in tokens {
+            match token.type_ {
+                TokenType::Comma if depth == 0 => {
+                    arguments.push(Vec::new());
+                    continue;
+                }
+                TokenType::LeftParen
+                | TokenType::LeftBracket
+                | TokenType::LeftBrace
+                | TokenType::QuestionLeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth -= 1
+                }
+                _ => {}
+            }
+
+            if let Some(argument) = arguments.last_mut() {
+                argument.push(token.clone());
+            }
+        }
+
+        // `name!()` has no arguments, and `name!(a, b,)` has two
+        if arguments.last().is_some_and(Vec::is_empty) {
+            arguments.pop();
+        }
+
+        arguments
+    }
+
+    /// The tokens an argument is pasted as. Expressions are parenthesized so they keep their
+    /// meaning next to operators in the body, as in `!cond` with `a == b`.
+    fn argument(tokens: &[Token]) -> Vec<Token> {
+        let parenthesize = tokens.len() > 1
+            && tokens[0].type_ != TokenType::LeftBrace
+            && !tokens
+                .iter()
+                .any(|token| token.type_ == TokenType::Semicolon);
+
+        if !parenthesize {
+            return tokens.to_vec();
+        }
+
+        let (start, end) = (tokens[0].span.start, tokens[tokens.len() - 1].span.end);
+        let mut argument = vec![Token {
+            type_: TokenType::LeftParen,
+            value: String::from("("),
+            span: start..start,
+        }];
+        argument.extend_from_slice(tokens);
+        argument.push(Token {
+            type_: TokenType::RightParen,
+            value: String::from(")"),
+            span: end..end,
+        });
+        argument
+    }
+
+    /// The names a macro body binds with `let`, `const` or `for`, other than its parameters.
+    fn bound_names(macro_: &Macro) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut tokens = TokenStream::new(&macro_.body);
+
+        while let Some(token) = tokens.next() {
+            if !matches!(
+                token.type_,
+                TokenType::Let | TokenType::Const | TokenType::For
+            ) {
+                continue;
+            }
+
+            tokens.unshift_if(TokenType::Mut);
+            if let Some(name) = tokens.unshift_if(TokenType::Identifier) {
+                if !macro_.parameters.contains(&name.value) && !names.contains(&name.value) {
+                    names.push(name.value.clone());
+                }
+            }
+        }
+
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::lexer::Lexer;
+    use crate::tests::before_each;
+
+    fn expand(input: &str) -> CompilerResult<(Vec<Token>, Vec<Expansion>)> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        MacroExpander::expand(&mut lexer.get_tokens_peekable(), input.len())
+    }
+
+    fn values(tokens: &[Token]) -> Vec<&str> {
+        tokens.iter().map(|token| token.value.as_str()).collect()
+    }
+
+    #[test]
+    fn test_expansion() {
+        before_each();
+        let input = "unless!(a == b, { go(); }); macro unless(cond, body) { if !cond body }";
+        let (tokens, expansions) = expand(input).unwrap_or_else(|err| {
+            panic!("Failed to expand: {}", err.error_message);
+        });
+
+        assert_eq!(
+            values(&tokens),
+            vec!["if", "!", "(", "a", "==", "b", ")", "{", "go", "(", ")", ";", "}", ";"]
+        );
+        assert_eq!(expansions.len(), 1);
+        assert_eq!(expansions[0].call_span, 0..26);
+        assert_eq!(expansions[0].definition, 54..69);
+
+        // `if` comes from the definition, `a` from the call site
+        let (span, traces) = Expansion::resolve(&expansions, &tokens[0].span);
+        assert_eq!(span, 0..26);
+        assert_eq!(traces, vec![(String::from("unless"), 55..57)]);
+        assert_eq!(Expansion::resolve(&expansions, &tokens[3].span).0, 8..9);
+    }
+
+    #[test]
+    fn test_hygiene() {
+        before_each();
+        let input = "macro twice(x) { let tmp = x; tmp + tmp } let tmp = 1; twice!(tmp);";
+        let (tokens, _) = expand(input).unwrap_or_else(|err| {
+            panic!("Failed to expand: {}", err.error_message);
+        });
+
+        assert_eq!(
+            values(&tokens),
+            vec![
+                "let", "tmp", "=", "1", ";", "let", "tmp#0", "=", "tmp", ";", "tmp#0", "+",
+                "tmp#0", ";"
+            ]
+        );
+
+        let input = "macro init(name) { let mut name = 0; } init!(count); count = 1;";
+        let (tokens, _) = expand(input).unwrap_or_else(|err| {
+            panic!("Failed to expand: {}", err.error_message);
+        });
+
+        // Parameters are the caller's names, so they aren't renamed
+        assert_eq!(
+            values(&tokens),
+            vec!["let", "mut", "count", "=", "0", ";", "count", "=", "1", ";"]
+        );
+    }
+
+    #[test]
+    fn test_expansion_errors() {
+        before_each();
+        let error = |input: &str| expand(input).map(|_| ()).unwrap_err().error_code;
+
+        assert_eq!(error("unles!(x)"), ErrorCode::UnknownMacro);
+        assert_eq!(
+            error("macro m(a) { a } m!(1, 2);"),
+            ErrorCode::WrongArgumentCount
+        );
+        assert_eq!(
+            error("macro m(a) { a } macro m(b) { b }"),
+            ErrorCode::DuplicateMacro
+        );
+        assert_eq!(error("macro m() { m!() } m!();"), ErrorCode::MacroRecursion);
+        assert_eq!(error("macro m() { m!() "), ErrorCode::NoTokensLeft);
+    }
+}
@@ -2,17 +2,35 @@ use crate::lang::compiler::token::TokenType;
 use core::fmt::Display;
 use log::trace;
 
-use self::{parser::{AST, Parser}, token::Token};
+use std::{collections::HashMap, path::Path};
+
+use self::{cfg::Cfg, checker::Checker, include::IncludeExpander, intrinsic::IntrinsicRegistry, macros::MacroExpander, module::{Module, ModuleLoader}, node::{NodeId, NodeIds}, parser::{AST, Parser}, resolver::{Declaration, Resolver}, token::{Token, TokenStream}};
 
 use super::util::error_logger::ErrorLogger;
 
+pub(crate) mod attribute;
+pub(crate) mod cfg;
+pub(crate) mod checker;
+pub(crate) mod cst;
+pub(crate) mod exhaustiveness;
 pub(crate) mod expression;
+pub(crate) mod fold;
+pub(crate) mod include;
+pub(crate) mod intrinsic;
 pub(crate) mod lexer;
+pub(crate) mod macros;
+pub(crate) mod module;
+pub(crate) mod node;
 pub(crate) mod parser;
+pub(crate) mod pattern;
+pub(crate) mod resolver;
+pub(crate) mod schema;
 pub(crate) mod statement;
 pub(crate) mod token;
+pub(crate) mod types;
+pub(crate) mod visit;
 
-pub(crate) type CompilerResult<T> = Result<T, CompilerError>;
+pub(crate) type CompilerResult<T> = Result<T, Box<CompilerError>>;
 pub(crate) struct CompilerError {
     pub(crate) error_code: ErrorCode,
     pub(crate) error_message: String,
@@ -22,14 +40,64 @@ pub(crate) struct CompilerError {
     pub(crate) info: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum ErrorCode {
+    AssignmentToImmutable,
+    DuplicateArgument,
+    DuplicateEnumDeclaration,
+    DuplicateEnumVariant,
+    DuplicateFunction,
+    DuplicateMacro,
+    DuplicateRecordKey,
+    DuplicateStructDeclaration,
+    DuplicateStructField,
+    DuplicateTypeParameter,
+    ImportCycle,
+    IncludeNotFound,
+    InvalidAssignmentTarget,
+    InvalidArgumentType,
+    InvalidAttributeTarget,
+    InvalidCfg,
+    InvalidExport,
     InvalidExpression,
+    InvalidInclude,
+    InvalidParameterOrder,
     InvalidParameterType,
+    InvalidPattern,
+    InvalidPropagation,
+    InvalidRange,
     InvalidReturnType,
+    InvalidYield,
+    MacroRecursion,
+    MalformedType,
+    MissingArgument,
+    MissingInitializer,
+    MissingStructField,
+    ModuleNotFound,
     NoTokensLeft,
+    NonConstantExpression,
+    NonExhaustiveMatch,
+    NullableValue,
+    PositionalAfterNamed,
+    PrivateImport,
+    UndefinedName,
     UnexpectedToken,
+    UnknownArgument,
+    UnknownStruct,
+    UnknownStructField,
+    UnknownAttribute,
+    UnknownConstraint,
+    UnknownEnumVariant,
+    UnknownIntrinsic,
+    UnknownMacro,
     UnknownToken,
+    UnreachableMatchArm,
+    UnresolvedImport,
+    UnsatisfiedConstraint,
     UnshiftedUnexpectedToken,
+    UseBeforeDeclaration,
+    WrongArgumentCount,
+    WrongTypeArgumentCount,
 }
 
 impl Display for ErrorCode {
@@ -42,14 +110,81 @@ impl Display for ErrorCode {
             ErrorCode::InvalidParameterType => write!(f, "Invalid parameter type"),
             ErrorCode::InvalidReturnType => write!(f, "Invalid return type"),
             ErrorCode::InvalidExpression => write!(f, "Invalid expression"),
+            ErrorCode::DuplicateRecordKey => write!(f, "Duplicate record key"),
+            ErrorCode::DuplicateStructDeclaration => write!(f, "Duplicate struct declaration"),
+            ErrorCode::DuplicateStructField => write!(f, "Duplicate struct field"),
+            ErrorCode::MissingStructField => write!(f, "Missing struct field"),
+            ErrorCode::UnknownStruct => write!(f, "Unknown struct"),
+            ErrorCode::UnknownStructField => write!(f, "Unknown struct field"),
+            ErrorCode::AssignmentToImmutable => write!(f, "Assignment to immutable binding"),
+            ErrorCode::MissingInitializer => write!(f, "Missing initializer"),
+            ErrorCode::InvalidAssignmentTarget => write!(f, "Invalid assignment target"),
+            ErrorCode::NonConstantExpression => write!(f, "Non-constant expression"),
+            ErrorCode::MalformedType => write!(f, "Malformed type"),
+            ErrorCode::WrongTypeArgumentCount => write!(f, "Wrong number of type arguments"),
+            ErrorCode::ImportCycle => write!(f, "Import cycle"),
+            ErrorCode::InvalidExport => write!(f, "Invalid export"),
+            ErrorCode::ModuleNotFound => write!(f, "Module not found"),
+            ErrorCode::PrivateImport => write!(f, "Private import"),
+            ErrorCode::UnresolvedImport => write!(f, "Unresolved import"),
+            ErrorCode::InvalidAttributeTarget => write!(f, "Invalid attribute target"),
+            ErrorCode::UnknownAttribute => write!(f, "Unknown attribute"),
+            ErrorCode::UnknownIntrinsic => write!(f, "Unknown intrinsic"),
+            ErrorCode::WrongArgumentCount => write!(f, "Wrong number of arguments"),
+            ErrorCode::InvalidArgumentType => write!(f, "Invalid argument type"),
+            ErrorCode::InvalidRange => write!(f, "Invalid range"),
+            ErrorCode::NullableValue => write!(f, "Possibly null value"),
+            ErrorCode::InvalidPropagation => write!(f, "Invalid use of `?`"),
+            ErrorCode::InvalidParameterOrder => write!(f, "Invalid parameter order"),
+            ErrorCode::DuplicateArgument => write!(f, "Duplicate argument"),
+            ErrorCode::MissingArgument => write!(f, "Missing argument"),
+            ErrorCode::UnknownArgument => write!(f, "Unknown argument"),
+            ErrorCode::PositionalAfterNamed => write!(f, "Positional argument after named argument"),
+            ErrorCode::DuplicateTypeParameter => write!(f, "Duplicate type parameter"),
+            ErrorCode::UnknownConstraint => write!(f, "Unknown constraint"),
+            ErrorCode::UnsatisfiedConstraint => write!(f, "Unsatisfied constraint"),
+            ErrorCode::InvalidYield => write!(f, "Invalid use of `yield`"),
+            ErrorCode::DuplicateMacro => write!(f, "Duplicate macro"),
+            ErrorCode::UnknownMacro => write!(f, "Unknown macro"),
+            ErrorCode::MacroRecursion => write!(f, "Macro recursion limit reached"),
+            ErrorCode::InvalidCfg => write!(f, "Invalid cfg predicate"),
+            ErrorCode::IncludeNotFound => write!(f, "Included file not found"),
+            ErrorCode::InvalidInclude => write!(f, "Invalid include"),
+            ErrorCode::UndefinedName => write!(f, "Undefined name"),
+            ErrorCode::UseBeforeDeclaration => write!(f, "Use before declaration"),
+            ErrorCode::DuplicateFunction => write!(f, "Duplicate function"),
+            ErrorCode::DuplicateEnumDeclaration => write!(f, "Duplicate enum declaration"),
+            ErrorCode::DuplicateEnumVariant => write!(f, "Duplicate enum variant"),
+            ErrorCode::UnknownEnumVariant => write!(f, "Unknown enum variant"),
+            ErrorCode::InvalidPattern => write!(f, "Invalid pattern"),
+            ErrorCode::NonExhaustiveMatch => write!(f, "Non-exhaustive match"),
+            ErrorCode::UnreachableMatchArm => write!(f, "Unreachable match arm"),
         }
     }
 }
 
+impl ErrorCode {
+    /// Warnings are reported like errors, but don't stop compilation.
+    pub(crate) fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::UnknownAttribute | ErrorCode::UnreachableMatchArm
+        )
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Compiler<'a> {
     pub(crate) input: &'a str,
     pub(crate) filename: &'a str,
+    /// Every module imported by the compiled file, directly or indirectly, in dependency order.
+    pub(crate) modules: Vec<Module>,
+    /// The declaration each identifier of the compiled file refers to, by expression id.
+    pub(crate) declarations: HashMap<NodeId, Declaration>,
+    /// The intrinsics `@name(...)` calls are checked against. Embedders can register their own.
+    pub(crate) intrinsics: IntrinsicRegistry,
+    /// The `--cfg` options that `#[cfg(...)]` attributes are checked against.
+    pub(crate) cfg: Cfg,
     lexer: lexer::Lexer<'a>,
     error_logger: ErrorLogger<'a>,
 }
@@ -60,51 +195,179 @@ impl Compiler<'_> {
         Compiler {
             input,
             filename,
+            modules: Vec::new(),
+            declarations: HashMap::new(),
+            intrinsics: IntrinsicRegistry::default(),
+            cfg: Cfg::default(),
             lexer: lexer::Lexer::new(),
             error_logger,
         }
     }
 
     pub(crate) fn compile(&mut self) -> Result<AST, String> {
-        self.lexer.lex(self.input);
+        let ast = Compiler::parse_source(
+            &mut self.lexer,
+            self.input,
+            &self.error_logger,
+            &mut NodeIds::default(),
+        )?;
+
+        self.compile_ast(ast)
+    }
+
+    /// Checks an AST that was already parsed, or built by another tool, along with its imports.
+    /// Imports and includes are resolved relative to `filename`.
+    pub(crate) fn compile_ast(&mut self, mut ast: AST) -> Result<AST, String> {
+        let entry = Path::new(self.filename);
+        self.error_logger = self.error_logger.clone().with_expansions(ast.expansions.clone());
+
+        let errors = self.cfg.strip(&mut ast);
+        if !errors.is_empty() {
+            self.error_logger.report_many(&errors);
+            return Err(String::from("Error evaluating cfg attributes"));
+        }
+
+        // Included literals and modules are numbered after the entry, so ids are unique
+        // across the compilation
+        let mut ids = NodeIds::after(&ast);
+
+        let (ast, errors) = IncludeExpander::expand(ast, directory(entry), &mut ids);
+        if !errors.is_empty() {
+            self.error_logger.report_many(&errors);
+            return Err(String::from("Error including files"));
+        }
+
+        let mut loader = ModuleLoader::new(entry);
+
+        let errors = loader.load_imports(entry, &ast, &mut ids);
+        if !errors.is_empty() {
+            self.error_logger.report_many(&errors);
+        }
+
+        if !errors.is_empty() || loader.failed {
+            return Err(String::from("Error loading imported modules"));
+        }
+
+        let mut error_count = 0;
+
+        for module in &mut loader.modules {
+            let mut errors = self.cfg.strip(&mut module.ast);
+            if errors.is_empty() {
+                let ast = std::mem::take(&mut module.ast);
+                let (ast, include_errors) =
+                    IncludeExpander::expand(ast, directory(&module.path), &mut ids);
+                module.ast = ast;
+                errors.extend(include_errors);
+            }
 
-        let unknown_tokens: Vec<&mut Token> = self
-            .lexer
+            if !errors.is_empty() {
+                ErrorLogger::new(&module.name, &module.source)
+                    .with_expansions(module.ast.expansions.clone())
+                    .report_many(&errors);
+                error_count += count_errors(&errors);
+            }
+        }
+
+        if error_count > 0 {
+            return Err(String::from("Error evaluating cfg attributes or includes"));
+        }
+
+        for module in &loader.modules {
+            let error_logger = ErrorLogger::new(&module.name, &module.source)
+                .with_expansions(module.ast.expansions.clone());
+            let (imports, mut errors) = loader.imports(&module.path, &module.ast);
+            errors.extend(Resolver::resolve(&module.ast, &imports).1);
+            errors.extend(Checker::check(&module.ast, &imports, &self.intrinsics));
+
+            error_logger.report_many(&errors);
+            error_count += count_errors(&errors);
+        }
+
+        let (imports, mut errors) = loader.imports(entry, &ast);
+        let (declarations, resolve_errors) = Resolver::resolve(&ast, &imports);
+        errors.extend(resolve_errors);
+        errors.extend(Checker::check(&ast, &imports, &self.intrinsics));
+
+        self.error_logger.report_many(&errors);
+        error_count += count_errors(&errors);
+
+        if error_count > 0 {
+            return Err(format!("Found {} error(s) while checking", error_count));
+        }
+
+        self.modules = loader.modules;
+        self.declarations = declarations;
+
+        Ok(ast)
+    }
+
+    /// Lexes a single file, expands its macros and parses it, reporting any errors against it.
+    /// Nodes are numbered with `ids`.
+    pub(crate) fn parse_source<'s>(
+        lexer: &mut lexer::Lexer<'s>,
+        input: &'s str,
+        error_logger: &ErrorLogger,
+        ids: &mut NodeIds,
+    ) -> Result<AST, String> {
+        lexer.lex(input);
+
+        let unknown_tokens: Vec<&Token> = lexer
             .get_tokens_peekable()
             .filter(|token| token.type_ == TokenType::Unknown)
             .collect();
 
-        if unknown_tokens.len() > 0 {
+        if !unknown_tokens.is_empty() {
             let errors: Vec<CompilerError> = unknown_tokens
                 .iter()
                 .map(|token| CompilerError {
                     error_code: ErrorCode::UnknownToken,
                     error_message: format!("Unknown token: {:?}", token.type_), // Clone the token object
                     token: (**token).clone(),
-                    span_message: format!("This token is unknown to the compiler"),
+                    span_message: String::from("This token is unknown to the compiler"),
                     help: None,
                     info: None,
                 })
                 .collect();
 
-            self.error_logger.report_many(&errors);
+            error_logger.report_many(&errors);
         }
 
         if log::max_level() >= log::LevelFilter::Trace {
             trace!("{:#04}..{:#04} {}", "Byte", "Rnge", "Token");
 
             trace!("{:-<1$}", "", 40);
-            for token in self.lexer.get_tokens_peekable() {
+            for token in lexer.get_tokens_peekable() {
                 trace!("{}", token);
             }
         }
 
-        let mut token_stream = self.lexer.get_tokens_peekable();
-        let ast = Parser::parse(&mut token_stream).map_err(|e| {
-            self.error_logger.report(&e);
-            return format!("Error parsing tokens: {}", e.error_message);
-        });
+        let (tokens, expansions) =
+            MacroExpander::expand(&mut lexer.get_tokens_peekable(), input.len()).map_err(|e| {
+                error_logger.report(&e);
+                format!("Error expanding macros: {}", e.error_message)
+            })?;
+        let error_logger = error_logger.clone().with_expansions(expansions.clone());
 
-        ast
+        let mut token_stream = TokenStream::new(&tokens);
+        let mut ast = Parser::parse(&mut token_stream, ids).map_err(|e| {
+            error_logger.report(&e);
+            format!("Error parsing tokens: {}", e.error_message)
+        })?;
+
+        ast.expansions = expansions;
+        Ok(ast)
     }
 }
+
+/// The directory the imports and includes of a file are resolved against.
+fn directory(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Counts the diagnostics that should fail compilation, ignoring warnings.
+fn count_errors(errors: &[CompilerError]) -> usize {
+    errors
+        .iter()
+        .filter(|error| !error.error_code.is_warning())
+        .count()
+}
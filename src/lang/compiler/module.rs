@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::lang::util::error_logger::ErrorLogger;
+
+use super::{
+    lexer::Lexer,
+    node::NodeIds,
+    parser::AST,
+    statement::{ImportStatement, StatementType},
+    token::{Span, Token, TokenType},
+    Compiler, CompilerError, ErrorCode,
+};
+
+/// A `.lit` file that was loaded through an `import` statement.
+#[derive(Debug, Clone)]
+pub(crate) struct Module {
+    /// The path as it is shown in error messages.
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) source: String,
+    pub(crate) ast: AST,
+}
+
+/// Loads every module reachable from a file's imports, in dependency order.
+///
+/// Errors in an imported file are reported against that file as soon as it is
+/// parsed. Errors in an `import` statement itself, such as a missing file or
+/// an import cycle, are returned so they can be reported against the importer.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ModuleLoader {
+    pub(crate) modules: Vec<Module>,
+    /// Files that are currently being loaded, used to detect import cycles.
+    stack: Vec<PathBuf>,
+    /// Files that failed to parse. Their errors were already reported, so they aren't parsed again.
+    unparsable: Vec<PathBuf>,
+    /// Set when an imported file failed to parse, after its errors were reported.
+    pub(crate) failed: bool,
+}
+
+impl ModuleLoader {
+    pub(crate) fn new(entry: &Path) -> ModuleLoader {
+        ModuleLoader {
+            stack: vec![canonical(entry)],
+            ..ModuleLoader::default()
+        }
+    }
+
+    /// Resolves an import path relative to the directory of the importing file.
+    pub(crate) fn resolve(importer: &Path, import: &str) -> PathBuf {
+        canonical(&importer.parent().unwrap_or(Path::new("")).join(import))
+    }
+
+    pub(crate) fn module(&self, path: &Path) -> Option<&Module> {
+        self.modules.iter().find(|module| module.path == path)
+    }
+
+    /// Loads the modules imported by `ast`, which was parsed from `importer`, numbering
+    /// their nodes with `ids`.
+    pub(crate) fn load_imports(
+        &mut self,
+        importer: &Path,
+        ast: &AST,
+        ids: &mut NodeIds,
+    ) -> Vec<CompilerError> {
+        let mut errors = Vec::new();
+
+        for statement in &ast.statements {
+            let StatementType::Import(import) = &statement.kind else {
+                continue;
+            };
+
+            let path = ModuleLoader::resolve(importer, &import.path);
+
+            if let Some(position) = self.stack.iter().position(|loading| *loading == path) {
+                let cycle = self.stack[position..]
+                    .iter()
+                    .chain(std::iter::once(&path))
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                errors.push(import_error(
+                    ErrorCode::ImportCycle,
+                    format!("Import cycle detected while importing '{}'", import.path),
+                    String::from("This import leads back to a file that is still being loaded"),
+                    import,
+                    Some(format!("Cycle: {}", cycle)),
+                ));
+                continue;
+            }
+
+            if self.module(&path).is_some() || self.unparsable.contains(&path) {
+                continue;
+            }
+
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(err) => {
+                    errors.push(import_error(
+                        ErrorCode::ModuleNotFound,
+                        format!("Could not read module '{}': {}", import.path, err),
+                        format!("Resolved to '{}'", path.display()),
+                        import,
+                        Some(String::from(
+                            "Import paths are relative to the file containing the import",
+                        )),
+                    ));
+                    continue;
+                }
+            };
+
+            let name = importer
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(&import.path)
+                .display()
+                .to_string();
+            debug!("Loading module {}", name);
+
+            let error_logger = ErrorLogger::new(&name, &source);
+            let Ok(module_ast) =
+                Compiler::parse_source(&mut Lexer::new(), &source, &error_logger, ids)
+            else {
+                self.unparsable.push(path);
+                self.failed = true;
+                continue;
+            };
+
+            self.stack.push(path.clone());
+            let module_errors = self.load_imports(Path::new(&name), &module_ast, ids);
+            self.stack.pop();
+
+            if !module_errors.is_empty() {
+                error_logger.report_many(&module_errors);
+                self.failed = true;
+            }
+
+            self.modules.push(Module {
+                name,
+                path,
+                source,
+                ast: module_ast,
+            });
+        }
+
+        errors
+    }
+
+    /// Collects the declarations `ast` imports from other modules.
+    ///
+    /// Imports of names that don't exist or aren't exported are reported as errors.
+    pub(crate) fn imports<'m>(
+        &'m self,
+        importer: &Path,
+        ast: &AST,
+    ) -> (Vec<&'m StatementType>, Vec<CompilerError>) {
+        let mut imported = Vec::new();
+        let mut errors = Vec::new();
+
+        for statement in &ast.statements {
+            let StatementType::Import(import) = &statement.kind else {
+                continue;
+            };
+
+            // Missing modules were already reported while loading
+            let Some(module) = self.module(&ModuleLoader::resolve(importer, &import.path)) else {
+                continue;
+            };
+
+            let declarations: HashMap<&str, &StatementType> = module
+                .ast
+                .statements
+                .iter()
+                .filter_map(|statement| {
+                    statement
+                        .kind
+                        .declared_name()
+                        .map(|name| (name, &statement.kind))
+                })
+                .collect();
+
+            let Some(names) = &import.names else {
+                imported.extend(
+                    module
+                        .ast
+                        .statements
+                        .iter()
+                        .map(|statement| &statement.kind)
+                        .filter(|statement| statement.is_exported()),
+                );
+                continue;
+            };
+
+            for name in names {
+                match declarations.get(name.name.as_str()) {
+                    Some(declaration) if declaration.is_exported() => imported.push(*declaration),
+                    Some(_) => errors.push(name_error(
+                        ErrorCode::PrivateImport,
+                        format!("'{}' is not exported by '{}'", name.name, import.path),
+                        &name.name,
+                        name.span.clone(),
+                        Some(format!(
+                            "Add `export` to the declaration of '{}' in '{}'",
+                            name.name, module.name
+                        )),
+                    )),
+                    None => errors.push(name_error(
+                        ErrorCode::UnresolvedImport,
+                        format!("'{}' is not declared in '{}'", name.name, import.path),
+                        &name.name,
+                        name.span.clone(),
+                        None,
+                    )),
+                }
+            }
+        }
+
+        (imported, errors)
+    }
+}
+
+/// Canonical paths make sure that one file is only loaded once, however it is imported.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn import_error(
+    error_code: ErrorCode,
+    error_message: String,
+    span_message: String,
+    import: &ImportStatement,
+    info: Option<String>,
+) -> CompilerError {
+    CompilerError {
+        error_code,
+        error_message,
+        span_message,
+        token: Token {
+            type_: TokenType::StringLiteral,
+            value: import.path.clone(),
+            span: import.path_span.clone(),
+        },
+        help: None,
+        info,
+    }
+}
+
+fn name_error(
+    error_code: ErrorCode,
+    error_message: String,
+    name: &str,
+    span: Span,
+    help: Option<String>,
+) -> CompilerError {
+    CompilerError {
+        error_code,
+        error_message,
+        span_message: String::from("Imported here"),
+        token: Token {
+            type_: TokenType::Identifier,
+            value: String::from(name),
+            span,
+        },
+        help,
+        info: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{node::NodeIds, parser::Parser};
+    use crate::tests::before_each;
+
+    fn load(entry: &str) -> (ModuleLoader, Vec<ErrorCode>) {
+        let path = Path::new(entry);
+        let source = fs::read_to_string(path).unwrap();
+        let mut lexer = Lexer::new();
+        lexer.lex(&source);
+        let ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+
+        let mut loader = ModuleLoader::new(path);
+        let mut errors = loader.load_imports(path, &ast, &mut NodeIds::after(&ast));
+        errors.extend(loader.imports(path, &ast).1);
+
+        (
+            loader,
+            errors.into_iter().map(|error| error.error_code).collect(),
+        )
+    }
+
+    #[test]
+    fn test_import_cycle() {
+        before_each();
+        let (loader, errors) = load("tests/modules/cycle_a.lit");
+
+        // The cycle is found while loading `cycle_b.lit`, so it is reported there
+        assert_eq!(errors, vec![]);
+        assert!(loader.failed);
+        assert_eq!(loader.modules.len(), 1);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        before_each();
+        let (loader, errors) = load("tests/modules/broken_imports.lit");
+
+        assert_eq!(
+            errors,
+            vec![
+                ErrorCode::ModuleNotFound,
+                ErrorCode::PrivateImport,
+                ErrorCode::UnresolvedImport
+            ]
+        );
+        assert!(!loader.failed);
+    }
+
+    #[test]
+    fn test_unparsable_module_loaded_once() {
+        before_each();
+        let (loader, errors) = load("tests/modules/unparsable_twice.lit");
+
+        // `unparsable_via.lit` imports the broken module again, which must not parse it twice
+        assert_eq!(errors, vec![]);
+        assert!(loader.failed);
+        assert_eq!(loader.unparsable.len(), 1);
+        assert_eq!(loader.modules.len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    expression::Expression,
+    parser::AST,
+    statement::Statement,
+    token::Span,
+    visit::{
+        walk_expression, walk_expression_mut, walk_statement, walk_statement_mut, Visitor,
+        VisitorMut,
+    },
+};
+
+/// Identifies a node within its AST, so analysis passes can record types, scopes and
+/// resolutions in side tables keyed by id instead of changing the tree.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub(crate) struct NodeId(pub(crate) u32);
+
+/// Hands out node ids. A compilation shares one allocator between the entry file and
+/// every module it imports, so ids are unique across all of their ASTs.
+#[derive(Debug, Default)]
+pub(crate) struct NodeIds {
+    next: u32,
+}
+
+impl NodeIds {
+    /// Continues after the largest id in `ast`, so later nodes never reuse one of its ids.
+    pub(crate) fn after(ast: &AST) -> NodeIds {
+        let mut last = LastId(None);
+        last.visit_ast(ast);
+        NodeIds {
+            next: last.0.map_or(0, |NodeId(id)| id + 1),
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> NodeId {
+        let id = NodeId(self.next);
+        self.next += 1;
+        id
+    }
+
+    /// Numbers every node of `ast` in the order the parser finishes them, children first.
+    pub(crate) fn number(&mut self, ast: &mut AST) {
+        Renumber { ids: self }.visit_ast_mut(ast);
+    }
+}
+
+/// A statement or expression, with its id and the source it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Node<K> {
+    /// Can be left out of JSON input, in which case every node is given a fresh one.
+    #[serde(default)]
+    pub(crate) id: NodeId,
+    pub(crate) kind: K,
+    pub(crate) span: Span,
+}
+
+impl<K> Node<K> {
+    /// Creates a node whose id is assigned once the whole AST is numbered.
+    pub(crate) fn new(kind: K, span: Span) -> Node<K> {
+        Node {
+            id: NodeId::default(),
+            kind,
+            span,
+        }
+    }
+}
+
+/// Gives every node a fresh id if any two nodes share one, as they do when an AST built by
+/// another tool leaves the ids out. An AST whose ids are already unique is left unchanged.
+pub(crate) fn renumber_duplicates(ast: &mut AST) {
+    let mut ids = UniqueIds {
+        seen: HashSet::new(),
+        unique: true,
+    };
+    ids.visit_ast(ast);
+
+    if !ids.unique {
+        NodeIds::default().number(ast);
+    }
+}
+
+struct UniqueIds {
+    seen: HashSet<NodeId>,
+    unique: bool,
+}
+
+impl Visitor<'_> for UniqueIds {
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.unique &= self.seen.insert(statement.id);
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.unique &= self.seen.insert(expression.id);
+        walk_expression(self, expression);
+    }
+}
+
+struct LastId(Option<NodeId>);
+
+impl Visitor<'_> for LastId {
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.0 = self.0.max(Some(statement.id));
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.0 = self.0.max(Some(expression.id));
+        walk_expression(self, expression);
+    }
+}
+
+/// Assigns ids children first, so they follow the order nodes finish parsing.
+struct Renumber<'a> {
+    ids: &'a mut NodeIds,
+}
+
+impl VisitorMut for Renumber<'_> {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+        statement.id = self.ids.next();
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        expression.id = self.ids.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::parser::AST;
+    use crate::lang::compiler::{
+        expression::ExpressionType, lexer::Lexer, parser::Parser, statement::StatementType,
+    };
+    use crate::tests::before_each;
+
+    fn ids(input: &str) -> Vec<NodeId> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| {
+                panic!("Failed to parse: {}", err.error_message);
+            });
+
+        let StatementType::Let(let_) = &ast.statements[0].kind else {
+            panic!("Expected let statement");
+        };
+        let value = let_.value.as_ref().unwrap();
+        let ExpressionType::Binary(binary) = &value.kind else {
+            panic!("Expected binary expression");
+        };
+
+        vec![
+            ast.statements[0].id,
+            value.id,
+            binary.left.id,
+            binary.right.id,
+            ast.statements[1].id,
+        ]
+    }
+
+    #[test]
+    fn test_node_ids() {
+        before_each();
+        let input = "let a = 1 + 2; a;";
+        let first = ids(input);
+
+        let mut unique = first.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), first.len());
+
+        // Children are finished before their parents
+        assert!(first[2] < first[1] && first[1] < first[0]);
+        assert_eq!(ids(input), first);
+    }
+
+    #[test]
+    fn test_shared_ids() {
+        before_each();
+        let parse = |input: &str, ids: &mut NodeIds| {
+            let mut lexer = Lexer::new();
+            lexer.lex(input);
+            Parser::parse(&mut lexer.get_tokens_peekable(), ids).unwrap_or_else(|err| {
+                panic!("Failed to parse: {}", err.error_message);
+            })
+        };
+
+        // Files parsed with one allocator, like the modules of a compilation, never share ids
+        let mut ids = NodeIds::default();
+        let entry = parse("let a = 1;", &mut ids);
+        let module = parse("let a = 1;", &mut ids);
+        assert_eq!(entry.statements[0].id, NodeId(1));
+        assert_eq!(module.statements[0].id, NodeId(3));
+
+        assert_eq!(NodeIds::after(&module).next(), NodeId(4));
+    }
+
+    #[test]
+    fn test_renumber_duplicates() {
+        before_each();
+        let json = r#"{"version": 2, "statements": [
+            {"kind": {"type": "Expression", "kind": {"type": "Literal", "literal": "Unknown",
+                "value": "a"}, "span": {"start": 0, "end": 1}}, "span": {"start": 0, "end": 2}},
+            {"id": 7, "kind": {"type": "Unknown"}, "span": {"start": 3, "end": 4}}
+        ]}"#;
+
+        let ast = AST::from_json(json).unwrap();
+        let StatementType::Expression(expression) = &ast.statements[0].kind else {
+            panic!("Expected expression statement");
+        };
+        assert_eq!(
+            (expression.id, ast.statements[0].id, ast.statements[1].id),
+            (NodeId(0), NodeId(1), NodeId(2))
+        );
+
+        // Unique ids are kept
+        let mut ast = ast;
+        ast.statements[1].id = NodeId(7);
+        let json = ast.to_json();
+        assert_eq!(AST::from_json(&json).unwrap().statements[1].id, NodeId(7));
+    }
+}
@@ -1,25 +1,134 @@
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-use super::{token::TokenStream, CompilerResult, statement::{Statement, SpannedStatement}};
+use super::{expression::Captures, macros::Expansion, node::{self, NodeIds}, schema::Versioned, token::TokenStream, CompilerResult, statement::Statement};
 
-#[derive(Debug, Clone, Serialize)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct AST {
-    statements: Vec<SpannedStatement>
+    pub(crate) statements: Vec<Statement>,
+    /// The macro expansions the spans of this AST may point into.
+    #[serde(skip)]
+    pub(crate) expansions: Vec<Expansion>,
+}
+
+impl AST {
+    /// Writes the AST as a versioned JSON document, described by `schema/ast.v<version>.json`.
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&Versioned::new(self)).unwrap()
+    }
+
+    /// Reads an AST in the format `--debug` prints, such as one emitted by a code generator.
+    /// Node ids may be left out, in which case every node is given a fresh one.
+    pub(crate) fn from_json(json: &str) -> serde_json::Result<AST> {
+        let mut ast = serde_json::from_str::<Versioned<AST>>(json)?.contents;
+        node::renumber_duplicates(&mut ast);
+        Ok(ast)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Parser {}
 
 impl Parser {
-    pub(crate) fn parse<'a>(tokens: &mut TokenStream) -> CompilerResult<AST> {
+    /// Parses a whole file, numbering its nodes with `ids`.
+    pub(crate) fn parse(tokens: &mut TokenStream, ids: &mut NodeIds) -> CompilerResult<AST> {
         let mut ast = AST {
             statements: Vec::new(),
+            expansions: Vec::new(),
         };
 
-        while let Some(_) = tokens.peek() {
-            ast.statements.push(Statement::parse(tokens)?);
+        while tokens.peek().is_some() {
+            ast.statements.push(Statement::parse_top_level(tokens)?);
         }
 
+        Captures::record(&mut ast);
+        ids.number(&mut ast);
         Ok(ast)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{lexer::Lexer, statement::StatementType, Compiler, ErrorCode};
+    use crate::lang::util::error_logger::ErrorLogger;
+    use crate::tests::before_each;
+
+    #[test]
+    fn test_json_round_trip() {
+        before_each();
+
+        for entry in std::fs::read_dir("tests/e2e").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("lit") {
+                continue;
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            let name = path.display().to_string();
+            let error_logger = ErrorLogger::new(&name, &source);
+            let ast = Compiler::parse_source(
+                &mut Lexer::new(),
+                &source,
+                &error_logger,
+                &mut NodeIds::default(),
+            )
+            .unwrap_or_else(|err| panic!("Failed to parse {}: {}", name, err));
+
+            let json = ast.to_json();
+            let parsed = AST::from_json(&json).unwrap_or_else(|err| {
+                panic!("Failed to read {} back: {}", path.display(), err);
+            });
+
+            assert_eq!(parsed.statements, ast.statements, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_enum_declaration() {
+        before_each();
+        let input = "export enum Status { Active, Suspended(reason: string, days: int), }";
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        let ast = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+
+        let StatementType::Enum(enum_) = &ast.statements[0].kind else {
+            panic!("Expected an enum");
+        };
+        assert!(enum_.exported);
+        let names: Vec<&str> = enum_
+            .variants
+            .iter()
+            .map(|variant| variant.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Active", "Suspended"]);
+        assert!(enum_.variants[0].fields.is_empty());
+        assert_eq!(enum_.variants[1].fields[1].name, "days");
+
+        let mut lexer = Lexer::new();
+        lexer.lex("enum Status { Active, Active }");
+        let error = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .err()
+            .unwrap();
+        assert_eq!(error.error_code, ErrorCode::DuplicateEnumVariant);
+    }
+
+    #[test]
+    fn test_let_without_initializer() {
+        before_each();
+        let mut lexer = Lexer::new();
+        lexer.lex("let x: int; x = 1;");
+        let error = Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .err()
+            .unwrap();
+        assert_eq!(error.error_code, ErrorCode::MissingInitializer);
+        assert_eq!(error.token.value, "x");
+
+        // A mutable binding can be assigned later
+        let mut lexer = Lexer::new();
+        lexer.lex("let mut x: int; x = 1;");
+        assert!(Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default()).is_ok());
+    }
+}
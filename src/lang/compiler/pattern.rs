@@ -0,0 +1,189 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::lang::util::vec::UnshiftExpect;
+
+use super::{
+    cst::SyntaxKind,
+    expression::{LiteralExpression, LiteralType},
+    token::{Span, Token, TokenStream, TokenType},
+    CompilerError, CompilerResult, ErrorCode,
+};
+
+/// The left side of a `match` arm, which the matched value is tested against.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Pattern {
+    pub(crate) kind: PatternType,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub(crate) enum PatternType {
+    /// `_`, which matches any value.
+    Wildcard,
+    /// A name, which matches any value and binds it for the guard and body of the arm.
+    Binding { name: String },
+    /// An integer, a string, `null`, `true` or `false`, which matches an equal value.
+    Literal(LiteralExpression),
+    /// `Status.Suspended(reason)`, which matches a variant and then its fields, by position.
+    Variant(VariantPattern),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct VariantPattern {
+    pub(crate) enum_: String,
+    pub(crate) variant: String,
+    pub(crate) fields: Vec<Pattern>,
+}
+
+impl Pattern {
+    pub(crate) fn parse(tokens: &mut TokenStream) -> CompilerResult<Pattern> {
+        tokens.start_node(SyntaxKind::Pattern);
+        let Some(token) = tokens.next().cloned() else {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::InvalidPattern,
+                error_message: String::from("End of file reached while parsing a pattern"),
+                span_message: String::from(""),
+                token: Token::invalid(),
+                help: None,
+                info: None,
+            }));
+        };
+
+        let kind = match token.type_ {
+            TokenType::Identifier if token.value == "_" => PatternType::Wildcard,
+            TokenType::Identifier if tokens.next_matches(TokenType::Dot) => {
+                return Pattern::variant(tokens, token);
+            }
+            // `true` and `false` are names in expressions, but always literals in patterns
+            TokenType::Identifier if token.value == "true" || token.value == "false" => {
+                PatternType::Literal(LiteralExpression {
+                    literal: LiteralType::Unknown,
+                    value: token.value,
+                })
+            }
+            TokenType::Identifier => PatternType::Binding { name: token.value },
+            TokenType::IntegerLiteral | TokenType::StringLiteral | TokenType::Null => {
+                PatternType::Literal(LiteralExpression {
+                    literal: token.type_.into(),
+                    value: token.value,
+                })
+            }
+            _ => {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::InvalidPattern,
+                    error_message: format!("Invalid pattern: got '{}'", token.value),
+                    span_message: String::from("Expected a pattern"),
+                    token,
+                    help: Some(String::from(
+                        "Expected one of: \n- _\n- A name\n- A literal\n- A variant, such as `Status.Active`",
+                    )),
+                    info: None,
+                }));
+            }
+        };
+        tokens.finish_node();
+
+        Ok(Pattern {
+            kind,
+            span: token.span,
+        })
+    }
+
+    /// Parses the rest of `Enum.Variant` or `Enum.Variant(pattern, ...)` after the enum name.
+    fn variant(tokens: &mut TokenStream, enum_: Token) -> CompilerResult<Pattern> {
+        tokens.unshift_expect(TokenType::Dot)?;
+        let variant = tokens.unshift_expect(TokenType::Identifier)?.clone();
+
+        let mut fields = Vec::new();
+        let mut end = variant.span.end;
+
+        if tokens.unshift_if(TokenType::LeftParen).is_some() {
+            while !tokens.next_matches(TokenType::RightParen) {
+                fields.push(Pattern::parse(tokens)?);
+
+                if !tokens.next_matches(TokenType::RightParen) {
+                    tokens.unshift_expect(TokenType::Comma)?;
+                }
+            }
+
+            end = tokens.unshift_expect(TokenType::RightParen)?.span.end;
+        }
+        tokens.finish_node();
+
+        Ok(Pattern {
+            kind: PatternType::Variant(VariantPattern {
+                enum_: enum_.value,
+                variant: variant.value,
+                fields,
+            }),
+            span: enum_.span.start..end,
+        })
+    }
+
+    /// The names the pattern binds, in order, with their spans.
+    pub(crate) fn bindings(&self) -> Vec<(&str, &Span)> {
+        match &self.kind {
+            PatternType::Wildcard | PatternType::Literal(_) => Vec::new(),
+            PatternType::Binding { name } => vec![(name.as_str(), &self.span)],
+            PatternType::Variant(variant) => variant
+                .fields
+                .iter()
+                .flat_map(|field| field.bindings())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::lexer::Lexer;
+    use crate::tests::before_each;
+
+    fn parse(input: &str) -> CompilerResult<Pattern> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        Pattern::parse(&mut lexer.get_tokens_peekable())
+    }
+
+    #[test]
+    fn test_patterns() {
+        before_each();
+        let pattern = parse("Shape.Rect(_, h, 2)")
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message));
+        assert_eq!(pattern.span, 0..19);
+
+        let PatternType::Variant(variant) = &pattern.kind else {
+            panic!("Expected a variant pattern");
+        };
+        assert_eq!(
+            (variant.enum_.as_str(), variant.variant.as_str()),
+            ("Shape", "Rect")
+        );
+        assert_eq!(variant.fields[0].kind, PatternType::Wildcard);
+        assert_eq!(
+            variant.fields[2].kind,
+            PatternType::Literal(LiteralExpression {
+                literal: LiteralType::Integer,
+                value: String::from("2"),
+            })
+        );
+
+        let names: Vec<&str> = pattern
+            .bindings()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["h"]);
+
+        let pattern = parse("true").unwrap_or_else(|err| panic!("{}", err.error_message));
+        assert!(pattern.bindings().is_empty());
+
+        assert!(matches!(
+            parse("[x]").map_err(|err| err.error_code),
+            Err(ErrorCode::InvalidPattern)
+        ));
+    }
+}
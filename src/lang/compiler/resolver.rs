@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use super::{
+    attribute::Attribute,
+    expression::{
+        ClosureBody, Expression, ExpressionType, LiteralExpression, LiteralType, MatchArm,
+    },
+    node::NodeId,
+    parser::AST,
+    statement::{BlockStatement, FunctionStatement, Parameter, Statement, StatementType},
+    token::{Span, Token, TokenType},
+    visit::{walk_expression, walk_statement, Visitor},
+    CompilerError, ErrorCode,
+};
+use crate::lang::util::suggest;
+
+/// Names that are always in scope without being declared.
+const BUILTINS: &[&str] = &["true", "false"];
+
+/// What a name refers to, recorded for each identifier by the id of its expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Declaration {
+    /// A `let`, `const`, function or enum statement.
+    Statement(NodeId),
+    /// A parameter of a function statement or closure expression, by position.
+    Parameter(NodeId, usize),
+    /// The binding of a `for` loop or `catch` handler, by the id of the statement.
+    Binding(NodeId),
+    /// A name bound by the pattern of a `match` arm, by the id of the match and the position
+    /// of the arm.
+    Pattern(NodeId, usize),
+    /// A declaration imported from another module, by its position in the imports.
+    Imported(usize),
+    Builtin,
+}
+
+/// Resolves every identifier to its declaration through the lexical scopes it is used in,
+/// reporting names that are undefined, used before they are declared, or declared twice.
+pub(crate) struct Resolver<'a> {
+    scopes: Vec<Scope<'a>>,
+    declarations: HashMap<NodeId, Declaration>,
+    errors: Vec<CompilerError>,
+}
+
+#[derive(Default)]
+struct Scope<'a> {
+    names: Vec<(&'a str, Declaration)>,
+    /// The `let` and `const` names of the block that aren't declared yet.
+    pending: Vec<&'a str>,
+    /// Functions whose bodies are resolved at the end of the block, once every
+    /// declaration they could use is known.
+    functions: Vec<(NodeId, &'a FunctionStatement)>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Resolves the whole AST, returning the declaration of each identifier and every error
+    /// found. `imports` are the declarations this file imports from other modules.
+    pub(crate) fn resolve(
+        ast: &'a AST,
+        imports: &[&'a StatementType],
+    ) -> (HashMap<NodeId, Declaration>, Vec<CompilerError>) {
+        let mut resolver = Resolver {
+            scopes: vec![Scope::default()],
+            declarations: HashMap::new(),
+            errors: Vec::new(),
+        };
+
+        for name in BUILTINS {
+            resolver.declare(name, Declaration::Builtin);
+        }
+
+        for (index, import) in imports.iter().enumerate() {
+            match import {
+                StatementType::Const(const_) => {
+                    resolver.declare(&const_.name, Declaration::Imported(index))
+                }
+                StatementType::Enum(enum_) => {
+                    resolver.declare(&enum_.name, Declaration::Imported(index))
+                }
+                StatementType::Function(function) => {
+                    resolver.declare(&function.name, Declaration::Imported(index))
+                }
+                StatementType::Let(let_) => {
+                    resolver.declare(&let_.name, Declaration::Imported(index))
+                }
+                _ => {}
+            }
+        }
+
+        resolver.scope(&ast.statements, None);
+        (resolver.declarations, resolver.errors)
+    }
+
+    /// Resolves a block in a scope of its own.
+    fn scope(&mut self, statements: &'a [Statement], tail: Option<&'a Expression>) {
+        let mut scope = Scope::default();
+
+        // Functions can be called before they are declared, and enums used
+        for statement in statements {
+            match &statement.kind {
+                StatementType::Function(function) => {
+                    if scope.names.iter().any(|(name, _)| *name == function.name) {
+                        self.error(
+                            ErrorCode::DuplicateFunction,
+                            format!("Function '{}' is declared more than once", function.name),
+                            String::from("This function was already declared in this scope"),
+                            &function.name,
+                            statement.span.clone(),
+                            Some(String::from("Rename one of the functions")),
+                        );
+                    }
+                    scope
+                        .names
+                        .push((&function.name, Declaration::Statement(statement.id)));
+                }
+                StatementType::Enum(enum_) => scope
+                    .names
+                    .push((&enum_.name, Declaration::Statement(statement.id))),
+                StatementType::Const(const_) => scope.pending.push(&const_.name),
+                StatementType::Let(let_) => scope.pending.push(&let_.name),
+                _ => {}
+            }
+        }
+
+        self.scopes.push(scope);
+
+        for statement in statements {
+            self.visit_statement(statement);
+        }
+        if let Some(tail) = tail {
+            self.visit_expression(tail);
+        }
+
+        let functions = std::mem::take(&mut self.scopes.last_mut().unwrap().functions);
+        for (id, function) in functions {
+            self.function(id, function);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn function(&mut self, id: NodeId, function: &'a FunctionStatement) {
+        self.parameters(id, &function.parameters);
+        self.visit_block(&function.body);
+        self.scopes.pop();
+    }
+
+    /// Pushes a scope with the parameters, which the caller pops once the body is resolved.
+    /// Parameters are bound in order, so a default value can refer to the parameters before it.
+    fn parameters(&mut self, id: NodeId, parameters: &'a [Parameter]) {
+        self.scopes.push(Scope {
+            pending: parameters
+                .iter()
+                .map(|parameter| parameter.name.as_str())
+                .collect(),
+            ..Default::default()
+        });
+
+        for (index, parameter) in parameters.iter().enumerate() {
+            self.visit_parameter(parameter);
+            self.declare(&parameter.name, Declaration::Parameter(id, index));
+        }
+    }
+
+    /// Resolves a block in a new scope where `name` is bound.
+    fn binding(&mut self, name: &'a str, declaration: Declaration, block: &'a BlockStatement) {
+        self.scopes.push(Scope {
+            names: vec![(name, declaration)],
+            ..Default::default()
+        });
+        self.visit_block(block);
+        self.scopes.pop();
+    }
+
+    /// Resolves the guard and body of a `match` arm in a new scope with the names its
+    /// pattern binds.
+    fn arm(&mut self, id: NodeId, index: usize, arm: &'a MatchArm) {
+        let mut scope = Scope::default();
+
+        for (name, span) in arm.pattern.bindings() {
+            if scope.names.iter().any(|(bound, _)| *bound == name) {
+                self.error(
+                    ErrorCode::InvalidPattern,
+                    format!("'{}' is bound more than once in the same pattern", name),
+                    String::from("Already bound in this pattern"),
+                    name,
+                    span.clone(),
+                    Some(String::from("Use `_` for the fields you don't need")),
+                );
+            }
+            scope.names.push((name, Declaration::Pattern(id, index)));
+        }
+
+        self.scopes.push(scope);
+        if let Some(guard) = &arm.guard {
+            self.visit_expression(guard);
+        }
+        self.visit_expression(&arm.body);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &'a str, declaration: Declaration) {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.pending.retain(|pending| *pending != name);
+        scope.names.push((name, declaration));
+    }
+
+    fn lookup(&self, name: &str) -> Option<Declaration> {
+        self.scopes.iter().rev().find_map(|scope| {
+            scope
+                .names
+                .iter()
+                .rev()
+                .find(|(declared, _)| *declared == name)
+                .map(|(_, declaration)| *declaration)
+        })
+    }
+
+    fn use_(&mut self, name: &str, id: NodeId, span: &Span) {
+        if let Some(declaration) = self.lookup(name) {
+            self.declarations.insert(id, declaration);
+            return;
+        }
+
+        let pending = self
+            .scopes
+            .iter()
+            .any(|scope| scope.pending.contains(&name));
+
+        if pending {
+            self.error(
+                ErrorCode::UseBeforeDeclaration,
+                format!("'{}' is used before it is declared", name),
+                String::from("Declared later in this scope"),
+                name,
+                span.clone(),
+                Some(format!(
+                    "Move the declaration of '{}' before its first use",
+                    name
+                )),
+            );
+            return;
+        }
+
+        let candidates = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.names.iter().map(|(declared, _)| *declared));
+        let help = match suggest::closest(name, candidates) {
+            Some(closest) => format!("Did you mean '{}'?", closest),
+            None => format!("Declare '{}' with `let` or `const` first", name),
+        };
+
+        self.error(
+            ErrorCode::UndefinedName,
+            format!("Cannot find '{}' in this scope", name),
+            String::from("Not declared"),
+            name,
+            span.clone(),
+            Some(help),
+        );
+    }
+
+    fn error(
+        &mut self,
+        error_code: ErrorCode,
+        error_message: String,
+        span_message: String,
+        value: &str,
+        span: Span,
+        help: Option<String>,
+    ) {
+        self.errors.push(CompilerError {
+            error_code,
+            error_message,
+            span_message,
+            token: Token {
+                type_: TokenType::Identifier,
+                value: String::from(value),
+                span,
+            },
+            help,
+            info: None,
+        });
+    }
+}
+
+impl<'a> Visitor<'a> for Resolver<'a> {
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        self.scope(&block.statements, block.tail.as_deref());
+    }
+
+    fn visit_statement(&mut self, statement: &'a Statement) {
+        match &statement.kind {
+            StatementType::Const(const_) => {
+                self.visit_expression(&const_.value);
+                self.declare(&const_.name, Declaration::Statement(statement.id));
+            }
+            StatementType::Let(let_) => {
+                if let Some(value) = &let_.value {
+                    self.visit_expression(value);
+                }
+                self.declare(&let_.name, Declaration::Statement(statement.id));
+            }
+            StatementType::Function(function) => {
+                let scope = self.scopes.last_mut().unwrap();
+                scope.functions.push((statement.id, function));
+            }
+            StatementType::For(for_) => {
+                self.visit_expression(&for_.iterable);
+                self.binding(
+                    &for_.binding,
+                    Declaration::Binding(statement.id),
+                    &for_.body,
+                );
+            }
+            StatementType::Try(try_) => {
+                self.visit_block(&try_.body);
+                self.binding(
+                    &try_.binding,
+                    Declaration::Binding(statement.id),
+                    &try_.handler,
+                );
+            }
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        match &expression.kind {
+            ExpressionType::Closure(closure) => {
+                self.parameters(expression.id, &closure.parameters);
+                match &closure.body {
+                    ClosureBody::Expression(body) => self.visit_expression(body),
+                    ClosureBody::Block(block) => self.visit_block(block),
+                }
+                self.scopes.pop();
+            }
+            ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) => self.use_(value, expression.id, &expression.span),
+            ExpressionType::Match(match_) => {
+                self.visit_expression(&match_.value);
+                for (index, arm) in match_.arms.iter().enumerate() {
+                    self.arm(expression.id, index, arm);
+                }
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+
+    // Attribute arguments name options, such as `cfg` predicates, rather than values
+    fn visit_attribute(&mut self, _attribute: &'a Attribute) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{lexer::Lexer, node::NodeIds, parser::Parser};
+    use crate::tests::before_each;
+
+    fn parse(input: &str) -> AST {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message))
+    }
+
+    fn resolve(input: &str) -> Vec<ErrorCode> {
+        let ast = parse(input);
+        Resolver::resolve(&ast, &[])
+            .1
+            .into_iter()
+            .map(|error| error.error_code)
+            .collect()
+    }
+
+    #[test]
+    fn test_undefined_names() {
+        before_each();
+        assert_eq!(resolve("let x = 1; @print(x + 1);"), vec![]);
+        assert_eq!(
+            resolve("let x = 1; @print(y);"),
+            vec![ErrorCode::UndefinedName]
+        );
+        assert_eq!(
+            resolve("function double(n: int) { return n * 2; } double(2); doubel(2);"),
+            vec![ErrorCode::UndefinedName]
+        );
+        assert_eq!(
+            resolve("{ let x = 1; } @print(x);"),
+            vec![ErrorCode::UndefinedName]
+        );
+        assert_eq!(resolve("let ok = true || false;"), vec![]);
+
+        let ast = parse("let count = 1; @print(coumt);");
+        let (_, errors) = Resolver::resolve(&ast, &[]);
+        assert_eq!(errors[0].help.as_deref(), Some("Did you mean 'count'?"));
+    }
+
+    #[test]
+    fn test_scopes() {
+        before_each();
+        assert_eq!(
+            resolve("for i in 0..3 { @print(i); } @print(i);"),
+            vec![ErrorCode::UndefinedName]
+        );
+        assert_eq!(
+            resolve("try { @print(1); } catch (e) { @print(e); } @print(e);"),
+            vec![ErrorCode::UndefinedName]
+        );
+        assert_eq!(
+            resolve("let base = 1; let add = |n: int| n + base; @print(n);"),
+            vec![ErrorCode::UndefinedName]
+        );
+        // Function bodies see every declaration of their block, but not the caller's variables
+        assert_eq!(
+            resolve(
+                "function f() { return g() + limit; } function g() { return 1; } let limit = 2;"
+            ),
+            vec![]
+        );
+        assert_eq!(
+            resolve("function f() { return local; } { let local = 1; f(); }"),
+            vec![ErrorCode::UndefinedName]
+        );
+        // A default value can refer to the parameters before it, but not after
+        assert_eq!(
+            resolve("function f(a: int, b: int = a) { return b; }"),
+            vec![]
+        );
+        assert_eq!(
+            resolve("function f(a: int = b, b: int = 1) { return a; }"),
+            vec![ErrorCode::UseBeforeDeclaration]
+        );
+        assert_eq!(resolve("let g = |a: int, b: int = a| a + b;"), vec![]);
+    }
+
+    #[test]
+    fn test_use_before_declaration() {
+        before_each();
+        assert_eq!(
+            resolve("@print(x); let x = 1;"),
+            vec![ErrorCode::UseBeforeDeclaration]
+        );
+        assert_eq!(
+            resolve("let x = x + 1;"),
+            vec![ErrorCode::UseBeforeDeclaration]
+        );
+        assert_eq!(
+            resolve("{ @print(limit); } const limit = 3;"),
+            vec![ErrorCode::UseBeforeDeclaration]
+        );
+        // The outer binding is still visible until the inner one is declared
+        assert_eq!(resolve("let x = 1; { let x = x + 1; }"), vec![]);
+    }
+
+    #[test]
+    fn test_duplicate_functions() {
+        before_each();
+        assert_eq!(
+            resolve("function f() { return 1; } function f() { return 2; }"),
+            vec![ErrorCode::DuplicateFunction]
+        );
+        assert_eq!(
+            resolve("function f() { function f() { return 1; } return f(); }"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_declarations() {
+        before_each();
+        let ast = parse("function f(a: int) { let b = a; return b; } let c = f(1);");
+        let (declarations, errors) = Resolver::resolve(&ast, &[]);
+        assert!(errors.is_empty());
+
+        let StatementType::Function(function) = &ast.statements[0].kind else {
+            panic!("Expected a function");
+        };
+        let StatementType::Let(let_) = &function.body.statements[0].kind else {
+            panic!("Expected a let statement");
+        };
+        let StatementType::Return(return_) = &function.body.statements[1].kind else {
+            panic!("Expected a return statement");
+        };
+        let f = ast.statements[0].id;
+
+        assert_eq!(
+            declarations[&let_.value.as_ref().unwrap().id],
+            Declaration::Parameter(f, 0)
+        );
+        assert_eq!(
+            declarations[&return_.value.as_ref().unwrap().id],
+            Declaration::Statement(function.body.statements[0].id)
+        );
+        assert_eq!(declarations.len(), 3);
+
+        let import = parse("export function g() { return 1; }");
+        let ast = parse("@print(g());");
+        let (declarations, errors) = Resolver::resolve(&ast, &[&import.statements[0].kind]);
+        assert!(errors.is_empty());
+        assert_eq!(
+            declarations.values().collect::<Vec<_>>(),
+            vec![&Declaration::Imported(0)]
+        );
+    }
+
+    #[test]
+    fn test_match_bindings() {
+        before_each();
+        let enum_ = "enum Shape { Circle(radius: int), Rect(w: int, h: int) }";
+        assert_eq!(
+            resolve(&format!(
+                "{} let s = Shape.Circle(1); let a = match s {{ Shape.Rect(w, h) if w > h => w * h, Shape.Circle(r) => r, _ => 0 }};",
+                enum_
+            )),
+            vec![]
+        );
+        // Bindings are scoped to their arm
+        assert_eq!(
+            resolve("let a = match 1 { n => n, _ => n };"),
+            vec![ErrorCode::UndefinedName]
+        );
+        assert_eq!(
+            resolve(&format!(
+                "{} let a = match Shape.Circle(1) {{ Shape.Rect(x, x) => x, _ => 0 }};",
+                enum_
+            )),
+            vec![ErrorCode::InvalidPattern]
+        );
+        // Enums can be used before they are declared
+        assert_eq!(
+            resolve(&format!("let s = Shape.Circle(1); {}", enum_)),
+            vec![]
+        );
+    }
+}
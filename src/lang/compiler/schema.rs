@@ -0,0 +1,136 @@
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, RootSchema, Schema, SchemaObject},
+    schema_for, JsonSchema,
+};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+use super::{parser::AST, token::Token};
+
+/// The version of the JSON format `--debug` and `--tokens` print and `--from-ast` reads.
+///
+/// Bump it whenever the serialized shape of the AST or tokens changes, and publish the new
+/// schemas with `lithc --schema ast > schema/ast.v<version>.json` (and likewise for `tokens`).
+/// Published schemas are never edited, so downstream tools can rely on them.
+pub(crate) const FORMAT_VERSION: u32 = 2;
+
+/// A JSON document: the format version, alongside the fields of its contents.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Versioned<T> {
+    /// The format version the document was written with. Other versions are rejected.
+    #[serde(deserialize_with = "version")]
+    #[schemars(schema_with = "version_schema")]
+    pub(crate) version: u32,
+    #[serde(flatten)]
+    pub(crate) contents: T,
+}
+
+impl<T> Versioned<T> {
+    pub(crate) fn new(contents: T) -> Versioned<T> {
+        Versioned {
+            version: FORMAT_VERSION,
+            contents,
+        }
+    }
+}
+
+/// The tokens of a source file, in order.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Tokens {
+    pub(crate) tokens: Vec<Token>,
+}
+
+impl Tokens {
+    /// Writes the tokens as a versioned JSON document, described by `schema/tokens.v<version>.json`.
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&Versioned::new(self)).unwrap()
+    }
+}
+
+/// The kinds of JSON document the compiler reads and writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Document {
+    Ast,
+    Tokens,
+}
+
+impl Document {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Document::Ast => "ast",
+            Document::Tokens => "tokens",
+        }
+    }
+
+    /// The JSON Schema of this document at the current [`FORMAT_VERSION`].
+    pub(crate) fn schema(self) -> RootSchema {
+        let mut schema = match self {
+            Document::Ast => schema_for!(Versioned<AST>),
+            Document::Tokens => schema_for!(Versioned<Tokens>),
+        };
+        schema.schema.metadata().title = Some(format!("lithc {} v{}", self.name(), FORMAT_VERSION));
+        schema
+    }
+}
+
+fn version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version != FORMAT_VERSION {
+        return Err(D::Error::custom(format!(
+            "unsupported format version {}, expected {}",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    Ok(version)
+}
+
+fn version_schema(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        const_value: Some(FORMAT_VERSION.into()),
+        ..Default::default()
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::before_each;
+
+    #[test]
+    fn test_published_schemas() {
+        before_each();
+
+        for document in [Document::Ast, Document::Tokens] {
+            let name = document.name();
+            let path = format!("schema/{}.v{}.json", name, FORMAT_VERSION);
+            let published = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+                panic!(
+                    "{} is missing, publish it with `cargo run -- --schema {} > {}`",
+                    path, name, path
+                )
+            });
+
+            let published: serde_json::Value = serde_json::from_str(&published).unwrap();
+            assert_eq!(
+                published,
+                serde_json::to_value(document.schema()).unwrap(),
+                "The {} JSON format no longer matches {}. Bump FORMAT_VERSION and publish a new schema",
+                name,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        before_each();
+        let err = serde_json::from_str::<Versioned<Tokens>>(r#"{"version": 0, "tokens": []}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported format version 0"));
+
+        assert!(serde_json::from_str::<Versioned<Tokens>>(r#"{"tokens": []}"#).is_err());
+    }
+}
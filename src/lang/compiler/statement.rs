@@ -1,372 +1,950 @@
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use super::{
-    expression::Expression,
+    attribute::Attribute,
+    cst::SyntaxKind,
+    expression::{Expression, ExpressionType, IntrinsicExpression},
+    node::Node,
     token::{Span, Token, TokenStream, TokenType},
+    types::TypeExpr,
     CompilerError, CompilerResult, ErrorCode,
 };
 
-use crate::lang::util::vec::{UnshiftExpect, Unshift};
+use crate::lang::util::vec::UnshiftExpect;
 
-pub(crate) type SpannedStatement = (StatementType, Span);
+pub(crate) type Statement = Node<StatementType>;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub(crate) struct Statement {
-    pub(crate) statement: StatementType,
-    pub(crate) span: Span,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
 pub(crate) enum StatementType {
     Unknown,
     Block(BlockStatement),
+    Const(ConstStatement),
+    Enum(EnumStatement),
+    Expression(Expression),
+    For(ForStatement),
     Let(LetStatement),
     Function(FunctionStatement),
+    Import(ImportStatement),
     Return(ReturnStatement),
-    Intrinsic(IntrinsicStatement),
+    /// An intrinsic call used as a statement, such as `@print(x);`.
+    Intrinsic(IntrinsicExpression),
+    Struct(StructStatement),
+    Try(TryStatement),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct BlockStatement {
-    pub(crate) statements: Vec<SpannedStatement>,
+    pub(crate) statements: Vec<Statement>,
+    /// A final expression without a semicolon, which is the value of the block.
+    pub(crate) tail: Option<Box<Expression>>,
+}
+
+/// `for item in items { ... }`, where `items` is a list, a map or a range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ForStatement {
+    pub(crate) binding: String,
+    pub(crate) binding_span: Span,
+    pub(crate) iterable: Expression,
+    pub(crate) body: BlockStatement,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct LetStatement {
+    pub(crate) attributes: Vec<Attribute>,
+    pub(crate) exported: bool,
     pub(crate) name: String,
+    pub(crate) mutable: bool,
+    pub(crate) type_: Option<TypeExpr>,
     pub(crate) value: Option<Expression>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ConstStatement {
+    pub(crate) attributes: Vec<Attribute>,
+    pub(crate) exported: bool,
+    pub(crate) name: String,
+    pub(crate) type_: Option<TypeExpr>,
+    pub(crate) value: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct FunctionStatement {
+    pub(crate) attributes: Vec<Attribute>,
+    pub(crate) exported: bool,
     pub(crate) name: String,
+    /// `function* name()`, which yields its values one at a time instead of returning once.
+    pub(crate) generator: bool,
+    /// `<T, U: Ord>`, inferred from the arguments at each call site.
+    pub(crate) type_parameters: Vec<TypeParameter>,
     pub(crate) parameters: Vec<Parameter>,
-    pub(crate) return_type: ParameterType,
-    pub(crate) body: Vec<SpannedStatement>,
+    pub(crate) return_type: Option<TypeExpr>,
+    /// The tail value of the body is returned, as if it was a `return` statement.
+    pub(crate) body: BlockStatement,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub(crate) struct IntrinsicStatement {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct StructStatement {
+    pub(crate) attributes: Vec<Attribute>,
+    pub(crate) exported: bool,
     pub(crate) name: String,
-    pub(crate) arguments: Vec<Expression>,
+    pub(crate) fields: Vec<StructField>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub(crate) struct Parameter {
+/// `enum Status { Active, Suspended(reason: string) }`, whose values are one of its variants.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct EnumStatement {
+    pub(crate) attributes: Vec<Attribute>,
+    pub(crate) exported: bool,
     pub(crate) name: String,
-    pub(crate) type_: ParameterType,
+    pub(crate) variants: Vec<EnumVariant>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub(crate) enum ParameterType {
-    Unknown,
-    Integer,
-    Boolean,
-    String,
+/// A variant of an enum, with the fields its values carry, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct EnumVariant {
+    pub(crate) name: String,
+    pub(crate) fields: Vec<StructField>,
+    pub(crate) span: Span,
 }
 
-impl From<String> for ParameterType {
-    fn from(string: String) -> Self {
-        match string.as_str() {
-            "int" => ParameterType::Integer,
-            "bool" => ParameterType::Boolean,
-            "string" => ParameterType::String,
-            _ => ParameterType::Unknown,
-        }
-    }
+/// `try { ... } catch (e) { ... }`. The handler runs if a runtime error is raised in the body,
+/// with the error bound to `binding`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct TryStatement {
+    pub(crate) body: BlockStatement,
+    pub(crate) binding: String,
+    pub(crate) binding_span: Span,
+    pub(crate) handler: BlockStatement,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct StructField {
+    pub(crate) name: String,
+    pub(crate) type_: TypeExpr,
+    pub(crate) span: Span,
+}
+
+/// `import "path.lit";` imports every exported declaration, while
+/// `import { a, b } from "path.lit";` only imports the listed ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ImportStatement {
+    pub(crate) path: String,
+    pub(crate) path_span: Span,
+    pub(crate) names: Option<Vec<ImportedName>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ImportedName {
+    pub(crate) name: String,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Parameter {
+    pub(crate) name: String,
+    pub(crate) type_: Option<TypeExpr>,
+    /// `limit: int = 100`; the value used when a call leaves the parameter out.
+    pub(crate) default: Option<Expression>,
+    /// `...args: list<int>`, which collects the remaining positional arguments.
+    pub(crate) variadic: bool,
+    pub(crate) span: Span,
+}
+
+/// A type parameter of a generic function, such as `T: Ord + Eq`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct TypeParameter {
+    pub(crate) name: String,
+    /// The names of the constraints a type argument has to satisfy.
+    pub(crate) constraints: Vec<String>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct ReturnStatement {
     pub(crate) value: Option<Expression>,
 }
 
+impl StatementType {
+    /// The name a top-level declaration makes visible to the rest of the file.
+    pub(crate) fn declared_name(&self) -> Option<&str> {
+        match self {
+            StatementType::Const(const_) => Some(&const_.name),
+            StatementType::Enum(enum_) => Some(&enum_.name),
+            StatementType::Function(function) => Some(&function.name),
+            StatementType::Let(let_) => Some(&let_.name),
+            StatementType::Struct(struct_) => Some(&struct_.name),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_exported(&self) -> bool {
+        match self {
+            StatementType::Const(const_) => const_.exported,
+            StatementType::Enum(enum_) => enum_.exported,
+            StatementType::Function(function) => function.exported,
+            StatementType::Let(let_) => let_.exported,
+            StatementType::Struct(struct_) => struct_.exported,
+            _ => false,
+        }
+    }
+}
+
 impl Statement {
-    pub(crate) fn parse(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    /// Parses a statement at the top level of a file, where imports and exports are allowed.
+    pub(crate) fn parse_top_level(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        if Attribute::starts(tokens) {
+            Statement::attributed(tokens, Statement::parse_top_level)
+        } else if tokens.next_matches(TokenType::Import) {
+            Statement::import(tokens)
+        } else if tokens.next_matches(TokenType::Export) {
+            Statement::export(tokens)
+        } else {
+            Statement::parse(tokens)
+        }
+    }
+
+    pub(crate) fn parse(tokens: &mut TokenStream) -> CompilerResult<Statement> {
         if let Some(token) = tokens.peek() {
             match token.type_ {
                 TokenType::Let => Statement::let_(tokens),
+                TokenType::Const => Statement::const_(tokens),
                 TokenType::Function => Statement::function(tokens),
+                TokenType::Struct => Statement::struct_(tokens),
+                TokenType::Enum => Statement::enum_(tokens),
+                TokenType::Hash => Statement::attributed(tokens, Statement::parse),
+                TokenType::At if Attribute::starts(tokens) => {
+                    Statement::attributed(tokens, Statement::parse)
+                }
                 _ => Statement::statement(tokens),
             }
         } else {
-            Err(CompilerError {
+            Err(Box::new(CompilerError {
                 error_code: ErrorCode::NoTokensLeft,
-                error_message: format!("Expected start of statement, got None"),
+                error_message: String::from("Expected start of statement, got None"),
                 span_message: String::from(""),
                 token: Token::invalid(),
                 help: Some(String::from(
                     "Expected one of: - TODO: List expected tokens",
                 )),
                 info: None,
-            })
+            }))
         }
     }
 
-    fn let_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn let_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Let);
         let start = tokens.unshift_expect(TokenType::Let)?.clone();
 
-        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
+        let mutable = tokens.unshift_if(TokenType::Mut).is_some();
+        let name_token = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        let name = name_token.value.clone();
+        let type_ = Statement::type_annotation(tokens)?;
         let mut value: Option<Expression> = None;
 
-        if tokens.next_matches(TokenType::Equals) {
-            // TODO: Maybe add a function that automatically unshifts when token type matches?
-            tokens.unshift_expect(TokenType::Equals)?;
+        if tokens.unshift_if(TokenType::Equals).is_some() {
             value = Some(Expression::parse(tokens)?);
         }
 
-        let end = tokens.unshift_expect(TokenType::Semicolon)?;
+        // An immutable binding without a value could never be given one
+        if value.is_none() && !mutable {
+            return Err(Box::new(CompilerError {
+                error_code: ErrorCode::MissingInitializer,
+                error_message: format!("'{}' is immutable but has no value", name),
+                span_message: String::from("This binding is never initialized"),
+                token: name_token,
+                help: Some(format!(
+                    "Give '{}' a value, or declare it with `let mut` to assign it later",
+                    name
+                )),
+                info: None,
+            }));
+        }
 
-        Ok((
-            StatementType::Let(LetStatement { name, value }),
-            start.span.start..end.span.end,
+        let end = tokens.unshift_expect(TokenType::Semicolon)?.span.end;
+        tokens.finish_node();
+
+        Ok(Statement::new(
+            StatementType::Let(LetStatement {
+                attributes: Vec::new(),
+                exported: false,
+                name,
+                mutable,
+                type_,
+                value,
+            }),
+            start.span.start..end,
         ))
     }
 
-    fn function(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn const_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Const);
+        let start = tokens.unshift_expect(TokenType::Const)?.clone();
+
+        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
+        let type_ = Statement::type_annotation(tokens)?;
+
+        // Unlike `let`, a constant always needs a value
+        tokens.unshift_expect(TokenType::Equals)?;
+        let value = Expression::parse(tokens)?;
+
+        let end = tokens.unshift_expect(TokenType::Semicolon)?.span.end;
+        tokens.finish_node();
+
+        Ok(Statement::new(
+            StatementType::Const(ConstStatement {
+                attributes: Vec::new(),
+                exported: false,
+                name,
+                type_,
+                value,
+            }),
+            start.span.start..end,
+        ))
+    }
+
+    /// Parses attributes and attaches them to the declaration parsed by `next`.
+    fn attributed(
+        tokens: &mut TokenStream,
+        next: fn(&mut TokenStream) -> CompilerResult<Statement>,
+    ) -> CompilerResult<Statement> {
+        let start = tokens.peek().cloned().unwrap_or_else(Token::invalid);
+        let checkpoint = tokens.checkpoint();
+        let attributes = Attribute::parse_many(tokens)?;
+
+        let mut statement = next(tokens)?;
+        tokens.attach(checkpoint);
+
+        match &mut statement.kind {
+            StatementType::Const(ConstStatement { attributes: target, .. })
+            | StatementType::Enum(EnumStatement { attributes: target, .. })
+            | StatementType::Function(FunctionStatement { attributes: target, .. })
+            | StatementType::Let(LetStatement { attributes: target, .. })
+            | StatementType::Struct(StructStatement { attributes: target, .. }) => {
+                *target = attributes;
+            }
+            _ => {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::InvalidAttributeTarget,
+                    error_message: String::from("Attributes can only be placed on declarations"),
+                    span_message: String::from("This is not a declaration"),
+                    token: Token {
+                        span: start.span.start..statement.span.end,
+                        ..start
+                    },
+                    help: Some(String::from(
+                        "Expected one of: \n- function\n- struct\n- enum\n- let\n- const",
+                    )),
+                    info: None,
+                }));
+            }
+        }
+
+        statement.span = start.span.start..statement.span.end;
+        Ok(statement)
+    }
+
+    fn import(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Import);
+        let start = tokens.unshift_expect(TokenType::Import)?.clone();
+
+        let mut names = None;
+
+        if tokens.next_matches(TokenType::LeftBrace) {
+            tokens.start_node(SyntaxKind::ImportNames);
+            tokens.unshift_expect(TokenType::LeftBrace)?;
+            let mut imported = Vec::new();
+
+            while !tokens.next_matches(TokenType::RightBrace) {
+                let name = tokens.unshift_expect(TokenType::Identifier)?;
+                imported.push(ImportedName {
+                    name: name.value.clone(),
+                    span: name.span.clone(),
+                });
+
+                // A comma directly before the closing brace is allowed
+                if !tokens.next_matches(TokenType::RightBrace) {
+                    tokens.unshift_expect(TokenType::Comma)?;
+                }
+            }
+
+            tokens.unshift_expect(TokenType::RightBrace)?;
+            tokens.finish_node();
+
+            // `from` is only special here, so it stays usable as a name everywhere else
+            let from = tokens.unshift_expect(TokenType::Identifier)?;
+            if from.value != "from" {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::UnexpectedToken,
+                    error_message: format!("Expected 'from', got '{}'", from.value),
+                    span_message: String::from("Expected 'from' after the imported names"),
+                    token: from.clone(),
+                    help: Some(String::from("Imports look like `import { a, b } from \"path.lit\";`")),
+                    info: None,
+                }));
+            }
+            names = Some(imported);
+        }
+
+        let path = tokens.unshift_expect(TokenType::StringLiteral)?.clone();
+        let end = tokens.unshift_expect(TokenType::Semicolon)?.span.end;
+        tokens.finish_node();
+
+        Ok(Statement::new(
+            StatementType::Import(ImportStatement {
+                path: path.value,
+                path_span: path.span,
+                names,
+            }),
+            start.span.start..end,
+        ))
+    }
+
+    fn export(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        let checkpoint = tokens.checkpoint();
+        let start = tokens.unshift_expect(TokenType::Export)?.clone();
+
+        let mut statement = Statement::parse(tokens)?;
+        tokens.attach(checkpoint);
+
+        match &mut statement.kind {
+            StatementType::Const(ConstStatement { exported, .. })
+            | StatementType::Enum(EnumStatement { exported, .. })
+            | StatementType::Function(FunctionStatement { exported, .. })
+            | StatementType::Let(LetStatement { exported, .. })
+            | StatementType::Struct(StructStatement { exported, .. }) => *exported = true,
+            _ => {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::InvalidExport,
+                    error_message: String::from("Only declarations can be exported"),
+                    span_message: String::from("This is not a declaration"),
+                    token: Token {
+                        span: statement.span.clone(),
+                        ..start
+                    },
+                    help: Some(String::from(
+                        "Expected one of: \n- function\n- struct\n- enum\n- let\n- const",
+                    )),
+                    info: None,
+                }))
+            }
+        }
+
+        statement.span = start.span.start..statement.span.end;
+        Ok(statement)
+    }
+
+    /// Parses an optional `: type` annotation.
+    fn type_annotation(tokens: &mut TokenStream) -> CompilerResult<Option<TypeExpr>> {
+        if tokens.unshift_if(TokenType::Colon).is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(TypeExpr::parse(tokens)?))
+    }
+
+    fn function(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Function);
         let start = tokens.unshift_expect(TokenType::Function)?.clone();
+        let generator = tokens.unshift_if(TokenType::Asterisk).is_some();
 
         let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
 
+        let mut type_parameters = Vec::new();
+
+        if tokens.next_matches(TokenType::LessThan) {
+            tokens.start_node(SyntaxKind::TypeParameterList);
+            tokens.unshift_expect(TokenType::LessThan)?;
+
+            while !tokens.next_matches(TokenType::GreaterThan) {
+                type_parameters.push(Statement::type_parameter(tokens)?);
+
+                if !tokens.next_matches(TokenType::GreaterThan) {
+                    tokens.unshift_expect(TokenType::Comma)?;
+                }
+            }
+
+            tokens.unshift_expect(TokenType::GreaterThan)?;
+            tokens.finish_node();
+        }
+
+        tokens.start_node(SyntaxKind::ParameterList);
         tokens.unshift_expect(TokenType::LeftParen)?;
 
         let mut parameters = Vec::new();
 
         while !tokens.next_matches(TokenType::RightParen) {
-            let parameter_name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
-            let mut parameter_type = ParameterType::Unknown;
-            if tokens.next_matches(TokenType::Colon) {
-                tokens.unshift_expect(TokenType::Colon)?;
-                parameter_type = tokens
-                    .unshift_expect(TokenType::Identifier)?
-                    .value
-                    .clone()
-                    .into();
-
-                if parameter_type == ParameterType::Unknown {
-                    return Err(CompilerError {
-                        error_code: ErrorCode::InvalidParameterType,
-                        error_message: format!(
-                            "Invalid parameter type: got '{}'",
-                            tokens.peek().unwrap().value
-                        ),
-                        span_message: String::from(""),
-                        token: tokens.unshift().unwrap().clone(),
-                        help: Some(String::from(
-                            "Expected one of: \n- int\n- bool\n- string",
-                        )),
-                        info: None,
-                    });
+            parameters.push(Statement::parameter(tokens)?);
+
+            if !tokens.next_matches(TokenType::RightParen) {
+                tokens.unshift_expect(TokenType::Comma)?;
+            }
+        }
+
+        tokens.unshift_expect(TokenType::RightParen)?;
+        tokens.finish_node();
+
+        let return_type = Statement::type_annotation(tokens)?;
+
+        let body = Statement::block(tokens)?;
+        tokens.finish_node();
+
+        Ok(Statement::new(
+            StatementType::Function(FunctionStatement {
+                attributes: Vec::new(),
+                exported: false,
+                name,
+                generator,
+                type_parameters,
+                parameters,
+                return_type,
+                body: match body.kind {
+                    StatementType::Block(block) => block,
+                    _ => unreachable!(),
+                },
+            }),
+            start.span.start..body.span.end,
+        ))
+    }
+
+    /// Parses a type parameter with its constraints, such as `T` or `T: Ord + Eq`.
+    fn type_parameter(tokens: &mut TokenStream) -> CompilerResult<TypeParameter> {
+        tokens.start_node(SyntaxKind::TypeParameter);
+        let name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        let mut constraints = Vec::new();
+        let mut end = name.span.end;
+
+        if tokens.unshift_if(TokenType::Colon).is_some() {
+            loop {
+                let constraint = tokens.unshift_expect(TokenType::Identifier)?;
+                constraints.push(constraint.value.clone());
+                end = constraint.span.end;
+
+                if tokens.unshift_if(TokenType::Plus).is_none() {
+                    break;
                 }
             }
+        }
+        tokens.finish_node();
+
+        Ok(TypeParameter {
+            name: name.value,
+            constraints,
+            span: name.span.start..end,
+        })
+    }
+
+    /// Parses a single parameter: `name`, `name: type`, `name: type = default` or `...name: type`.
+    pub(crate) fn parameter(tokens: &mut TokenStream) -> CompilerResult<Parameter> {
+        tokens.start_node(SyntaxKind::Parameter);
+        let ellipsis = tokens.unshift_if(TokenType::DotDotDot).cloned();
+        let name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        let type_ = Statement::type_annotation(tokens)?;
+        let default = match tokens.unshift_if(TokenType::Equals) {
+            Some(_) => Some(Expression::parse(tokens)?),
+            None => None,
+        };
+        tokens.finish_node();
+
+        let start = ellipsis.as_ref().map_or(name.span.start, |ellipsis| ellipsis.span.start);
+        let end = match (&default, &type_) {
+            (Some(default), _) => default.span.end,
+            (None, Some(type_)) => type_.span.end,
+            (None, None) => name.span.end,
+        };
+
+        Ok(Parameter {
+            name: name.value,
+            type_,
+            default,
+            variadic: ellipsis.is_some(),
+            span: start..end,
+        })
+    }
+
+    fn struct_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Struct);
+        let start = tokens.unshift_expect(TokenType::Struct)?.clone();
+
+        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
+
+        tokens.unshift_expect(TokenType::LeftBrace)?;
+        let fields =
+            Statement::fields(tokens, TokenType::RightBrace, &format!("struct '{}'", name))?;
+        let end = tokens.unshift_expect(TokenType::RightBrace)?.span.end;
+        tokens.finish_node();
+
+        Ok(Statement::new(
+            StatementType::Struct(StructStatement {
+                attributes: Vec::new(),
+                exported: false,
+                name,
+                fields,
+            }),
+            start.span.start..end,
+        ))
+    }
+
+    /// Parses `name: type` fields up to the `closing` token, which is left for the caller.
+    /// `owner` names what the fields belong to in errors, such as `struct 'User'`.
+    fn fields(
+        tokens: &mut TokenStream,
+        closing: TokenType,
+        owner: &str,
+    ) -> CompilerResult<Vec<StructField>> {
+        let mut fields: Vec<StructField> = Vec::new();
+
+        while !tokens.next_matches(closing) {
+            tokens.start_node(SyntaxKind::StructField);
+            let field_name = tokens.unshift_expect(TokenType::Identifier)?.clone();
+
+            if fields.iter().any(|field| field.name == field_name.value) {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::DuplicateStructField,
+                    error_message: format!("Duplicate field '{}' in {}", field_name.value, owner),
+                    span_message: format!("This field was already declared in this {}", owner),
+                    token: field_name,
+                    help: Some(String::from("Remove or rename one of the duplicate fields")),
+                    info: None,
+                }));
+            }
+
+            tokens.unshift_expect(TokenType::Colon)?;
+            let type_ = TypeExpr::parse(tokens)?;
+            tokens.finish_node();
 
-            parameters.push(Parameter {
-                name: parameter_name,
-                type_: parameter_type,
+            fields.push(StructField {
+                name: field_name.value,
+                span: field_name.span.start..type_.span.end,
+                type_,
             });
 
-            if !tokens.next_matches(TokenType::RightParen) {
+            // A comma directly before the closing token is allowed
+            if !tokens.next_matches(closing) {
                 tokens.unshift_expect(TokenType::Comma)?;
             }
         }
 
-        tokens.unshift_expect(TokenType::RightParen)?;
+        Ok(fields)
+    }
 
-        let mut return_type = ParameterType::Unknown;
+    fn enum_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Enum);
+        let start = tokens.unshift_expect(TokenType::Enum)?.clone();
 
-        if tokens.next_matches(TokenType::Colon) {
-            tokens.unshift_expect(TokenType::Colon)?;
-            return_type = tokens
-                .unshift_expect(TokenType::Identifier)?
-                .value
-                .clone()
-                .into();
-
-            if return_type == ParameterType::Unknown {
-                return Err(CompilerError {
-                    error_code: ErrorCode::InvalidReturnType,
+        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
+
+        tokens.unshift_expect(TokenType::LeftBrace)?;
+
+        let mut variants: Vec<EnumVariant> = Vec::new();
+
+        while !tokens.next_matches(TokenType::RightBrace) {
+            tokens.start_node(SyntaxKind::EnumVariant);
+            let variant = tokens.unshift_expect(TokenType::Identifier)?.clone();
+
+            if variants
+                .iter()
+                .any(|declared| declared.name == variant.value)
+            {
+                return Err(Box::new(CompilerError {
+                    error_code: ErrorCode::DuplicateEnumVariant,
                     error_message: format!(
-                        "Invalid return type: got '{}'",
-                        tokens.peek().unwrap().value
+                        "Duplicate variant '{}' in enum '{}'",
+                        variant.value, name
                     ),
-                    span_message: String::from(""),
-                    token: tokens.unshift().unwrap().clone(),
+                    span_message: String::from("This variant was already declared in this enum"),
+                    token: variant,
                     help: Some(String::from(
-                        "Expected one of: \n- int\n- bool\n- string",
+                        "Remove or rename one of the duplicate variants",
                     )),
                     info: None,
-                });
+                }));
+            }
+
+            let mut fields = Vec::new();
+            let mut end = variant.span.end;
+
+            if tokens.unshift_if(TokenType::LeftParen).is_some() {
+                let owner = format!("variant '{}.{}'", name, variant.value);
+                fields = Statement::fields(tokens, TokenType::RightParen, &owner)?;
+                end = tokens.unshift_expect(TokenType::RightParen)?.span.end;
+            }
+            tokens.finish_node();
+
+            variants.push(EnumVariant {
+                name: variant.value,
+                fields,
+                span: variant.span.start..end,
+            });
+
+            // A comma directly before the closing brace is allowed
+            if !tokens.next_matches(TokenType::RightBrace) {
+                tokens.unshift_expect(TokenType::Comma)?;
             }
         }
 
-        let body = Statement::block(tokens)?;
+        let end = tokens.unshift_expect(TokenType::RightBrace)?.span.end;
+        tokens.finish_node();
 
-        Ok((
-            StatementType::Function(FunctionStatement {
+        Ok(Statement::new(
+            StatementType::Enum(EnumStatement {
+                attributes: Vec::new(),
+                exported: false,
                 name,
-                parameters,
-                return_type,
-                body: match body.0 {
-                    StatementType::Block(block) => block.statements,
-                    _ => unreachable!(),
-                },
+                variants,
             }),
-            start.span.start..body.1.end,
+            start.span.start..end,
         ))
     }
 
-    fn statement(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn statement(tokens: &mut TokenStream) -> CompilerResult<Statement> {
         if let Some(token) = tokens.peek() {
             match token.type_ {
                 TokenType::For => Statement::for_(tokens),
-                TokenType::If => Statement::if_(tokens),
                 TokenType::Return => Statement::return_(tokens),
+                TokenType::Try => Statement::try_(tokens),
                 TokenType::Break => Statement::break_(tokens),
                 TokenType::Continue => Statement::continue_(tokens),
                 TokenType::While => Statement::while_(tokens),
                 TokenType::LeftBrace => Statement::block(tokens),
-                TokenType::At => Statement::intrinsic(tokens),
                 _ => Statement::expression(tokens),
             }
         } else {
-            Err(CompilerError {
+            Err(Box::new(CompilerError {
                 error_code: ErrorCode::NoTokensLeft,
-                error_message: format!("Expected start of statement, got None"),
+                error_message: String::from("Expected start of statement, got None"),
                 span_message: String::from(""),
                 token: Token::invalid(),
                 help: Some(String::from(
                     "Expected one of: - TODO: List expected tokens",
                 )),
                 info: None,
-            })
+            }))
         }
     }
 
-    fn for_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
-        let mut initializer = None;
-        let mut condition = None;
-        let mut increment = None;
-
+    fn for_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::For);
         let start = tokens.unshift_expect(TokenType::For)?.clone();
 
-        // TODO: Differentiate between `for [init]; [cond]; [incr]` and `for [item] in [iter]`
-        //       For now, only support `for [init]; [cond]; [incr]`
-
-        if !tokens.next_matches(TokenType::Semicolon) {
-            initializer = Some(Expression::parse(tokens)?);
-        }
-
-        tokens.unshift_expect(TokenType::Semicolon)?;
+        let binding = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        tokens.unshift_expect(TokenType::In)?;
 
-        if !tokens.next_matches(TokenType::Semicolon) {
-            condition = Some(Expression::parse(tokens)?);
-        }
-
-        tokens.unshift_expect(TokenType::Semicolon)?;
-
-        if !tokens.next_matches(TokenType::LeftBrace) {
-            increment = Some(Expression::parse(tokens)?);
-        }
+        let iterable = Expression::condition(tokens, "in")?;
 
         let body = Statement::block(tokens)?;
-
-        Ok((StatementType::Unknown, start.span.start..body.1.end))
-    }
-
-    fn if_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
-        let condition = Expression::parse(tokens)?;
-        let body = Statement::block(tokens)?;
-
-        let mut else_body = None;
-
-        if tokens.next_matches(TokenType::Else) {
-            // TODO: Support `else if` statements
-            else_body = Some(Statement::block(tokens)?);
-        }
-
-        Ok((
-            StatementType::Unknown,
-            condition.span.start..else_body.unwrap_or(body).1.end,
+        tokens.finish_node();
+
+        Ok(Statement::new(
+            StatementType::For(ForStatement {
+                binding: binding.value,
+                binding_span: binding.span,
+                iterable,
+                body: match body.kind {
+                    StatementType::Block(block) => block,
+                    _ => unreachable!(),
+                },
+            }),
+            start.span.start..body.span.end,
         ))
     }
 
-    fn return_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn return_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Return);
         let start = tokens.unshift_expect(TokenType::Return)?.clone();
         let mut value = None;
 
         if !tokens.next_matches(TokenType::Semicolon) {
             value = Some(Expression::parse(tokens)?);
         }
-        let end = tokens.unshift_expect(TokenType::Semicolon)?;
+        let end = tokens.unshift_expect(TokenType::Semicolon)?.span.end;
+        tokens.finish_node();
 
-        Ok((
+        Ok(Statement::new(
             StatementType::Return(ReturnStatement { value }),
-            start.span.start..end.span.end,
+            start.span.start..end,
         ))
     }
 
-    fn break_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn try_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Try);
+        let start = tokens.unshift_expect(TokenType::Try)?.clone();
+        let body = Statement::block(tokens)?;
+
+        tokens.unshift_expect(TokenType::Catch)?;
+        tokens.unshift_expect(TokenType::LeftParen)?;
+        let binding = tokens.unshift_expect(TokenType::Identifier)?.clone();
+        tokens.unshift_expect(TokenType::RightParen)?;
+
+        let handler = Statement::block(tokens)?;
+        let end = handler.span.end;
+        tokens.finish_node();
+
+        match (body.kind, handler.kind) {
+            (StatementType::Block(body), StatementType::Block(handler)) => Ok(Statement::new(
+                StatementType::Try(TryStatement {
+                    body,
+                    binding: binding.value,
+                    binding_span: binding.span,
+                    handler,
+                }),
+                start.span.start..end,
+            )),
+            _ => unreachable!(),
+        }
+    }
+
+    fn break_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Break);
         let start = tokens.unshift_expect(TokenType::Break)?.clone();
         tokens.unshift_expect(TokenType::Semicolon)?;
-        Ok((StatementType::Unknown, start.span.start..start.span.end))
+        tokens.finish_node();
+        Ok(Statement::new(
+            StatementType::Unknown,
+            start.span.start..start.span.end,
+        ))
     }
 
-    fn continue_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn continue_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Continue);
         let start = tokens.unshift_expect(TokenType::Continue)?.clone();
         tokens.unshift_expect(TokenType::Semicolon)?;
-        Ok((StatementType::Unknown, start.span.start..start.span.end))
+        tokens.finish_node();
+        Ok(Statement::new(
+            StatementType::Unknown,
+            start.span.start..start.span.end,
+        ))
     }
 
-    fn while_(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    fn while_(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::While);
         let start = tokens.unshift_expect(TokenType::While)?.clone();
-        let condition = Expression::parse(tokens)?;
+        let _condition = Expression::parse(tokens)?;
         let body = Statement::block(tokens)?;
+        tokens.finish_node();
 
-        Ok((StatementType::Unknown, start.span.start..body.1.end))
+        Ok(Statement::new(
+            StatementType::Unknown,
+            start.span.start..body.span.end,
+        ))
     }
 
-    fn block(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
+    pub(crate) fn block(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        tokens.start_node(SyntaxKind::Block);
         let start = tokens.unshift_expect(TokenType::LeftBrace)?.span.start;
 
         let mut statements = Vec::new();
+        let mut tail = None;
 
         while !tokens.next_matches(TokenType::RightBrace) {
-            statements.push(Statement::parse(tokens)?);
+            if !Statement::starts_expression(tokens) {
+                let statement = Statement::parse(tokens)?;
+
+                // A nested block at the end is the value of the outer block
+                if let StatementType::Block(block) = statement.kind {
+                    if tokens.next_matches(TokenType::RightBrace) {
+                        tail = Some(Box::new(Expression {
+                            id: statement.id,
+                            kind: ExpressionType::Block(block),
+                            span: statement.span,
+                        }));
+                        break;
+                    }
+
+                    statements.push(Statement {
+                        kind: StatementType::Block(block),
+                        ..statement
+                    });
+                } else {
+                    statements.push(statement);
+                }
+
+                continue;
+            }
+
+            let checkpoint = tokens.checkpoint();
+            let expression = Statement::leading_expression(tokens)?;
+            tokens.start_node_at(checkpoint, SyntaxKind::ExpressionStatement);
+
+            // The last expression without a semicolon is the value of the block
+            if tokens.next_matches(TokenType::RightBrace) {
+                tokens.finish_node();
+                tail = Some(Box::new(expression));
+                break;
+            }
+
+            statements.push(Statement::terminate(tokens, expression)?);
+            tokens.finish_node();
         }
 
-        let end = tokens.unshift_expect(TokenType::RightBrace)?;
+        let end = tokens.unshift_expect(TokenType::RightBrace)?.span.end;
+        tokens.finish_node();
 
-        Ok((
-            StatementType::Block(BlockStatement { statements }),
-            start..end.span.end,
+        Ok(Statement::new(
+            StatementType::Block(BlockStatement { statements, tail }),
+            start..end,
         ))
     }
 
-    fn expression(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
-        let expr = Expression::parse(tokens)?;
-        let end = tokens.unshift_expect(TokenType::Semicolon)?;
+    /// Whether the next statement is an expression statement, rather than a declaration or keyword statement.
+    fn starts_expression(tokens: &mut TokenStream) -> bool {
+        !Attribute::starts(tokens)
+            && !tokens.next_matches_any(&[
+                TokenType::Break,
+                TokenType::Const,
+                TokenType::Continue,
+                TokenType::Enum,
+                TokenType::For,
+                TokenType::Function,
+                TokenType::Hash,
+                TokenType::LeftBrace,
+                TokenType::Let,
+                TokenType::Return,
+                TokenType::Struct,
+                TokenType::Try,
+                TokenType::While,
+            ])
+    }
 
-        Ok((StatementType::Unknown, expr.span.start..end.span.end))
+    fn expression(tokens: &mut TokenStream) -> CompilerResult<Statement> {
+        let checkpoint = tokens.checkpoint();
+        let expression = Statement::leading_expression(tokens)?;
+        tokens.start_node_at(checkpoint, SyntaxKind::ExpressionStatement);
+        let statement = Statement::terminate(tokens, expression)?;
+        tokens.finish_node();
+        Ok(statement)
     }
 
-    fn intrinsic(tokens: &mut TokenStream) -> CompilerResult<SpannedStatement> {
-        let start = tokens.unshift_expect(TokenType::At)?.clone();
-        let name = tokens.unshift_expect(TokenType::Identifier)?.value.clone();
-        tokens.unshift_expect(TokenType::LeftParen)?;
-        let mut arguments = Vec::new();
-        while !tokens.next_matches(TokenType::RightParen) {
-            arguments.push(Expression::parse(tokens)?);
-            if !tokens.next_matches(TokenType::RightParen) {
-                tokens.unshift_expect(TokenType::Comma)?;
-            }
+    /// Parses the expression at the start of a statement.
+    ///
+    /// An `if` or `match` there ends at its closing brace, so `if a { b(); } (c)` is not read
+    /// as a call.
+    fn leading_expression(tokens: &mut TokenStream) -> CompilerResult<Expression> {
+        if tokens.next_matches(TokenType::If) {
+            Expression::if_(tokens)
+        } else if tokens.next_matches(TokenType::Match) {
+            Expression::match_(tokens)
+        } else {
+            Expression::parse(tokens)
         }
-        tokens.unshift_expect(TokenType::RightParen)?;
-
-        let end = tokens.unshift_expect(TokenType::Semicolon)?;
+    }
 
-        Ok((
-            StatementType::Intrinsic(IntrinsicStatement { name, arguments }),
-            start.span.start..end.span.end,
-        ))
+    /// Finishes an expression statement. A semicolon is optional after an `if` or `match`.
+    fn terminate(tokens: &mut TokenStream, expression: Expression) -> CompilerResult<Statement> {
+        let end = match expression.kind {
+            ExpressionType::If(_) | ExpressionType::Match(_) => tokens
+                .unshift_if(TokenType::Semicolon)
+                .map_or(expression.span.end, |semicolon| semicolon.span.end),
+            _ => tokens.unshift_expect(TokenType::Semicolon)?.span.end,
+        };
+        let span = expression.span.start..end;
+
+        match expression.kind {
+            ExpressionType::Intrinsic(intrinsic) => {
+                Ok(Statement::new(StatementType::Intrinsic(intrinsic), span))
+            }
+            _ => Ok(Statement::new(StatementType::Expression(expression), span)),
+        }
     }
 }
@@ -1,13 +1,12 @@
-use std::iter::Peekable;
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use crate::lang::util::vec::{Unshift, UnshiftExpect };
-use super::{CompilerError, CompilerResult};
+use super::{cst::{sink::SyntaxSink, Checkpoint, SyntaxKind}, CompilerError, CompilerResult};
 
 pub(crate) type Span = std::ops::Range<usize>;
-pub(crate) type TokenStream<'a> = Peekable<std::slice::IterMut<'a, Token>>;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum TokenType {
     // Special tokens
     Unknown,
@@ -16,37 +15,72 @@ pub enum TokenType {
     Ampersand,
     Asterisk,
     At,
+    Bang,
     Colon,
     Comma,
+    Dot,
     Equals,
+    GreaterThan,
+    Hash,
     LeftBrace,
+    LeftBracket,
     LeftParen,
+    LessThan,
+    Minus,
     Pipe,
+    Plus,
+    Question,
     RightBrace,
+    RightBracket,
     RightParen,
     Semicolon,
     Slash,
 
     // Multi-char tokens
     AmpersandAmpersand,
+    Arrow,
     BangEquals,
+    DotDot,
+    DotDotDot,
+    DotDotEquals,
     EqualsEquals,
+    FatArrow,
     PipePipe,
+    QuestionDot,
+    QuestionLeftBracket,
+    QuestionQuestion,
 
     // keywords
     Break,
+    Catch,
+    Const,
     Continue,
     Else,
+    Enum,
+    Export,
     For,
+    // No longer produced, as `from` is only matched in imports. Kept so documents in the
+    // current format version keep the same token kinds.
+    From,
     Function,
     Let,
     If,
+    Import,
+    In,
+    Macro,
+    Match,
+    Mut,
+    Null,
     Return,
+    Struct,
+    Try,
     While,
+    Yield,
 
     // N-char tokens
     Identifier,
     IntegerLiteral,
+    StringLiteral,
 }
 
 impl From<TokenType> for String {
@@ -61,12 +95,135 @@ impl std::fmt::Display for TokenType {
     }
 }
 
+/// The tokens left to parse. Looking ahead indexes into the tokens, so it never copies the stream.
+///
+/// The parser marks where syntax nodes start and finish as it goes. Those marks only build
+/// something when the stream has a [`SyntaxSink`], which turns them into a concrete syntax tree.
+#[derive(Debug)]
+pub(crate) struct TokenStream<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    /// Where the stream ends, which [`TokenStream::limit`] can move closer.
+    end: usize,
+    syntax: Option<SyntaxSink>,
+}
+
+impl<'a> TokenStream<'a> {
+    pub(crate) fn new(tokens: &'a [Token]) -> TokenStream<'a> {
+        TokenStream {
+            tokens,
+            position: 0,
+            end: tokens.len(),
+            syntax: None,
+        }
+    }
+
+    /// A stream that builds a concrete syntax tree in `syntax` while it is parsed.
+    pub(crate) fn with_syntax(tokens: &'a [Token], syntax: SyntaxSink) -> TokenStream<'a> {
+        TokenStream {
+            syntax: Some(syntax),
+            ..TokenStream::new(tokens)
+        }
+    }
+
+    pub(crate) fn into_syntax(self) -> Option<SyntaxSink> {
+        self.syntax
+    }
+
+    /// Returns the next token without consuming it.
+    pub(crate) fn peek(&self) -> Option<&'a Token> {
+        self.peek_nth(0)
+    }
+
+    /// Returns the token `n` ahead without consuming anything, where `0` is the next token.
+    pub(crate) fn peek_nth(&self, n: usize) -> Option<&'a Token> {
+        self.as_slice().get(n)
+    }
+
+    /// The tokens that haven't been consumed yet.
+    pub(crate) fn as_slice(&self) -> &'a [Token] {
+        &self.tokens[self.position..self.end]
+    }
+
+    /// Ends the stream `length` tokens from here, until [`TokenStream::unlimit`] is called with
+    /// the end this returns.
+    pub(crate) fn limit(&mut self, length: usize) -> usize {
+        std::mem::replace(&mut self.end, self.position + length)
+    }
+
+    pub(crate) fn unlimit(&mut self, end: usize) {
+        self.end = end;
+    }
+
+    /// Starts a syntax node at the next token.
+    pub(crate) fn start_node(&mut self, kind: SyntaxKind) {
+        let next = self.peek();
+        if let Some(syntax) = &mut self.syntax {
+            syntax.start_node(kind, next);
+        }
+    }
+
+    /// Marks the next token, so a node can start there once the parser knows what it is, as
+    /// with the left side of a binary expression.
+    pub(crate) fn checkpoint(&mut self) -> Checkpoint {
+        let next = self.peek();
+        match &mut self.syntax {
+            Some(syntax) => syntax.checkpoint(next),
+            None => Checkpoint::default(),
+        }
+    }
+
+    pub(crate) fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        if let Some(syntax) = &mut self.syntax {
+            syntax.start_node_at(checkpoint, kind);
+        }
+    }
+
+    pub(crate) fn finish_node(&mut self) {
+        if let Some(syntax) = &mut self.syntax {
+            syntax.finish_node();
+        }
+    }
+
+    /// Moves what was parsed since `checkpoint` into the node finished last, such as the
+    /// attributes or `export` before a declaration.
+    pub(crate) fn attach(&mut self, checkpoint: Checkpoint) {
+        if let Some(syntax) = &mut self.syntax {
+            syntax.attach(checkpoint);
+        }
+    }
+
+    /// How many syntax nodes are open.
+    pub(crate) fn syntax_depth(&self) -> usize {
+        self.syntax.as_ref().map_or(0, SyntaxSink::depth)
+    }
+}
+
+impl<'a> Iterator for TokenStream<'a> {
+    type Item = &'a Token;
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.peek()?;
+        self.position += 1;
+        if let Some(syntax) = &mut self.syntax {
+            syntax.token(token);
+        }
+        Some(token)
+    }
+}
+
+impl<'a> Unshift<&'a Token> for TokenStream<'a> {
+    fn unshift(&mut self) -> Option<&'a Token> {
+        self.next()
+    }
+}
+
 impl<'a> UnshiftExpect<Token, TokenType, CompilerError> for TokenStream<'a>
 {
-    fn unshift_expect(&mut self, expected: TokenType) -> CompilerResult<&mut Token> {
+    fn unshift_expect(&mut self, expected: TokenType) -> CompilerResult<&Token> {
         match self.peek() {
             Some(token) if token.type_ == expected => Ok(self.unshift().unwrap()),
-            Some(token) => Err(CompilerError {
+            Some(token) => Err(Box::new(CompilerError {
                 error_code: crate::lang::compiler::ErrorCode::UnshiftedUnexpectedToken,
                 error_message: format!("Expected '{}', got {}", expected, token),
                 span_message: String::from(""),
@@ -75,8 +232,8 @@ impl<'a> UnshiftExpect<Token, TokenType, CompilerError> for TokenStream<'a>
                     "Expected one of: - TODO: List expected tokens",
                 )),
                 info: None,
-            }),
-            None => Err(CompilerError {
+            })),
+            None => Err(Box::new(CompilerError {
                 error_code: crate::lang::compiler::ErrorCode::NoTokensLeft,
                 error_message: format!("Expected token {}, got None", expected),
                 span_message: String::from(""),
@@ -85,14 +242,14 @@ impl<'a> UnshiftExpect<Token, TokenType, CompilerError> for TokenStream<'a>
                     "Expected one of: - TODO: List expected tokens",
                 )),
                 info: None,
-            }),
+            })),
         }
     }
 
-    fn unshift_expect_any(&mut self, expected: &[TokenType]) -> CompilerResult<&mut Token> {
+    fn unshift_expect_any(&mut self, expected: &[TokenType]) -> CompilerResult<&Token> {
         match self.peek() {
             Some(token) if expected.contains(&token.type_) => Ok(self.unshift().unwrap()),
-            Some(token) => Err(CompilerError {
+            Some(token) => Err(Box::new(CompilerError {
                 error_code: crate::lang::compiler::ErrorCode::UnshiftedUnexpectedToken,
                 error_message: format!("Expected one of: {:?}, got {}", expected, token),
                 span_message: String::from(""),
@@ -101,8 +258,8 @@ impl<'a> UnshiftExpect<Token, TokenType, CompilerError> for TokenStream<'a>
                     "Expected one of: - TODO: List expected tokens",
                 )),
                 info: None,
-            }),
-            None => Err(CompilerError {
+            })),
+            None => Err(Box::new(CompilerError {
                 error_code: crate::lang::compiler::ErrorCode::NoTokensLeft,
                 error_message: format!("Expected token {:?}, got None", expected),
                 span_message: String::from(""),
@@ -111,11 +268,11 @@ impl<'a> UnshiftExpect<Token, TokenType, CompilerError> for TokenStream<'a>
                     "Expected one of: - TODO: List expected tokens",
                 )),
                 info: None,
-            }),
+            })),
         }
     }
 
-    fn unshift_if(&mut self, token_type: TokenType) -> Option<&mut Token> {
+    fn unshift_if(&mut self, token_type: TokenType) -> Option<&Token> {
         match self.peek() {
             Some(token) => {
                 if token.type_ == token_type {
@@ -141,15 +298,38 @@ impl<'a> UnshiftExpect<Token, TokenType, CompilerError> for TokenStream<'a>
             None => false,
         }
     }
+
+    fn nth_matches(&mut self, n: usize, token_type: TokenType) -> bool {
+        match self.peek_nth(n) {
+            Some(token) => token.type_ == token_type,
+            None => false,
+        }
+    }
 }
 
 pub(crate) static KEYWORDS: phf::Map<&'static str, TokenType> = phf::phf_map! {
+    "catch" => TokenType::Catch,
+    "const" => TokenType::Const,
+    "else" => TokenType::Else,
+    "enum" => TokenType::Enum,
+    "export" => TokenType::Export,
+    "for" => TokenType::For,
+    "if" => TokenType::If,
+    "import" => TokenType::Import,
+    "in" => TokenType::In,
     "let" => TokenType::Let,
+    "macro" => TokenType::Macro,
+    "match" => TokenType::Match,
+    "mut" => TokenType::Mut,
+    "null" => TokenType::Null,
     "function" => TokenType::Function,
     "return" => TokenType::Return,
+    "struct" => TokenType::Struct,
+    "try" => TokenType::Try,
+    "yield" => TokenType::Yield,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct Token {
     pub(crate) type_: TokenType,
     pub(crate) value: String,
@@ -0,0 +1,575 @@
+use core::fmt::Display;
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::lang::util::vec::UnshiftExpect;
+
+use super::{
+    cst::SyntaxKind,
+    token::{Span, Token, TokenStream, TokenType},
+    CompilerError, CompilerResult, ErrorCode,
+};
+
+/// A type as it was written in the source, such as `map<string, list<int>>?`.
+///
+/// Names are not resolved while parsing, so `TypeExpr` can refer to structs
+/// that are declared further down the file. The checker turns it into a
+/// [`ParameterType`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct TypeExpr {
+    pub(crate) type_: TypeExprType,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "value")]
+pub(crate) enum TypeExprType {
+    /// A built-in or user-defined type name with optional type arguments, such as `int` or `list<int>`.
+    Named(NamedType),
+    /// `T?`, a value of type `T` or `null`.
+    Optional(Box<TypeExpr>),
+    /// `(int, string)`. The empty tuple `()` is the unit type.
+    Tuple(Vec<TypeExpr>),
+    /// `fn(int) -> bool`. A missing return type means the function returns nothing.
+    Function(FunctionType),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct NamedType {
+    pub(crate) name: String,
+    pub(crate) name_span: Span,
+    pub(crate) arguments: Vec<TypeExpr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct FunctionType {
+    pub(crate) parameters: Vec<TypeExpr>,
+    pub(crate) return_type: Option<Box<TypeExpr>>,
+}
+
+/// A resolved type, after names in a [`TypeExpr`] have been looked up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ParameterType {
+    Unknown,
+    Integer,
+    Boolean,
+    String,
+    List(Box<ParameterType>),
+    /// `iter<T>`, the values produced one at a time by a generator.
+    Iterator(Box<ParameterType>),
+    Map(Box<ParameterType>, Box<ParameterType>),
+    /// A user-defined `struct`, referenced by name.
+    Struct(String),
+    /// A user-defined `enum`, referenced by name.
+    Enum(String),
+    /// A function value such as `fn(int, int) -> bool`.
+    Function(Vec<ParameterType>, Box<ParameterType>),
+    Optional(Box<ParameterType>),
+    Tuple(Vec<ParameterType>),
+    /// `result<T, E>`, either a value of type `T` or an error of type `E`.
+    Result(Box<ParameterType>, Box<ParameterType>),
+    /// A runtime error caught by `try { } catch (e) { }`, with a message and the span it was raised at.
+    Error,
+    /// A type parameter of a generic function, such as `T` in `function first<T>(xs: list<T>): T?`.
+    Parameter(String),
+}
+
+/// A requirement on a type parameter, such as `Ord` in `function max<T: Ord>(a: T, b: T): T`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Constraint {
+    /// Can be compared with `==` and `!=`.
+    Eq,
+    /// Can be compared with `<` and `>`, and with `==` and `!=`.
+    Ord,
+}
+
+impl Constraint {
+    pub(crate) const NAMES: [&'static str; 2] = ["Eq", "Ord"];
+
+    pub(crate) fn from_name(name: &str) -> Option<Constraint> {
+        match name {
+            "Eq" => Some(Constraint::Eq),
+            "Ord" => Some(Constraint::Ord),
+            _ => None,
+        }
+    }
+
+    /// Whether a constraint declared on a type parameter also provides `self`.
+    pub(crate) fn implied_by(&self, declared: Constraint) -> bool {
+        *self == declared || (*self == Constraint::Eq && declared == Constraint::Ord)
+    }
+
+    /// Whether values of a concrete type satisfy the constraint. Type parameters
+    /// satisfy it only if they declare it, which the checker looks up.
+    pub(crate) fn satisfied_by(&self, type_: &ParameterType) -> bool {
+        match (self, type_) {
+            (_, ParameterType::Unknown | ParameterType::Integer | ParameterType::String) => true,
+            (Constraint::Eq, ParameterType::Boolean) => true,
+            (Constraint::Eq, ParameterType::Optional(inner)) => self.satisfied_by(inner),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constraint::Eq => write!(f, "Eq"),
+            Constraint::Ord => write!(f, "Ord"),
+        }
+    }
+}
+
+impl From<String> for ParameterType {
+    fn from(string: String) -> Self {
+        match string.as_str() {
+            "int" => ParameterType::Integer,
+            "bool" => ParameterType::Boolean,
+            "string" => ParameterType::String,
+            "error" => ParameterType::Error,
+            _ => ParameterType::Unknown,
+        }
+    }
+}
+
+impl ParameterType {
+    /// Whether a value of type `actual` can be passed where `self` is expected.
+    ///
+    /// `Unknown` is compatible with every type, so values whose type can't be
+    /// worked out yet are never rejected.
+    pub(crate) fn accepts(&self, actual: &ParameterType) -> bool {
+        match (self, actual) {
+            (ParameterType::Unknown, _) | (_, ParameterType::Unknown) => true,
+            (ParameterType::List(expected), ParameterType::List(actual))
+            | (ParameterType::Iterator(expected), ParameterType::Iterator(actual)) => {
+                expected.accepts(actual)
+            }
+            (
+                ParameterType::Map(expected_key, expected_value),
+                ParameterType::Map(actual_key, actual_value),
+            ) => expected_key.accepts(actual_key) && expected_value.accepts(actual_value),
+            (ParameterType::Optional(expected), ParameterType::Optional(actual)) => {
+                expected.accepts(actual)
+            }
+            (ParameterType::Optional(expected), actual) => expected.accepts(actual),
+            (
+                ParameterType::Result(expected_value, expected_error),
+                ParameterType::Result(actual_value, actual_error),
+            ) => expected_value.accepts(actual_value) && expected_error.accepts(actual_error),
+            (expected, actual) => expected == actual,
+        }
+    }
+
+    /// Binds the type parameters in `self` to the matching parts of `actual`.
+    ///
+    /// Returns the name, earlier binding and new type of a type parameter that
+    /// is bound to two different types.
+    pub(crate) fn infer(
+        &self,
+        actual: &ParameterType,
+        bindings: &mut HashMap<String, ParameterType>,
+    ) -> Result<(), (String, ParameterType, ParameterType)> {
+        match (self, actual) {
+            (_, ParameterType::Unknown) => Ok(()),
+            (ParameterType::Parameter(name), actual) => match bindings.get(name) {
+                Some(bound) if !bound.accepts(actual) => {
+                    Err((name.clone(), bound.clone(), actual.clone()))
+                }
+                Some(_) => Ok(()),
+                None => {
+                    bindings.insert(name.clone(), actual.clone());
+                    Ok(())
+                }
+            },
+            (ParameterType::List(expected), ParameterType::List(actual))
+            | (ParameterType::Iterator(expected), ParameterType::Iterator(actual))
+            | (ParameterType::Optional(expected), ParameterType::Optional(actual)) => {
+                expected.infer(actual, bindings)
+            }
+            (ParameterType::Optional(expected), actual) => expected.infer(actual, bindings),
+            (
+                ParameterType::Map(expected_key, expected_value),
+                ParameterType::Map(actual_key, actual_value),
+            )
+            | (
+                ParameterType::Result(expected_key, expected_value),
+                ParameterType::Result(actual_key, actual_value),
+            ) => {
+                expected_key.infer(actual_key, bindings)?;
+                expected_value.infer(actual_value, bindings)
+            }
+            (
+                ParameterType::Function(expected_parameters, expected_return),
+                ParameterType::Function(actual_parameters, actual_return),
+            ) => {
+                for (expected, actual) in expected_parameters.iter().zip(actual_parameters) {
+                    expected.infer(actual, bindings)?;
+                }
+                expected_return.infer(actual_return, bindings)
+            }
+            (ParameterType::Tuple(expected), ParameterType::Tuple(actual)) => {
+                for (expected, actual) in expected.iter().zip(actual) {
+                    expected.infer(actual, bindings)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the bound type parameters in `self`. Unbound ones become `Unknown`.
+    pub(crate) fn substitute(&self, bindings: &HashMap<String, ParameterType>) -> ParameterType {
+        let all = |types: &[ParameterType]| {
+            types
+                .iter()
+                .map(|type_| type_.substitute(bindings))
+                .collect()
+        };
+
+        match self {
+            ParameterType::Parameter(name) => bindings
+                .get(name)
+                .cloned()
+                .unwrap_or(ParameterType::Unknown),
+            ParameterType::List(element) => {
+                ParameterType::List(Box::new(element.substitute(bindings)))
+            }
+            ParameterType::Iterator(element) => {
+                ParameterType::Iterator(Box::new(element.substitute(bindings)))
+            }
+            ParameterType::Map(key, value) => ParameterType::Map(
+                Box::new(key.substitute(bindings)),
+                Box::new(value.substitute(bindings)),
+            ),
+            ParameterType::Function(parameters, return_type) => {
+                ParameterType::Function(all(parameters), Box::new(return_type.substitute(bindings)))
+            }
+            ParameterType::Optional(inner) => {
+                ParameterType::Optional(Box::new(inner.substitute(bindings)))
+            }
+            ParameterType::Tuple(types) => ParameterType::Tuple(all(types)),
+            ParameterType::Result(value, error) => ParameterType::Result(
+                Box::new(value.substitute(bindings)),
+                Box::new(error.substitute(bindings)),
+            ),
+            type_ => type_.clone(),
+        }
+    }
+}
+
+impl Display for ParameterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |types: &[ParameterType]| {
+            types
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            ParameterType::Unknown => write!(f, "any"),
+            ParameterType::Integer => write!(f, "int"),
+            ParameterType::Boolean => write!(f, "bool"),
+            ParameterType::String => write!(f, "string"),
+            ParameterType::List(element) => write!(f, "list<{}>", element),
+            ParameterType::Iterator(element) => write!(f, "iter<{}>", element),
+            ParameterType::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            ParameterType::Struct(name) | ParameterType::Enum(name) => write!(f, "{}", name),
+            ParameterType::Function(parameters, return_type) => {
+                write!(f, "fn({}) -> {}", join(parameters), return_type)
+            }
+            ParameterType::Optional(inner) => write!(f, "{}?", inner),
+            ParameterType::Tuple(types) => write!(f, "({})", join(types)),
+            ParameterType::Result(value, error) => write!(f, "result<{}, {}>", value, error),
+            ParameterType::Error => write!(f, "error"),
+            ParameterType::Parameter(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+const TYPE_HELP: &str =
+    "Types look like `int`, `list<int>`, `map<string, int>`, `result<int, error>`, `int?`, `(int, string)` or `fn(int) -> int`";
+
+impl TypeExpr {
+    /// Parses a type, including type arguments, `?` suffixes, tuples and function types.
+    pub(crate) fn parse(tokens: &mut TokenStream) -> CompilerResult<TypeExpr> {
+        let checkpoint = tokens.checkpoint();
+        let mut type_ = TypeExpr::atom(tokens)?;
+
+        const QUESTION: [TokenType; 2] = [TokenType::Question, TokenType::QuestionQuestion];
+
+        while tokens.next_matches_any(&QUESTION) {
+            // `??` is lexed as one token, but `int??` is two optionals
+            tokens.start_node_at(checkpoint, SyntaxKind::OptionalType);
+            let question = tokens.unshift_expect_any(&QUESTION)?;
+            let depth = question.value.len();
+            let end = question.span.end;
+            tokens.finish_node();
+
+            for offset in (0..depth).rev() {
+                type_ = TypeExpr {
+                    span: type_.span.start..end - offset,
+                    type_: TypeExprType::Optional(Box::new(type_)),
+                };
+            }
+        }
+
+        Ok(type_)
+    }
+
+    fn atom(tokens: &mut TokenStream) -> CompilerResult<TypeExpr> {
+        if tokens.next_matches(TokenType::LeftParen) {
+            return TypeExpr::tuple(tokens);
+        }
+
+        let checkpoint = tokens.checkpoint();
+        let name = TypeExpr::expect(tokens, TokenType::Identifier, "a type name")?;
+
+        if name.value == "fn" && tokens.next_matches(TokenType::LeftParen) {
+            tokens.start_node_at(checkpoint, SyntaxKind::FunctionType);
+            let function = TypeExpr::function(tokens, name)?;
+            tokens.finish_node();
+            return Ok(function);
+        }
+
+        tokens.start_node_at(checkpoint, SyntaxKind::NamedType);
+        let mut arguments = Vec::new();
+        let mut end = name.span.end;
+
+        if tokens.next_matches(TokenType::LessThan) {
+            tokens.start_node(SyntaxKind::TypeArgumentList);
+            tokens.unshift_expect(TokenType::LessThan)?;
+            let context = format!("type arguments of '{}'", name.value);
+            let (types, close) = TypeExpr::list(tokens, (TokenType::GreaterThan, ">"), &context)?;
+            tokens.finish_node();
+
+            if types.is_empty() {
+                return Err(TypeExpr::malformed(
+                    close,
+                    format!(
+                        "'{}' needs at least one type argument between '<' and '>'",
+                        name.value
+                    ),
+                ));
+            }
+
+            arguments = types;
+            end = close.span.end;
+        }
+        tokens.finish_node();
+
+        Ok(TypeExpr {
+            type_: TypeExprType::Named(NamedType {
+                name: name.value,
+                name_span: name.span.clone(),
+                arguments,
+            }),
+            span: name.span.start..end,
+        })
+    }
+
+    /// Parses `(T)`, `(T, U)`, `(T,)` or `()`. A single type without a comma is just grouping.
+    fn tuple(tokens: &mut TokenStream) -> CompilerResult<TypeExpr> {
+        let checkpoint = tokens.checkpoint();
+        let start = TypeExpr::expect(tokens, TokenType::LeftParen, "'('")?;
+
+        let mut types = Vec::new();
+        let mut trailing_comma = false;
+
+        while !tokens.next_matches(TokenType::RightParen) {
+            types.push(TypeExpr::parse(tokens)?);
+            trailing_comma = false;
+
+            if !tokens.next_matches(TokenType::RightParen) {
+                TypeExpr::expect(tokens, TokenType::Comma, "',' or ')' in the tuple type")?;
+                trailing_comma = true;
+            }
+        }
+
+        let end = TypeExpr::expect(tokens, TokenType::RightParen, "')' to close the tuple type")?;
+
+        if types.len() == 1 && !trailing_comma {
+            let mut type_ = types.remove(0);
+            type_.span = start.span.start..end.span.end;
+            return Ok(type_);
+        }
+
+        tokens.start_node_at(checkpoint, SyntaxKind::TupleType);
+        tokens.finish_node();
+
+        Ok(TypeExpr {
+            type_: TypeExprType::Tuple(types),
+            span: start.span.start..end.span.end,
+        })
+    }
+
+    fn function(tokens: &mut TokenStream, name: Token) -> CompilerResult<TypeExpr> {
+        TypeExpr::expect(tokens, TokenType::LeftParen, "'(' after 'fn'")?;
+        let (parameters, close) = TypeExpr::list(
+            tokens,
+            (TokenType::RightParen, ")"),
+            "parameter types of 'fn'",
+        )?;
+
+        let mut end = close.span.end;
+        let mut return_type = None;
+
+        if tokens.unshift_if(TokenType::Arrow).is_some() {
+            let type_ = TypeExpr::parse(tokens)?;
+            end = type_.span.end;
+            return_type = Some(Box::new(type_));
+        }
+
+        Ok(TypeExpr {
+            type_: TypeExprType::Function(FunctionType {
+                parameters,
+                return_type,
+            }),
+            span: name.span.start..end,
+        })
+    }
+
+    /// Parses comma separated types up to and including `close`, allowing a trailing comma.
+    fn list(
+        tokens: &mut TokenStream,
+        (close, symbol): (TokenType, &str),
+        context: &str,
+    ) -> CompilerResult<(Vec<TypeExpr>, Token)> {
+        let mut types = Vec::new();
+
+        while !tokens.next_matches(close) {
+            types.push(TypeExpr::parse(tokens)?);
+
+            if !tokens.next_matches(close) {
+                let description = format!("',' or '{}' between the {}", symbol, context);
+                TypeExpr::expect(tokens, TokenType::Comma, &description)?;
+            }
+        }
+
+        let description = format!("'{}' to close the {}", symbol, context);
+        let end = TypeExpr::expect(tokens, close, &description)?;
+
+        Ok((types, end))
+    }
+
+    /// Like `unshift_expect`, but describes what was expected in terms of the type being parsed.
+    fn expect(
+        tokens: &mut TokenStream,
+        expected: TokenType,
+        description: &str,
+    ) -> CompilerResult<Token> {
+        tokens.unshift_expect(expected).cloned().map_err(|error| {
+            let message = format!(
+                "Malformed type: expected {}, got '{}'",
+                description, error.token.value
+            );
+            TypeExpr::malformed(error.token, message)
+        })
+    }
+
+    fn malformed(token: Token, error_message: String) -> Box<CompilerError> {
+        Box::new(CompilerError {
+            error_code: ErrorCode::MalformedType,
+            error_message,
+            span_message: String::from("The type is malformed here"),
+            token,
+            help: Some(String::from(TYPE_HELP)),
+            info: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::lexer::Lexer;
+    use crate::tests::before_each;
+
+    fn parse(input: &str) -> CompilerResult<TypeExpr> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        TypeExpr::parse(&mut lexer.get_tokens_peekable())
+    }
+
+    #[test]
+    fn test_nested_types() {
+        before_each();
+        let type_ = parse("map<string, list<(int, User?)>>?").unwrap_or_else(|err| {
+            panic!("Failed to parse type: {}", err.error_message);
+        });
+        assert_eq!(type_.span, 0..32);
+
+        let TypeExprType::Optional(map) = type_.type_ else {
+            panic!("Expected optional type");
+        };
+        let TypeExprType::Named(map) = map.type_ else {
+            panic!("Expected named type");
+        };
+        assert_eq!(map.name, "map");
+        assert_eq!(map.arguments.len(), 2);
+        assert_eq!(map.arguments[1].span, 12..30);
+
+        let TypeExprType::Named(list) = &map.arguments[1].type_ else {
+            panic!("Expected named type");
+        };
+        let TypeExprType::Tuple(tuple) = &list.arguments[0].type_ else {
+            panic!("Expected tuple type");
+        };
+        assert_eq!(tuple.len(), 2);
+        assert_eq!(tuple[1].span, 23..28);
+    }
+
+    #[test]
+    fn test_nested_optionals() {
+        before_each();
+        let type_ = parse("int??").unwrap_or_else(|err| {
+            panic!("Failed to parse type: {}", err.error_message);
+        });
+        assert_eq!(type_.span, 0..5);
+
+        let TypeExprType::Optional(inner) = type_.type_ else {
+            panic!("Expected optional type");
+        };
+        assert_eq!(inner.span, 0..4);
+        assert!(matches!(inner.type_, TypeExprType::Optional(_)));
+    }
+
+    #[test]
+    fn test_function_types() {
+        before_each();
+        let type_ = parse("fn(int, (string)) -> fn() -> bool").unwrap_or_else(|err| {
+            panic!("Failed to parse type: {}", err.error_message);
+        });
+        assert_eq!(type_.span, 0..33);
+
+        let TypeExprType::Function(function) = type_.type_ else {
+            panic!("Expected function type");
+        };
+        assert_eq!(function.parameters.len(), 2);
+        assert!(matches!(
+            function.parameters[1].type_,
+            TypeExprType::Named(_)
+        ));
+        assert_eq!(function.return_type.unwrap().span, 21..33);
+    }
+
+    #[test]
+    fn test_malformed_types() {
+        before_each();
+        for input in [
+            "list<int",
+            "map<string int>",
+            "list<>",
+            "(int, string",
+            "fn(int) ->",
+        ] {
+            let Err(error) = parse(input) else {
+                panic!("Expected '{}' to be malformed", input);
+            };
+            assert_eq!(error.error_code, ErrorCode::MalformedType, "{}", input);
+        }
+    }
+}
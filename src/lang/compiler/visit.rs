@@ -0,0 +1,509 @@
+//! Traversals over the AST, so passes only handle the nodes they care about.
+//!
+//! Each `visit_*` method of [`Visitor`] and [`VisitorMut`] defaults to the matching `walk_*`
+//! function, which visits the children of the node. An override that still wants the children
+//! visited calls the `walk_*` function itself. See [`super::fold::Fold`] for rewriting the tree.
+//!
+//! The walkers match every node variant without a wildcard, so a new variant only compiles
+//! once each walker handles it.
+
+use super::{
+    attribute::Attribute,
+    expression::{ClosureBody, Expression, ExpressionType},
+    parser::AST,
+    statement::{BlockStatement, FunctionStatement, Parameter, Statement, StatementType},
+};
+
+/// Visits the AST by reference. `'a` lets visitors keep references to the nodes they visit.
+pub(crate) trait Visitor<'a>: Sized {
+    fn visit_ast(&mut self, ast: &'a AST) {
+        walk_ast(self, ast)
+    }
+
+    fn visit_statement(&mut self, statement: &'a Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        walk_block(self, block)
+    }
+
+    fn visit_function(&mut self, function: &'a FunctionStatement) {
+        walk_function(self, function)
+    }
+
+    fn visit_parameter(&mut self, parameter: &'a Parameter) {
+        walk_parameter(self, parameter)
+    }
+
+    fn visit_attribute(&mut self, attribute: &'a Attribute) {
+        walk_attribute(self, attribute)
+    }
+}
+
+pub(crate) fn walk_ast<'a, V: Visitor<'a>>(visitor: &mut V, ast: &'a AST) {
+    for statement in &ast.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub(crate) fn walk_statement<'a, V: Visitor<'a>>(visitor: &mut V, statement: &'a Statement) {
+    match &statement.kind {
+        StatementType::Unknown | StatementType::Import(_) => {}
+        StatementType::Block(block) => visitor.visit_block(block),
+        StatementType::Const(const_) => {
+            for attribute in &const_.attributes {
+                visitor.visit_attribute(attribute);
+            }
+            visitor.visit_expression(&const_.value);
+        }
+        StatementType::Enum(enum_) => {
+            for attribute in &enum_.attributes {
+                visitor.visit_attribute(attribute);
+            }
+        }
+        StatementType::Expression(expression) => visitor.visit_expression(expression),
+        StatementType::For(for_) => {
+            visitor.visit_expression(&for_.iterable);
+            visitor.visit_block(&for_.body);
+        }
+        StatementType::Let(let_) => {
+            for attribute in &let_.attributes {
+                visitor.visit_attribute(attribute);
+            }
+            if let Some(value) = &let_.value {
+                visitor.visit_expression(value);
+            }
+        }
+        StatementType::Function(function) => visitor.visit_function(function),
+        StatementType::Return(return_) => {
+            if let Some(value) = &return_.value {
+                visitor.visit_expression(value);
+            }
+        }
+        StatementType::Intrinsic(intrinsic) => {
+            for argument in &intrinsic.arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        StatementType::Struct(struct_) => {
+            for attribute in &struct_.attributes {
+                visitor.visit_attribute(attribute);
+            }
+        }
+        StatementType::Try(try_) => {
+            visitor.visit_block(&try_.body);
+            visitor.visit_block(&try_.handler);
+        }
+    }
+}
+
+pub(crate) fn walk_expression<'a, V: Visitor<'a>>(visitor: &mut V, expression: &'a Expression) {
+    match &expression.kind {
+        ExpressionType::Assignment(assignment) => {
+            visitor.visit_expression(&assignment.target);
+            visitor.visit_expression(&assignment.value);
+        }
+        ExpressionType::Binary(binary) => {
+            visitor.visit_expression(&binary.left);
+            visitor.visit_expression(&binary.right);
+        }
+        ExpressionType::Block(block) => visitor.visit_block(block),
+        ExpressionType::Closure(closure) => {
+            for parameter in &closure.parameters {
+                visitor.visit_parameter(parameter);
+            }
+            match &closure.body {
+                ClosureBody::Expression(body) => visitor.visit_expression(body),
+                ClosureBody::Block(block) => visitor.visit_block(block),
+            }
+        }
+        ExpressionType::Coalesce(coalesce) => {
+            visitor.visit_expression(&coalesce.value);
+            visitor.visit_expression(&coalesce.default);
+        }
+        ExpressionType::FunctionCall(call) => {
+            visitor.visit_expression(&call.callee);
+            for argument in &call.arguments {
+                visitor.visit_expression(&argument.value);
+            }
+        }
+        ExpressionType::If(if_) => {
+            visitor.visit_expression(&if_.condition);
+            visitor.visit_block(&if_.then_branch);
+            if let Some(else_branch) = &if_.else_branch {
+                visitor.visit_expression(else_branch);
+            }
+        }
+        ExpressionType::Index(index) | ExpressionType::OptionalIndex(index) => {
+            visitor.visit_expression(&index.object);
+            visitor.visit_expression(&index.index);
+        }
+        ExpressionType::Intrinsic(intrinsic) => {
+            for argument in &intrinsic.arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ExpressionType::List(list) => {
+            for element in &list.elements {
+                visitor.visit_expression(element);
+            }
+        }
+        ExpressionType::Literal(_) => {}
+        ExpressionType::Match(match_) => {
+            visitor.visit_expression(&match_.value);
+            for arm in &match_.arms {
+                if let Some(guard) = &arm.guard {
+                    visitor.visit_expression(guard);
+                }
+                visitor.visit_expression(&arm.body);
+            }
+        }
+        ExpressionType::Member(member) | ExpressionType::OptionalMember(member) => {
+            visitor.visit_expression(&member.object)
+        }
+        ExpressionType::Propagate(propagate) => visitor.visit_expression(&propagate.value),
+        ExpressionType::Range(range) => {
+            for part in range.start.iter().chain(&range.end) {
+                visitor.visit_expression(part);
+            }
+        }
+        ExpressionType::Record(record) => {
+            for field in &record.fields {
+                visitor.visit_expression(&field.value);
+            }
+        }
+        ExpressionType::Struct(struct_) => {
+            for field in &struct_.fields {
+                visitor.visit_expression(&field.value);
+            }
+        }
+        ExpressionType::Unary(unary) => visitor.visit_expression(&unary.operand),
+        ExpressionType::Yield(yield_) => {
+            if let Some(value) = &yield_.value {
+                visitor.visit_expression(value);
+            }
+        }
+    }
+}
+
+pub(crate) fn walk_block<'a, V: Visitor<'a>>(visitor: &mut V, block: &'a BlockStatement) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+    if let Some(tail) = &block.tail {
+        visitor.visit_expression(tail);
+    }
+}
+
+pub(crate) fn walk_function<'a, V: Visitor<'a>>(visitor: &mut V, function: &'a FunctionStatement) {
+    for attribute in &function.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    for parameter in &function.parameters {
+        visitor.visit_parameter(parameter);
+    }
+    visitor.visit_block(&function.body);
+}
+
+pub(crate) fn walk_parameter<'a, V: Visitor<'a>>(visitor: &mut V, parameter: &'a Parameter) {
+    if let Some(default) = &parameter.default {
+        visitor.visit_expression(default);
+    }
+}
+
+pub(crate) fn walk_attribute<'a, V: Visitor<'a>>(visitor: &mut V, attribute: &'a Attribute) {
+    for argument in &attribute.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+/// Visits the AST by mutable reference, to change nodes in place.
+pub(crate) trait VisitorMut: Sized {
+    fn visit_ast_mut(&mut self, ast: &mut AST) {
+        walk_ast_mut(self, ast)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockStatement) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut FunctionStatement) {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_parameter_mut(&mut self, parameter: &mut Parameter) {
+        walk_parameter_mut(self, parameter)
+    }
+
+    fn visit_attribute_mut(&mut self, attribute: &mut Attribute) {
+        walk_attribute_mut(self, attribute)
+    }
+}
+
+pub(crate) fn walk_ast_mut<V: VisitorMut>(visitor: &mut V, ast: &mut AST) {
+    for statement in &mut ast.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub(crate) fn walk_statement_mut<V: VisitorMut>(visitor: &mut V, statement: &mut Statement) {
+    match &mut statement.kind {
+        StatementType::Unknown | StatementType::Import(_) => {}
+        StatementType::Block(block) => visitor.visit_block_mut(block),
+        StatementType::Const(const_) => {
+            for attribute in &mut const_.attributes {
+                visitor.visit_attribute_mut(attribute);
+            }
+            visitor.visit_expression_mut(&mut const_.value);
+        }
+        StatementType::Enum(enum_) => {
+            for attribute in &mut enum_.attributes {
+                visitor.visit_attribute_mut(attribute);
+            }
+        }
+        StatementType::Expression(expression) => visitor.visit_expression_mut(expression),
+        StatementType::For(for_) => {
+            visitor.visit_expression_mut(&mut for_.iterable);
+            visitor.visit_block_mut(&mut for_.body);
+        }
+        StatementType::Let(let_) => {
+            for attribute in &mut let_.attributes {
+                visitor.visit_attribute_mut(attribute);
+            }
+            if let Some(value) = &mut let_.value {
+                visitor.visit_expression_mut(value);
+            }
+        }
+        StatementType::Function(function) => visitor.visit_function_mut(function),
+        StatementType::Return(return_) => {
+            if let Some(value) = &mut return_.value {
+                visitor.visit_expression_mut(value);
+            }
+        }
+        StatementType::Intrinsic(intrinsic) => {
+            for argument in &mut intrinsic.arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        StatementType::Struct(struct_) => {
+            for attribute in &mut struct_.attributes {
+                visitor.visit_attribute_mut(attribute);
+            }
+        }
+        StatementType::Try(try_) => {
+            visitor.visit_block_mut(&mut try_.body);
+            visitor.visit_block_mut(&mut try_.handler);
+        }
+    }
+}
+
+pub(crate) fn walk_expression_mut<V: VisitorMut>(visitor: &mut V, expression: &mut Expression) {
+    match &mut expression.kind {
+        ExpressionType::Assignment(assignment) => {
+            visitor.visit_expression_mut(&mut assignment.target);
+            visitor.visit_expression_mut(&mut assignment.value);
+        }
+        ExpressionType::Binary(binary) => {
+            visitor.visit_expression_mut(&mut binary.left);
+            visitor.visit_expression_mut(&mut binary.right);
+        }
+        ExpressionType::Block(block) => visitor.visit_block_mut(block),
+        ExpressionType::Closure(closure) => {
+            for parameter in &mut closure.parameters {
+                visitor.visit_parameter_mut(parameter);
+            }
+            match &mut closure.body {
+                ClosureBody::Expression(body) => visitor.visit_expression_mut(body),
+                ClosureBody::Block(block) => visitor.visit_block_mut(block),
+            }
+        }
+        ExpressionType::Coalesce(coalesce) => {
+            visitor.visit_expression_mut(&mut coalesce.value);
+            visitor.visit_expression_mut(&mut coalesce.default);
+        }
+        ExpressionType::FunctionCall(call) => {
+            visitor.visit_expression_mut(&mut call.callee);
+            for argument in &mut call.arguments {
+                visitor.visit_expression_mut(&mut argument.value);
+            }
+        }
+        ExpressionType::If(if_) => {
+            visitor.visit_expression_mut(&mut if_.condition);
+            visitor.visit_block_mut(&mut if_.then_branch);
+            if let Some(else_branch) = &mut if_.else_branch {
+                visitor.visit_expression_mut(else_branch);
+            }
+        }
+        ExpressionType::Index(index) | ExpressionType::OptionalIndex(index) => {
+            visitor.visit_expression_mut(&mut index.object);
+            visitor.visit_expression_mut(&mut index.index);
+        }
+        ExpressionType::Intrinsic(intrinsic) => {
+            for argument in &mut intrinsic.arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        ExpressionType::List(list) => {
+            for element in &mut list.elements {
+                visitor.visit_expression_mut(element);
+            }
+        }
+        ExpressionType::Literal(_) => {}
+        ExpressionType::Match(match_) => {
+            visitor.visit_expression_mut(&mut match_.value);
+            for arm in &mut match_.arms {
+                if let Some(guard) = &mut arm.guard {
+                    visitor.visit_expression_mut(guard);
+                }
+                visitor.visit_expression_mut(&mut arm.body);
+            }
+        }
+        ExpressionType::Member(member) | ExpressionType::OptionalMember(member) => {
+            visitor.visit_expression_mut(&mut member.object)
+        }
+        ExpressionType::Propagate(propagate) => visitor.visit_expression_mut(&mut propagate.value),
+        ExpressionType::Range(range) => {
+            for part in range.start.iter_mut().chain(&mut range.end) {
+                visitor.visit_expression_mut(part);
+            }
+        }
+        ExpressionType::Record(record) => {
+            for field in &mut record.fields {
+                visitor.visit_expression_mut(&mut field.value);
+            }
+        }
+        ExpressionType::Struct(struct_) => {
+            for field in &mut struct_.fields {
+                visitor.visit_expression_mut(&mut field.value);
+            }
+        }
+        ExpressionType::Unary(unary) => visitor.visit_expression_mut(&mut unary.operand),
+        ExpressionType::Yield(yield_) => {
+            if let Some(value) = &mut yield_.value {
+                visitor.visit_expression_mut(value);
+            }
+        }
+    }
+}
+
+pub(crate) fn walk_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut BlockStatement) {
+    for statement in &mut block.statements {
+        visitor.visit_statement_mut(statement);
+    }
+    if let Some(tail) = &mut block.tail {
+        visitor.visit_expression_mut(tail);
+    }
+}
+
+pub(crate) fn walk_function_mut<V: VisitorMut>(visitor: &mut V, function: &mut FunctionStatement) {
+    for attribute in &mut function.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    for parameter in &mut function.parameters {
+        visitor.visit_parameter_mut(parameter);
+    }
+    visitor.visit_block_mut(&mut function.body);
+}
+
+pub(crate) fn walk_parameter_mut<V: VisitorMut>(visitor: &mut V, parameter: &mut Parameter) {
+    if let Some(default) = &mut parameter.default {
+        visitor.visit_expression_mut(default);
+    }
+}
+
+pub(crate) fn walk_attribute_mut<V: VisitorMut>(visitor: &mut V, attribute: &mut Attribute) {
+    for argument in &mut attribute.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::compiler::{
+        expression::{LiteralExpression, LiteralType},
+        lexer::Lexer,
+        node::NodeIds,
+        parser::Parser,
+    };
+    use crate::tests::before_each;
+
+    fn parse(input: &str) -> AST {
+        let mut lexer = Lexer::new();
+        lexer.lex(input);
+        Parser::parse(&mut lexer.get_tokens_peekable(), &mut NodeIds::default())
+            .unwrap_or_else(|err| panic!("Failed to parse: {}", err.error_message))
+    }
+
+    /// Collects identifiers in the order they are visited.
+    struct Identifiers<'a>(Vec<&'a str>);
+
+    impl<'a> Visitor<'a> for Identifiers<'a> {
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            if let ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) = &expression.kind
+            {
+                self.0.push(value);
+            }
+            walk_expression(self, expression);
+        }
+    }
+
+    /// Renames every identifier in place.
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            if let ExpressionType::Literal(LiteralExpression {
+                literal: LiteralType::Unknown,
+                value,
+            }) = &mut expression.kind
+            {
+                *value = value.to_uppercase();
+            }
+            walk_expression_mut(self, expression);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        before_each();
+        let input = "
+            #[deprecated(reason)]
+            function f(x = d) { let y = if c { x } else { [z] }; return g(y, f: |q| q + w); }
+            for i in xs { @print(i?.v); }
+        ";
+        let ast = parse(input);
+
+        let mut identifiers = Identifiers(Vec::new());
+        identifiers.visit_ast(&ast);
+        assert_eq!(
+            identifiers.0,
+            vec!["reason", "d", "c", "x", "z", "g", "y", "q", "w", "xs", "i"]
+        );
+
+        let mut ast = parse(input);
+        Rename.visit_ast_mut(&mut ast);
+        let mut identifiers = Identifiers(Vec::new());
+        identifiers.visit_ast(&ast);
+        assert_eq!(
+            identifiers.0,
+            vec!["REASON", "D", "C", "X", "Z", "G", "Y", "Q", "W", "XS", "I"]
+        );
+    }
+}
@@ -1,30 +1,53 @@
-use crate::lang::compiler::CompilerError;
+use crate::lang::compiler::{macros::Expansion, CompilerError};
 
 #[derive(Clone)]
 pub(crate) struct ErrorLogger<'a> {
     filename: &'a str,
     input: &'a str,
+    expansions: Vec<Expansion>,
 }
 
 impl<'a> ErrorLogger<'a> {
     pub(crate) fn new(filename:&'a str , input: &'a str) -> Self {
-        ErrorLogger { filename, input }
+        ErrorLogger { filename, input, expansions: Vec::new() }
     }
 
-    pub(crate) fn report<'b>(&'a self, error_detail: &'b CompilerError) -> () {
+    /// Reports errors in expanded macro code at the call site, pointing into the macro definition as well.
+    pub(crate) fn with_expansions(mut self, expansions: Vec<Expansion>) -> Self {
+        self.expansions = expansions;
+        self
+    }
+
+    pub(crate) fn report(&self, error_detail: &CompilerError) {
         use ariadne::{ColorGenerator, Label, Report, ReportKind, Source};
         let mut colors = ColorGenerator::new();
         let color_1 = colors.next();
 
-        let mut report = Report::build(ReportKind::Error, self.filename, 0)
-            .with_code(&error_detail.error_code)
+        let kind = if error_detail.error_code.is_warning() {
+            ReportKind::Warning
+        } else {
+            ReportKind::Error
+        };
+
+        let (span, traces) = Expansion::resolve(&self.expansions, &error_detail.token.span);
+
+        let mut report = Report::build(kind, self.filename, 0)
+            .with_code(error_detail.error_code)
             .with_message(&error_detail.error_message)
             .with_label(
-                Label::new((self.filename, error_detail.token.span.clone()))
+                Label::new((self.filename, span))
                     .with_message(&error_detail.span_message)
                     .with_color(color_1),
             );
 
+        for (name, definition) in traces {
+            report = report.with_label(
+                Label::new((self.filename, definition))
+                    .with_message(format!("In this expansion of '{}!'", name))
+                    .with_color(colors.next()),
+            );
+        }
+
         if let Some(help) = &error_detail.help {
             report = report.with_help(help);
         }
@@ -39,7 +62,7 @@ impl<'a> ErrorLogger<'a> {
             .unwrap();
     }
 
-    pub(crate) fn report_many(&self, errors: &[CompilerError]) -> () {
+    pub(crate) fn report_many(&self, errors: &[CompilerError]) {
         for error in errors {
             self.report(error);
         }
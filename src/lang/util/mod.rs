@@ -1,2 +1,3 @@
 pub(crate) mod vec;
-pub(crate) mod error_logger;
\ No newline at end of file
+pub(crate) mod error_logger;
+pub(crate) mod suggest;
\ No newline at end of file
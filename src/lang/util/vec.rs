@@ -33,9 +33,11 @@ where
 }
 
 pub(crate) trait UnshiftExpect<T, K, E> {
-    fn unshift_expect(&mut self, expected: K) -> CompilerResult<&mut T>;
-    fn unshift_expect_any(&mut self, expected: &[K]) -> CompilerResult<&mut T>;
-    fn unshift_if(&mut self, expected: K) -> Option<&mut T>;
+    fn unshift_expect(&mut self, expected: K) -> CompilerResult<&T>;
+    fn unshift_expect_any(&mut self, expected: &[K]) -> CompilerResult<&T>;
+    fn unshift_if(&mut self, expected: K) -> Option<&T>;
     fn next_matches(&mut self, expected: K) -> bool;
     fn next_matches_any(&mut self, expected: &[K]) -> bool;
+    /// Looks `n` elements ahead without consuming anything, where `0` is the next element.
+    fn nth_matches(&mut self, n: usize, expected: K) -> bool;
 }
@@ -1,3 +1,4 @@
+
 use clap::Parser;
 
 pub(crate) mod lang;
@@ -7,20 +8,58 @@ fn main() {
 
     // Load file from command line argument
     let args = args::Args::parse();
-    let input = std::fs::read_to_string(&args.input).unwrap_or_else(|err| {
+    if let Some(document) = args.schema {
+        println!("{}", serde_json::to_string_pretty(&document.schema()).unwrap());
+        return;
+    }
+
+    let path = args.from_ast.as_ref().unwrap_or(&args.input);
+    let input = std::fs::read_to_string(path).unwrap_or_else(|err| {
         panic!("Error reading file: {}", err);
     });
-    
-    // Compile file
-    let mut compiler = lang::compiler::Compiler::new(&input, &args.input);
 
-    let ast = compiler.compile().unwrap_or_else(|err| {
+    // Compile file, or the AST read from it. Without the source, diagnostics can't show the code.
+    let source = if args.from_ast.is_some() { "" } else { input.as_str() };
+
+    // Print tokens
+    if args.tokens {
+        let mut lexer = lang::compiler::lexer::Lexer::new();
+        lexer.lex(source);
+        let tokens = lang::compiler::schema::Tokens {
+            tokens: lexer.get_tokens_peekable().cloned().collect(),
+        };
+        println!("{}", tokens.to_json());
+    }
+
+    // Print concrete syntax tree
+    if args.cst {
+        let parse = lang::compiler::cst::parse(source);
+        print!("{:?}\n{}", parse.syntax(), parse.root().outline());
+        lang::util::error_logger::ErrorLogger::new(path, source)
+            .with_expansions(parse.expansions)
+            .report_many(&parse.errors);
+    }
+
+    let mut compiler = lang::compiler::Compiler::new(source, path);
+    compiler.cfg = lang::compiler::cfg::Cfg::from_flags(&args.cfg);
+
+    let result = match &args.from_ast {
+        Some(_) => {
+            let ast = lang::compiler::parser::AST::from_json(&input).unwrap_or_else(|err| {
+                panic!("Error reading AST: {}", err);
+            });
+            compiler.compile_ast(ast)
+        }
+        None => compiler.compile(),
+    };
+
+    let ast = result.unwrap_or_else(|err| {
         panic!("Error compiling file: {}", err);
     });
 
     // Print AST
     if args.debug {
-        println!("{}", serde_json::to_string_pretty(&ast).unwrap());
+        println!("{}", ast.to_json());
     }
 }
 
@@ -52,13 +91,13 @@ mod tests {
         let test_dir = Path::new("tests/e2e");
         let test_files = fs::read_dir(test_dir).unwrap().collect::<Vec<_>>();
 
-        assert!(&test_files.len() > &0, "No test files found");
+        assert!(!test_files.is_empty(), "No test files found");
 
         for file in test_files {
             let file = file.unwrap();
             let path = file.path();
-            let file_name = path.file_name().unwrap().to_str().unwrap();
-            let file_ext = path.extension().unwrap().to_str().unwrap();
+            let file_name = path.to_str().unwrap();
+            let file_ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
 
             if file_ext == "lit" {
                 debug!("Testing file: {}", file_name);
@@ -68,7 +107,7 @@ mod tests {
                 let ast = compiler.compile().unwrap_or_else(|err| {
                     panic!("Error compiling file: {}", err);
                 });
-                debug!("Output: {}", serde_json::to_string_pretty(&ast).unwrap());
+                debug!("Output: {}", ast.to_json());
 
             } else {
                 debug!("Skipping file: {} (not a lithanium source file)", file_name);